@@ -0,0 +1,26 @@
+use anyhow::Result;
+
+use crossterm_prompt::{prompt, Completion, PromptOptions};
+
+fn main() -> Result<()> {
+    #[cfg(feature = "panic")]
+    crossterm_prompt::stdout_panic_hook();
+
+    let mut stdout = std::io::stdout();
+    let options = PromptOptions::new().completion(Completion {
+        complete: Box::new(|_, words| {
+            if words.is_empty() {
+                vec!["cat".to_string(), "cd".to_string(), "echo".to_string()]
+            } else {
+                vec![
+                    "my file.txt".to_string(),
+                    "my files/".to_string(),
+                    "it's here".to_string(),
+                ]
+            }
+        }),
+    });
+    let value = prompt("Press Tab to complete: ", &mut stdout, &options)?;
+    println!("value: {}", value);
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use anyhow::Result;
+use std::thread;
+use std::time::Duration;
+
+use crossterm_prompt::{prompt, ExternalPrinter, PromptOptions};
+
+fn main() -> Result<()> {
+    #[cfg(feature = "panic")]
+    crossterm_prompt::stdout_panic_hook();
+
+    let printer = ExternalPrinter::new();
+    let logger = printer.clone();
+    thread::spawn(move || {
+        for count in 1.. {
+            thread::sleep(Duration::from_secs(1));
+            logger.print(format!("log line {}", count));
+        }
+    });
+
+    let mut stdout = std::io::stdout();
+    let options = PromptOptions::new().external_printer(printer);
+    let value = prompt("Type while logging: ", &mut stdout, &options)?;
+    println!("value: {}", value);
+    Ok(())
+}
@@ -1,28 +1,41 @@
 use anyhow::Result;
-use crossterm_prompt::{history::MemoryHistory, shell, PromptOptions};
-use std::sync::Mutex;
+use crossterm_prompt::{history::HistoryRegistry, shell, PromptOptions};
+use std::cell::Cell;
 
 #[derive(thiserror::Error, Debug)]
 enum Error {}
 
 fn main() -> Result<()> {
+    #[cfg(feature = "panic")]
     crossterm_prompt::stdout_panic_hook();
 
     let mut stdout = std::io::stdout();
-    let history = Box::new(Mutex::new(MemoryHistory::new(Default::default())));
-    let options = PromptOptions::new().history(history);
+    let mut registry = HistoryRegistry::new();
+    let shell_options = PromptOptions::new()
+        .history(registry.get_or_create("shell", Default::default()))
+        .history_expansion(Default::default());
+    let sql_options = PromptOptions::new()
+        .history(registry.get_or_create("sql", Default::default()));
+    let sql_mode = Cell::new(false);
 
-    println!(r#"Welcome, type "q" or "quit" to exit"#);
+    println!(r#"Welcome, type "sql" to switch history, "q" or "quit" to exit"#);
 
     shell(
-        || "shell> ",
+        |_| if sql_mode.get() { "sql> " } else { "shell> " },
         &mut stdout,
-        || &options,
+        |_| {
+            if sql_mode.get() {
+                &sql_options
+            } else {
+                &shell_options
+            }
+        },
         |command| {
             match &command[..] {
                 "q" | "quit" => {
                     std::process::exit(0);
                 }
+                "sql" => sql_mode.set(!sql_mode.get()),
                 _ => {}
             }
             Ok::<(), Error>(())
@@ -3,6 +3,7 @@ use anyhow::Result;
 use crossterm_prompt::{prompt, PromptOptions};
 
 fn main() -> Result<()> {
+    #[cfg(feature = "panic")]
     crossterm_prompt::stdout_panic_hook();
 
     let mut stdout = std::io::stdout();
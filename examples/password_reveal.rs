@@ -0,0 +1,23 @@
+use anyhow::Result;
+use std::time::Duration;
+
+use crossterm_prompt::{prompt, PassWord, PromptOptions};
+
+fn main() -> Result<()> {
+    #[cfg(feature = "panic")]
+    crossterm_prompt::stdout_panic_hook();
+
+    let mut stdout = std::io::stdout();
+    let options = PromptOptions::new().password(PassWord {
+        mask_after: Some(Duration::from_secs(3)),
+        show_typed: Some(Duration::from_millis(500)),
+        ..Default::default()
+    });
+    let value = prompt(
+        "Enter a password (Alt+r to reveal): ",
+        &mut stdout,
+        &options,
+    )?;
+    println!("password: {}", value);
+    Ok(())
+}
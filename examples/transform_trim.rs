@@ -4,6 +4,7 @@ use std::borrow::Cow;
 use crossterm_prompt::{prompt, PromptOptions, Transformer};
 
 fn main() -> Result<()> {
+    #[cfg(feature = "panic")]
     crossterm_prompt::stdout_panic_hook();
 
     let mut stdout = std::io::stdout();
@@ -3,16 +3,16 @@ use anyhow::Result;
 use crossterm_prompt::{prompt, PromptOptions, Validation};
 
 fn main() -> Result<()> {
+    #[cfg(feature = "panic")]
     crossterm_prompt::stdout_panic_hook();
 
     let mut stdout = std::io::stdout();
     let options = PromptOptions::new().validation(Validation {
         validate: Box::new(|s| {
             if s == "world" {
-                true
+                Ok(())
             } else {
-                println!(r#"invalid value, type "world"!"#);
-                false
+                Err(r#"invalid value, type "world"!"#.to_string())
             }
         }),
     });
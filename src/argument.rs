@@ -0,0 +1,78 @@
+//! Numeric arguments that repeat actions.
+
+/// Largest count an argument may repeat an action.
+///
+/// Each repetition runs the action on its own so the count
+/// is kept small enough for the prompt to stay responsive.
+const MAX_COUNT: usize = 1_000;
+
+/// Numeric argument typed before an action.
+#[derive(Debug, Default)]
+pub(crate) struct Argument {
+    count: Option<usize>,
+    digits: bool,
+}
+
+impl Argument {
+    /// Determine if an argument is being typed.
+    pub fn is_active(&self) -> bool {
+        self.count.is_some()
+    }
+
+    /// Add a digit to the argument.
+    pub fn digit(&mut self, digit: u8) {
+        let count = match (self.count, self.digits) {
+            (Some(count), true) => count * 10 + digit as usize,
+            _ => digit as usize,
+        };
+        self.count = Some(count.min(MAX_COUNT));
+        self.digits = true;
+    }
+
+    /// Multiply the argument by four, starting at four.
+    ///
+    /// Ignored once digits have been typed.
+    pub fn universal(&mut self) {
+        if !self.digits {
+            let count = self.count.map_or(4, |count| count * 4);
+            self.count = Some(count.min(MAX_COUNT));
+        }
+    }
+
+    /// Take the number of times to repeat an action.
+    pub fn take(&mut self) -> usize {
+        self.digits = false;
+        self.count.take().unwrap_or(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argument_count() {
+        let mut argument = Argument::default();
+        assert!(!argument.is_active());
+        assert_eq!(1, argument.take());
+
+        argument.digit(1);
+        argument.digit(2);
+        assert!(argument.is_active());
+        argument.universal();
+        assert_eq!(12, argument.take());
+
+        argument.universal();
+        argument.universal();
+        assert_eq!(16, argument.take());
+
+        argument.universal();
+        argument.digit(3);
+        assert_eq!(3, argument.take());
+
+        for _ in 0..4 {
+            argument.digit(9);
+        }
+        assert_eq!(MAX_COUNT, argument.take());
+    }
+}
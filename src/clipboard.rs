@@ -0,0 +1,53 @@
+//! Copying text to the system clipboard.
+//!
+//! Text is sent to the terminal using the OSC 52 escape sequence,
+//! terminals that do not support the sequence ignore it.
+use anyhow::Result;
+use std::io::Write;
+
+/// Characters of the standard base64 alphabet.
+const ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Ask the terminal to copy text to the clipboard.
+pub(crate) fn copy<W: Write>(writer: &mut W, text: &str) -> Result<()> {
+    write!(writer, "\x1B]52;c;{}\x07", base64(text.as_bytes()))?;
+    Ok(())
+}
+
+/// Encode bytes as base64 with padding.
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |value, (i, b)| value | (*b as u32) << (16 - i * 8));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (value >> (18 - i * 6)) & 0x3F;
+                encoded.push(ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_sequence() -> Result<()> {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9vYmFy", base64(b"foobar"));
+        let mut writer = Vec::new();
+        copy(&mut writer, "ls -l")?;
+        assert_eq!(b"\x1B]52;c;bHMgLWw=\x07", &writer[..]);
+        Ok(())
+    }
+}
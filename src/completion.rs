@@ -0,0 +1,346 @@
+//! Completion engine.
+//!
+//! The word before the cursor is found using the shell tokenizer
+//! so quotes and escapes are removed before matching against the
+//! candidates and the replacement is escaped again for insertion.
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    style::Print,
+    terminal::{size, Clear, ClearType},
+    QueueableCommand,
+};
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::key_binding::{KeyAction, KeyBindings};
+use crate::message::{clear as close, make_room};
+use crate::mode::{Mode, ModeContext, ModeEvent};
+use crate::options::{Completion, CursorStyles};
+use crate::shell::{escape, tokenize};
+use crate::style::{write_styled, CursorStyle};
+use crate::terminal_buffer::{text_width, TerminalBuffer};
+
+/// Result of completing the word before the cursor.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct Completed {
+    /// Byte offset of the start of the raw word being replaced.
+    pub start: usize,
+    /// Text to replace the raw word with.
+    pub replacement: String,
+    /// Candidates matching the unescaped word.
+    pub candidates: Vec<String>,
+    /// Quote character open at the start of the word.
+    pub quote: Option<char>,
+}
+
+/// Menu of candidates shown below the prompt.
+///
+/// While the menu is open typing narrows the candidates, Tab and
+/// the arrow keys change the selection, Enter inserts the selected
+/// candidate and Esc closes the menu.
+pub(crate) struct Menu {
+    candidates: Vec<String>,
+    selected: usize,
+    keymap: KeyBindings,
+}
+
+impl<W: Write> Mode<W> for Menu {
+    fn keymap(&self) -> &KeyBindings {
+        &self.keymap
+    }
+
+    fn cursor_style(&self, styles: &CursorStyles) -> Option<CursorStyle> {
+        styles.menu
+    }
+
+    fn enter(&mut self, ctx: &mut ModeContext<'_, '_, W>) -> Result<()> {
+        make_room(ctx.writer, ctx.buf)?;
+        self.draw(ctx.writer, ctx.buf)
+    }
+
+    fn exit(&mut self, ctx: &mut ModeContext<'_, '_, W>) -> Result<()> {
+        close(ctx.writer, ctx.buf)
+    }
+
+    fn handle(
+        &mut self,
+        ctx: &mut ModeContext<'_, '_, W>,
+        action: Option<KeyAction>,
+    ) -> Result<ModeEvent> {
+        let completion = match &ctx.options.completion {
+            Some(completion) => completion,
+            None => return Ok(ModeEvent::Forward),
+        };
+        match action {
+            Some(KeyAction::SelectNext) => {
+                self.selected = (self.selected + 1) % self.candidates.len();
+            }
+            Some(KeyAction::SelectPrevious) => {
+                self.selected = self
+                    .selected
+                    .checked_sub(1)
+                    .unwrap_or(self.candidates.len() - 1);
+            }
+            Some(KeyAction::AcceptSelection) => {
+                let (before, _) = ctx.buf.split_at_cursor();
+                if let Some(completed) = complete_line(&before, completion) {
+                    let candidate = &self.candidates[self.selected];
+                    ctx.buf.replace_before_cursor(
+                        ctx.writer,
+                        completed.start,
+                        &finish(candidate, completed.quote),
+                    )?;
+                }
+                return Ok(ModeEvent::Exit);
+            }
+            Some(KeyAction::CancelMode) => return Ok(ModeEvent::Exit),
+            Some(KeyAction::WriteChar(c)) => {
+                ctx.buf.write_char(ctx.writer, c)?;
+                return self.filter(ctx.writer, ctx.buf, completion);
+            }
+            Some(KeyAction::EraseCharacter) => {
+                ctx.buf.erase_before(ctx.writer, 1)?;
+                return self.filter(ctx.writer, ctx.buf, completion);
+            }
+            _ => return Ok(ModeEvent::Forward),
+        }
+        self.draw(ctx.writer, ctx.buf)?;
+        Ok(ModeEvent::Consumed)
+    }
+}
+
+impl Menu {
+    /// Narrow the candidates to those matching the word before
+    /// the cursor, closing the menu when none are left.
+    fn filter<W>(
+        &mut self,
+        writer: &mut W,
+        buf: &TerminalBuffer,
+        completion: &Completion,
+    ) -> Result<ModeEvent>
+    where
+        W: Write,
+    {
+        let (before, _) = buf.split_at_cursor();
+        match complete_line(&before, completion) {
+            Some(completed) => {
+                let selected = &self.candidates[self.selected];
+                self.selected = completed
+                    .candidates
+                    .iter()
+                    .position(|c| c == selected)
+                    .unwrap_or(0);
+                self.candidates = completed.candidates;
+                self.draw(writer, buf)?;
+                Ok(ModeEvent::Consumed)
+            }
+            None => Ok(ModeEvent::Exit),
+        }
+    }
+
+    /// Draw the menu on the line below the prompt.
+    fn draw<W>(&self, writer: &mut W, buf: &TerminalBuffer) -> Result<()>
+    where
+        W: Write,
+    {
+        let (width, _) = size()?;
+        let width = width as usize;
+        let widths: Vec<usize> =
+            self.candidates.iter().map(|c| text_width(c) + 2).collect();
+
+        // Scroll the candidates so the selection is visible
+        let mut first = 0;
+        while first < self.selected
+            && widths[first..=self.selected].iter().sum::<usize>() > width
+        {
+            first += 1;
+        }
+
+        writer.queue(cursor::SavePosition)?;
+        writer.queue(cursor::MoveToNextLine(buf.rows_below()))?;
+        writer.queue(Clear(ClearType::CurrentLine))?;
+        let theme = buf.theme();
+        let mut used = 0;
+        for (index, candidate) in self.candidates.iter().enumerate().skip(first)
+        {
+            used += widths[index];
+            if used > width {
+                break;
+            }
+            let style = match index == self.selected {
+                true => &theme.menu_selected,
+                false => &theme.menu,
+            };
+            write_styled(writer, style, candidate)?;
+            writer.queue(Print("  "))?;
+        }
+        writer.queue(cursor::RestorePosition)?;
+        Ok(())
+    }
+}
+
+/// Find the candidates and replacement for the end of a line.
+pub(crate) fn complete_line(
+    line: &str,
+    completion: &Completion,
+) -> Option<Completed> {
+    let mut words = tokenize(line);
+    let (start, word, quote) = match words.last() {
+        Some(token) if token.end == line.len() => {
+            let token = words.pop().unwrap();
+            (token.start, token.value, token.quote)
+        }
+        _ => (line.len(), String::new(), None),
+    };
+
+    let words: Vec<String> = words.into_iter().map(|t| t.value).collect();
+    // Duplicates are dropped keeping the order of the completer
+    let mut seen = HashSet::new();
+    let candidates: Vec<String> = (completion.complete)(&word, &words)
+        .into_iter()
+        .filter(|c| c.starts_with(&word) && seen.insert(c.clone()))
+        .collect();
+
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let replacement = if candidates.len() == 1 {
+        finish(&candidates[0], quote)
+    } else {
+        quoted(common_prefix(&candidates), quote)
+    };
+
+    Some(Completed {
+        start,
+        replacement,
+        candidates,
+        quote,
+    })
+}
+
+/// Complete the word before the cursor.
+///
+/// When there is nothing left to insert and there are several
+/// candidates a menu is returned to be pushed as a mode.
+pub(crate) fn complete<W>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer,
+    completion: &Completion,
+) -> Result<Option<Menu>>
+where
+    W: Write,
+{
+    let (before, _) = buf.split_at_cursor();
+    if let Some(completed) = complete_line(&before, completion) {
+        if before[completed.start..] != completed.replacement {
+            buf.replace_before_cursor(
+                writer,
+                completed.start,
+                &completed.replacement,
+            )?;
+        } else if completed.candidates.len() > 1 {
+            return Ok(Some(Menu {
+                candidates: completed.candidates,
+                selected: 0,
+                keymap: KeyBindings::completion_menu(),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// Write a complete candidate closing any open quote.
+fn finish(candidate: &str, quote: Option<char>) -> String {
+    let mut value = quoted(candidate, quote);
+    if !candidate.ends_with('/') {
+        if let Some(quote) = quote {
+            value.push(quote);
+        }
+        value.push(' ');
+    }
+    value
+}
+
+/// Write a word in the given quoting style.
+fn quoted(word: &str, quote: Option<char>) -> String {
+    match quote {
+        Some(quote) => format!("{}{}", quote, escape(word, Some(quote))),
+        None => escape(word, None),
+    }
+}
+
+/// Longest prefix shared by all the candidates.
+fn common_prefix(candidates: &[String]) -> &str {
+    let first = &candidates[0];
+    let mut end = first.len();
+    for candidate in &candidates[1..] {
+        end = first
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map(|((i, _), _)| i)
+            .unwrap_or_else(|| first.len().min(candidate.len()))
+            .min(end);
+    }
+    &first[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Completion {
+        Completion {
+            complete: Box::new(|_, _| {
+                vec![
+                    "my file.txt".to_string(),
+                    "my files/".to_string(),
+                    "it's".to_string(),
+                ]
+            }),
+        }
+    }
+
+    fn replace(line: &str) -> String {
+        let completed = complete_line(line, &files()).unwrap();
+        format!("{}{}", &line[..completed.start], completed.replacement)
+    }
+
+    #[test]
+    fn complete_unquoted() {
+        assert_eq!(r"cat my\ file", replace("cat m"));
+        assert_eq!(r"cat my\ file.txt ", replace(r"cat my\ file."));
+        assert_eq!(r"cat it\'s ", replace("cat i"));
+    }
+
+    #[test]
+    fn complete_quoted() {
+        assert_eq!(r#"cat "my file.txt" "#, replace(r#"cat "my file."#));
+        assert_eq!("cat 'my files/", replace("cat 'my files"));
+        assert_eq!(r"cat 'it'\''s' ", replace("cat 'it"));
+    }
+
+    #[test]
+    fn complete_no_match() {
+        assert_eq!(None, complete_line("cat x", &files()));
+        assert_eq!(
+            3,
+            complete_line("cat ", &files()).unwrap().candidates.len()
+        );
+    }
+
+    #[test]
+    fn complete_duplicates() {
+        let completion = Completion {
+            complete: Box::new(|_, _| {
+                ["b.txt", "a.txt", "b.txt", "a.txt"]
+                    .map(String::from)
+                    .to_vec()
+            }),
+        };
+        let completed = complete_line("cat ", &completion).unwrap();
+        assert_eq!(vec!["b.txt", "a.txt"], completed.candidates);
+    }
+}
@@ -0,0 +1,88 @@
+//! Overlay describing the state of the prompt.
+//!
+//! Shows the active options, cursor indices and where the
+//! line will wrap which helps when diagnosing layout bugs,
+//! followed by the text of the application set with
+//! [PromptOptions::debug_info].
+use crate::options::PromptOptions;
+use crate::terminal_buffer::TerminalBuffer;
+
+/// Describe the options and buffer state on a single line
+/// truncated to the terminal width.
+pub(crate) fn overlay(options: &PromptOptions, buf: &TerminalBuffer) -> String {
+    #[allow(unused_mut)]
+    let mut flags: Vec<(&str, bool)> = [
+        ("password", options.password.is_some()),
+        ("multiline", options.multiline.is_some()),
+        ("required", options.required.is_some()),
+        ("validation", options.validation.is_some()),
+        ("transformer", options.transformer.is_some()),
+    ]
+    .into();
+    #[cfg(feature = "history")]
+    flags.push(("history", options.history.is_some()));
+    #[cfg(feature = "completion")]
+    flags.push(("completion", options.completion.is_some()));
+
+    let flags: Vec<String> = flags
+        .into_iter()
+        .map(|(name, enabled)| {
+            format!("{}={}", name, if enabled { "on" } else { "off" })
+        })
+        .collect();
+
+    let (column, row) = buf.position();
+    let (width, height) = buf.size();
+    let (before, _) = buf.split_at_cursor();
+    let remainder = (width as usize).saturating_sub(buf.prefix_columns());
+
+    let mut line = format!(
+        "keymap={} bindings | {} | cursor={} byte={} col={} row={} \
+         prefix_cols={} buffer_cols={} | size={}x{} wrap_at={}",
+        options.bindings.borrow().len(),
+        flags.join(" "),
+        buf.cursor(),
+        before.len(),
+        column,
+        row,
+        buf.prefix_columns(),
+        buf.buffer_columns(),
+        width,
+        height,
+        remainder,
+    );
+    if let Some(handler) = &options.debug_info {
+        line.push_str(" | ");
+        line.push_str(&(handler)(buf));
+    }
+    line.chars().take(width as usize).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlay_text() {
+        let options = PromptOptions::new()
+            .password(Default::default())
+            .debug_info(|buf| {
+                format!("words={}", buf.buffer().split(' ').count())
+            });
+        let mut buf = TerminalBuffer::new("> ", None);
+        buf.set_size((400, 24));
+        buf.move_to_row(3);
+        buf.set_value(&mut Vec::new(), "ab cd").unwrap();
+
+        let line = overlay(&options, &buf);
+        assert!(line.contains(" password=on multiline=off "));
+        assert!(line.contains(
+            "| cursor=5 byte=5 col=7 row=3 prefix_cols=2 buffer_cols=5 |"
+        ));
+        assert!(line.contains("size=400x24 wrap_at=398"));
+        assert!(line.ends_with(" | words=2"));
+
+        buf.set_size((20, 24));
+        assert_eq!(20, overlay(&options, &buf).chars().count());
+    }
+}
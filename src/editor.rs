@@ -0,0 +1,136 @@
+//! Access to the line for commands defined by the application.
+use std::cell::RefMut;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::key_binding::{AbortReason, KeyBindings};
+use crate::PromptOptions;
+
+#[cfg(any(feature = "history", doc))]
+use crate::history::HistoryGuard;
+
+/// Handler for a command bound with
+/// [PromptOptions::command](crate::PromptOptions::command).
+pub(crate) type CommandHandler = Box<dyn Fn(&mut Editor<'_>) -> CommandOutcome>;
+
+/// What the prompt does after a command is executed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CommandOutcome {
+    /// Keep the prompt active.
+    Continue,
+    /// Submit the line as if Enter was pressed.
+    Submit,
+    /// Abort the prompt.
+    Abort(AbortReason),
+    /// End the input, the prompt returns an
+    /// [EndOfInput](crate::EndOfInput) error.
+    Eof,
+    /// Ring the terminal bell and keep the prompt active.
+    Beep,
+}
+
+/// State of the prompt that a command may change.
+///
+/// Changes to the line and cursor are drawn after
+/// the command returns.
+pub struct Editor<'a> {
+    line: String,
+    cursor: usize,
+    options: &'a PromptOptions,
+}
+
+impl<'a> Editor<'a> {
+    /// Create an editor for a line with the cursor
+    /// at a grapheme index.
+    pub(crate) fn new(
+        line: &str,
+        cursor: usize,
+        options: &'a PromptOptions,
+    ) -> Self {
+        Self {
+            line: line.to_string(),
+            cursor,
+            options,
+        }
+    }
+
+    /// Text of the line.
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Grapheme index of the cursor in the line.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Replace the line, moving the cursor to the end.
+    pub fn set_line<S: Into<String>>(&mut self, line: S) {
+        self.line = line.into();
+        self.cursor = self.len();
+    }
+
+    /// Move the cursor to a grapheme index, an index past
+    /// the end of the line moves the cursor to the end.
+    pub fn set_cursor(&mut self, index: usize) {
+        self.cursor = index.min(self.len());
+    }
+
+    /// Insert text at the cursor, moving the cursor
+    /// to the end of the text.
+    pub fn insert(&mut self, text: &str) {
+        let offset = self
+            .line
+            .grapheme_indices(true)
+            .nth(self.cursor)
+            .map_or(self.line.len(), |(offset, _)| offset);
+        self.line.insert_str(offset, text);
+        self.cursor += text.graphemes(true).count();
+    }
+
+    /// Acquire the history of the prompt.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    pub fn history(&self) -> Option<HistoryGuard<'_>> {
+        self.options.acquire_history()
+    }
+
+    /// Change the key bindings of the prompt, the changes
+    /// apply to the next key typed.
+    pub fn key_bindings(&self) -> RefMut<'_, KeyBindings> {
+        self.options.key_bindings()
+    }
+
+    /// Number of graphemes in the line.
+    fn len(&self) -> usize {
+        self.line.graphemes(true).count()
+    }
+
+    /// Line and cursor after the command.
+    pub(crate) fn into_parts(self) -> (String, usize) {
+        (self.line, self.cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editor_changes() {
+        let options = PromptOptions::default();
+        let mut editor = Editor::new("a日b", 2, &options);
+        editor.insert("xé");
+        assert_eq!("a日xéb", editor.line());
+        assert_eq!(4, editor.cursor());
+        editor.set_cursor(10);
+        assert_eq!(5, editor.cursor());
+        editor.insert("!");
+        editor.set_cursor(0);
+        editor.insert("$ ");
+        assert_eq!(("$ a日xéb!".to_string(), 2), editor.into_parts());
+
+        let mut editor = Editor::new("old", 0, &options);
+        editor.set_line("new line");
+        assert_eq!(8, editor.cursor());
+    }
+}
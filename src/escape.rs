@@ -0,0 +1,149 @@
+//! Combining Esc with the next key.
+//!
+//! Terminals send a key with Alt as Esc followed by the key,
+//! when an escape timeout is configured an Esc is held until
+//! the next key arrives or the timeout passes.
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use std::time::{Duration, Instant};
+
+/// Key event for Esc typed on its own.
+const ESCAPE: KeyEvent = KeyEvent {
+    code: KeyCode::Esc,
+    modifiers: KeyModifiers::NONE,
+};
+
+/// Esc waiting for the next key.
+#[derive(Debug, Default)]
+pub(crate) struct Escape {
+    timeout: Option<Duration>,
+    escape_at: Option<Instant>,
+}
+
+impl Escape {
+    /// Create the state for an escape timeout.
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            escape_at: None,
+        }
+    }
+
+    /// Time when a waiting Esc is handled on its own.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.escape_at
+    }
+
+    /// Get the Esc to handle on its own once the timeout
+    /// has passed without another key.
+    pub fn expire(&mut self, now: Instant) -> Option<Event> {
+        if self.escape_at.is_some_and(|at| now >= at) {
+            self.escape_at = None;
+            Some(Event::Key(ESCAPE))
+        } else {
+            None
+        }
+    }
+
+    /// Handle an event read at a time.
+    ///
+    /// Esc is only held when `hold` is set, for example not for
+    /// a key that is quoted. Returns `None` while Esc waits for
+    /// the next key, otherwise the event to handle and an event
+    /// to handle after it.
+    pub fn read(
+        &mut self,
+        event: Event,
+        now: Instant,
+        hold: bool,
+    ) -> Option<(Event, Option<Event>)> {
+        if self.escape_at.take().is_some() {
+            return Some(match event {
+                Event::Key(key)
+                    if !key.modifiers.contains(KeyModifiers::ALT) =>
+                {
+                    (
+                        Event::Key(KeyEvent::new(
+                            key.code,
+                            key.modifiers | KeyModifiers::ALT,
+                        )),
+                        None,
+                    )
+                }
+                event => (Event::Key(ESCAPE), Some(event)),
+            });
+        }
+        match (event, self.timeout) {
+            (Event::Key(key), Some(timeout)) if hold && key == ESCAPE => {
+                self.escape_at = Some(now + timeout);
+                None
+            }
+            (event, _) => Some((event, None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(50);
+
+    fn key(c: char, modifiers: KeyModifiers) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(c), modifiers))
+    }
+
+    #[test]
+    fn escape_then_key() {
+        let mut escape = Escape::new(Some(TIMEOUT));
+        let now = Instant::now();
+        assert_eq!(None, escape.read(Event::Key(ESCAPE), now, true));
+        assert_eq!(Some(now + TIMEOUT), escape.deadline());
+        assert_eq!(None, escape.expire(now + TIMEOUT / 2));
+
+        assert_eq!(
+            Some((key('b', KeyModifiers::ALT), None)),
+            escape.read(key('b', KeyModifiers::NONE), now + TIMEOUT / 2, true)
+        );
+        assert_eq!(None, escape.deadline());
+
+        // Esc is held for another Esc and then handled on its own
+        // before a key that already has Alt
+        assert_eq!(None, escape.read(Event::Key(ESCAPE), now, true));
+        assert_eq!(
+            Some((Event::Key(ESCAPE), Some(key('b', KeyModifiers::ALT)))),
+            escape.read(key('b', KeyModifiers::ALT), now, true)
+        );
+    }
+
+    #[test]
+    fn escape_timeout() {
+        let mut escape = Escape::new(Some(TIMEOUT));
+        let now = Instant::now();
+        assert_eq!(None, escape.read(Event::Key(ESCAPE), now, true));
+        assert_eq!(Some(Event::Key(ESCAPE)), escape.expire(now + TIMEOUT));
+        assert_eq!(None, escape.deadline());
+
+        // The next key is not combined with the Esc
+        let b = key('b', KeyModifiers::NONE);
+        assert_eq!(Some((b, None)), escape.read(b, now + TIMEOUT, true));
+
+        // Esc is not held when it is quoted
+        assert_eq!(
+            Some((Event::Key(ESCAPE), None)),
+            escape.read(Event::Key(ESCAPE), now, false)
+        );
+    }
+
+    #[test]
+    fn escape_without_timeout() {
+        let mut escape = Escape::default();
+        let now = Instant::now();
+        assert_eq!(
+            Some((Event::Key(ESCAPE), None)),
+            escape.read(Event::Key(ESCAPE), now, true)
+        );
+        assert_eq!(None, escape.deadline());
+        let b = key('b', KeyModifiers::NONE);
+        assert_eq!(Some((b, None)), escape.read(b, now, true));
+    }
+}
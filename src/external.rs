@@ -0,0 +1,82 @@
+//! Editing the line in an external editor.
+use anyhow::Result;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, process};
+
+/// Editor launched when neither `VISUAL` nor `EDITOR` is set.
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Program and arguments of the editor to launch.
+fn editor_command(
+    visual: Option<String>,
+    editor: Option<String>,
+) -> Vec<String> {
+    let command = visual
+        .into_iter()
+        .chain(editor)
+        .find(|command| !command.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+    command.split_whitespace().map(String::from).collect()
+}
+
+/// Remove the line breaks that editors add to the end of a file.
+fn trim_line_breaks(text: &str) -> &str {
+    text.trim_end_matches(['\n', '\r'])
+}
+
+/// Edit text in the editor named by `VISUAL` or `EDITOR`.
+///
+/// Raw mode is disabled while the editor runs; `None` is
+/// returned when the editor exits unsuccessfully.
+pub(crate) fn edit(text: &str) -> Result<Option<String>> {
+    let mut args =
+        editor_command(env::var("VISUAL").ok(), env::var("EDITOR").ok());
+    let program = args.remove(0);
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+    let path = env::temp_dir().join(format!(
+        "crossterm-prompt-{}-{}.txt",
+        process::id(),
+        nanos
+    ));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    let path = scopeguard::guard(path, |path| {
+        let _ = fs::remove_file(path);
+    });
+    writeln!(file, "{}", text)?;
+    drop(file);
+
+    disable_raw_mode()?;
+    let status = Command::new(program).args(args).arg(&*path).status();
+    enable_raw_mode()?;
+
+    if !status?.success() {
+        return Ok(None);
+    }
+    let edited = fs::read_to_string(&*path)?;
+    Ok(Some(trim_line_breaks(&edited).to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choose_editor() {
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(vec!["vim"], editor_command(some("vim"), some("nano")));
+        assert_eq!(
+            vec!["code", "--wait"],
+            editor_command(some(" "), some("code --wait"))
+        );
+        assert_eq!(vec![DEFAULT_EDITOR], editor_command(None, None));
+        assert_eq!("a\nb", trim_line_breaks("a\nb\r\n\n"));
+    }
+}
@@ -0,0 +1,48 @@
+//! Limiting how often the prompt is drawn.
+//!
+//! While keys are arriving faster than the terminal can draw,
+//! such as pasted text or held keys, drawing is skipped until the
+//! frame interval passes and the waiting input is handled first.
+use std::time::{Duration, Instant};
+
+/// Shortest time between drawing frames while input is waiting.
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Time when the next frame may be drawn.
+#[derive(Debug, Default)]
+pub(crate) struct Frame {
+    render_at: Option<Instant>,
+}
+
+impl Frame {
+    /// Determine if the prompt is drawn now.
+    ///
+    /// The prompt is always drawn when no input is waiting,
+    /// otherwise only once the frame interval has passed.
+    pub fn is_due(&mut self, now: Instant, waiting: bool) -> bool {
+        if waiting && self.render_at.is_some_and(|at| now < at) {
+            return false;
+        }
+        self.render_at = Some(now + FRAME_INTERVAL);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_interval() {
+        let mut frame = Frame::default();
+        let now = Instant::now();
+        assert!(frame.is_due(now, true));
+        assert!(!frame.is_due(now + Duration::from_millis(1), true));
+        assert!(!frame.is_due(now + FRAME_INTERVAL / 2, true));
+        assert!(frame.is_due(now + FRAME_INTERVAL / 2, false));
+
+        let later = now + FRAME_INTERVAL / 2 + FRAME_INTERVAL;
+        assert!(frame.is_due(later, true));
+        assert!(!frame.is_due(later, true));
+    }
+}
@@ -1,21 +1,224 @@
 //! Support for shell history.
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::{Deref, DerefMut, Range};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+
+mod import;
+
+/// History shared between prompts.
+pub type SharedHistory = Arc<Mutex<dyn History>>;
+
+/// Access to a history used by a prompt.
+///
+/// Implemented for histories shared between threads with
+/// `Arc<Mutex<_>>` or within a thread with `Rc<RefCell<_>>`
+/// and for a history owned by the prompt options using
+/// `RefCell` or `Mutex`.
+pub trait HistoryHandle {
+    /// Get exclusive access to the history.
+    fn acquire(&self) -> HistoryGuard<'_>;
+}
+
+/// Exclusive access to a history returned by [HistoryHandle].
+pub struct HistoryGuard<'a>(Box<dyn DerefMut<Target = dyn History> + 'a>);
+
+impl Deref for HistoryGuard<'_> {
+    type Target = dyn History;
+
+    fn deref(&self) -> &Self::Target {
+        &**self.0
+    }
+}
+
+impl DerefMut for HistoryGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut **self.0
+    }
+}
+
+/// Guard for a concrete history that dereferences
+/// to the history trait.
+struct Erased<G>(G);
+
+impl<G, H> Deref for Erased<G>
+where
+    G: DerefMut<Target = H>,
+    H: History + 'static,
+{
+    type Target = dyn History;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl<G, H> DerefMut for Erased<G>
+where
+    G: DerefMut<Target = H>,
+    H: History + 'static,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.0
+    }
+}
+
+/// Lock a mutex ignoring poisoning, a panic while the history
+/// was locked does not make it unusable.
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<H: History + 'static> HistoryHandle for Mutex<H> {
+    fn acquire(&self) -> HistoryGuard<'_> {
+        HistoryGuard(Box::new(Erased(lock(self))))
+    }
+}
+
+impl HistoryHandle for Mutex<dyn History> {
+    fn acquire(&self) -> HistoryGuard<'_> {
+        HistoryGuard(Box::new(lock(self)))
+    }
+}
+
+impl<H: History + 'static> HistoryHandle for RefCell<H> {
+    fn acquire(&self) -> HistoryGuard<'_> {
+        HistoryGuard(Box::new(Erased(self.borrow_mut())))
+    }
+}
+
+impl HistoryHandle for RefCell<dyn History> {
+    fn acquire(&self) -> HistoryGuard<'_> {
+        HistoryGuard(Box::new(self.borrow_mut()))
+    }
+}
+
+impl<T: HistoryHandle + ?Sized> HistoryHandle for Arc<T> {
+    fn acquire(&self) -> HistoryGuard<'_> {
+        (**self).acquire()
+    }
+}
+
+impl<T: HistoryHandle + ?Sized> HistoryHandle for Rc<T> {
+    fn acquire(&self) -> HistoryGuard<'_> {
+        (**self).acquire()
+    }
+}
+
+/// Entry in a history with optional metadata.
+///
+/// The metadata can be attached by a shell handler after the
+/// command has been executed using [History::update_last].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HistoryEntry {
+    /// The submitted line.
+    pub value: String,
+    /// Namespace the entry was submitted in.
+    pub namespace: Option<String>,
+    /// Exit status of the command.
+    pub exit_status: Option<i32>,
+    /// Time taken to execute the command.
+    pub duration: Option<Duration>,
+    /// Working directory the command was executed in.
+    pub working_directory: Option<PathBuf>,
+}
+
+impl HistoryEntry {
+    /// Create a new history entry without metadata.
+    pub fn new<S: Into<String>>(value: S) -> Self {
+        Self {
+            value: value.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Policy for duplicate history items.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum DedupPolicy {
+    /// Keep all duplicate items.
+    #[default]
+    None,
+    /// Ignore an item that is the same as the last item.
+    IgnoreConsecutive,
+    /// Remove all existing items that are the same as a new item.
+    EraseAll,
+}
 
 /// Options for history implementations.
 pub struct HistoryOptions {
     /// Maximum number of history items.
-    maximum_size: u16,
+    ///
+    /// When the maximum is reached the oldest item is removed,
+    /// `None` allows an unlimited number of items.
+    pub maximum_size: Option<usize>,
+
+    /// Policy for duplicate items.
+    pub dedup: DedupPolicy,
+
+    /// Patterns for items that should not be added to the history.
+    ///
+    /// Like `HISTIGNORE` each pattern must match the entire item,
+    /// `*` matches any sequence of characters, `?` matches a single
+    /// character and `\` escapes the next character.
+    pub ignore: Vec<String>,
+
+    /// Do not add items that begin with a space.
+    ///
+    /// Like `HISTCONTROL=ignorespace` this allows a command to be
+    /// run without it being recorded.
+    pub ignore_leading_space: bool,
+
+    /// Called with each new entry before it is stored.
+    ///
+    /// Return `None` to discard the entry or a rewritten entry,
+    /// for example with secrets redacted, to store instead.
+    pub on_add: Option<AddHandler>,
+}
+
+/// Callback that can discard or rewrite a new history entry.
+pub type AddHandler =
+    Box<dyn Fn(HistoryEntry) -> Option<HistoryEntry> + Send + Sync>;
+
+impl HistoryOptions {
+    /// Determine if an item matches one of the ignore patterns.
+    pub fn is_ignored(&self, item: &str) -> bool {
+        (self.ignore_leading_space && item.starts_with(' '))
+            || self.ignore.iter().any(|pattern| glob_match(pattern, item))
+    }
 }
 
 impl Default for HistoryOptions {
     fn default() -> Self {
-        Self { maximum_size: 1000 }
+        Self {
+            maximum_size: Some(1000),
+            dedup: Default::default(),
+            ignore: Vec::new(),
+            ignore_leading_space: false,
+            on_add: None,
+        }
     }
 }
 
 /// Trait for history implementations.
+///
+/// Entries and lines are returned as [Cow] so a backend that does
+/// not keep its entries in memory, for example a database, can
+/// return owned values.
 pub trait History {
-    /// Get the underlying history items.
-    fn items(&self) -> &Vec<String>;
+    /// Get the entry at an index, the oldest entry is at index zero.
+    ///
+    /// Backends that do not keep entries in memory may return
+    /// an owned entry.
+    fn entry(&self, index: usize) -> Option<Cow<'_, HistoryEntry>>;
 
     /// Get the number of items in the history.
     fn len(&self) -> usize;
@@ -29,55 +232,548 @@ pub trait History {
     /// Remove all the history items and reset the cursor.
     fn clear(&mut self);
 
+    /// Keep only the entries for which a predicate returns `true`.
+    ///
+    /// The cursor is moved past the last entry.
+    fn retain(&mut self, keep: &mut dyn FnMut(&HistoryEntry) -> bool);
+
+    /// Remove the entry at an index.
+    ///
+    /// The cursor is moved past the last entry.
+    fn remove(&mut self, index: usize) -> Option<HistoryEntry>;
+
     /// Push an item onto this history.
     ///
     /// This moves the cursor to the last item in
     /// the history.
-    fn push(&mut self, item: String);
+    fn push(&mut self, item: String) {
+        self.push_entry(HistoryEntry::new(item));
+    }
+
+    /// Push an entry onto this history.
+    ///
+    /// Entries without a namespace are tagged with the
+    /// current namespace.
+    fn push_entry(&mut self, entry: HistoryEntry);
+
+    /// Change the most recently pushed entry, for example
+    /// to attach metadata.
+    ///
+    /// The update is not called when no entry has been pushed.
+    fn update_last(&mut self, update: &mut dyn FnMut(&mut HistoryEntry));
 
     /// Get the item at the current cursor position.
-    fn get(&self) -> Option<&String>;
+    fn get(&self) -> Option<Cow<'_, str>>;
 
     /// Move the current cursor position and get an item
     /// at the new position.
-    fn move_by(&mut self, amount: i16) -> Option<&String>;
+    fn move_by(&mut self, amount: i16) -> Option<Cow<'_, str>>;
 
     /// Get the position of the cursor.
-    fn position(&self) -> &Option<usize>;
+    fn position(&self) -> Option<usize>;
 
     /// Move the cursor to the previous entry in the history.
-    fn previous(&mut self) -> Option<&String>;
+    fn previous(&mut self) -> Option<Cow<'_, str>>;
 
     /// Move the cursor to the next entry in the history.
-    fn next(&mut self) -> Option<&String>;
+    fn next(&mut self) -> Option<Cow<'_, str>>;
+
+    /// Move the cursor to the previous entry that starts
+    /// with a prefix.
+    ///
+    /// When there is no such entry the cursor is not moved
+    /// and `None` is returned.
+    ///
+    /// The default implementation scans the entries with
+    /// [History::entry] and moves the cursor with [History::move_by].
+    fn previous_with_prefix(&mut self, prefix: &str) -> Option<Cow<'_, str>> {
+        let index = find_previous(self, |value| value.starts_with(prefix))?;
+        seek(self, index)
+    }
+
+    /// Move the cursor to the next entry that starts with
+    /// a prefix.
+    ///
+    /// When there is no such entry the cursor is moved past
+    /// the last entry and `None` is returned.
+    ///
+    /// The default implementation scans the entries with
+    /// [History::entry] and moves the cursor with [History::move_by].
+    fn next_with_prefix(&mut self, prefix: &str) -> Option<Cow<'_, str>> {
+        let index = find_next(self, |value| value.starts_with(prefix))?;
+        seek(self, index)
+    }
+
+    /// Move the cursor to the previous entry that contains
+    /// some text.
+    ///
+    /// When there is no such entry the cursor is not moved
+    /// and `None` is returned.
+    ///
+    /// The default implementation scans the entries with
+    /// [History::entry] and moves the cursor with [History::move_by].
+    fn previous_containing(&mut self, text: &str) -> Option<Cow<'_, str>> {
+        let index = find_previous(self, |value| value.contains(text))?;
+        seek(self, index)
+    }
+
+    /// Move the cursor to the next entry that contains
+    /// some text.
+    ///
+    /// When there is no such entry the cursor is moved past
+    /// the last entry and `None` is returned.
+    ///
+    /// The default implementation scans the entries with
+    /// [History::entry] and moves the cursor with [History::move_by].
+    fn next_containing(&mut self, text: &str) -> Option<Cow<'_, str>> {
+        let index = find_next(self, |value| value.contains(text))?;
+        seek(self, index)
+    }
+
+    /// Get the namespace for the history.
+    fn namespace(&self) -> Option<&str> {
+        None
+    }
+
+    /// Set the namespace used to tag pushed items.
+    ///
+    /// Navigation only visits items in the current namespace
+    /// unless the scope has been widened.
+    fn set_namespace(&mut self, _namespace: Option<String>) {}
+
+    /// Determine if navigation visits items in all namespaces.
+    fn is_widened(&self) -> bool {
+        true
+    }
+
+    /// Set whether navigation visits items in all namespaces.
+    fn set_widened(&mut self, _widened: bool) {}
+}
+
+/// Determine if an entry is visible in the namespace of a history.
+fn is_visible<H: History + ?Sized>(history: &H, entry: &HistoryEntry) -> bool {
+    history.is_widened() || entry.namespace.as_deref() == history.namespace()
+}
+
+/// Find the index of the previous visible entry before the
+/// cursor matching a predicate.
+fn find_previous<H, F>(history: &H, matches: F) -> Option<usize>
+where
+    H: History + ?Sized,
+    F: Fn(&str) -> bool,
+{
+    let cursor = history.position()?.min(history.len());
+    (0..cursor).rev().find(|index| {
+        history.entry(*index).is_some_and(|entry| {
+            is_visible(history, &entry) && matches(&entry.value)
+        })
+    })
+}
+
+/// Find the index of the next visible entry after the cursor
+/// matching a predicate, or the index past the last entry.
+fn find_next<H, F>(history: &H, matches: F) -> Option<usize>
+where
+    H: History + ?Sized,
+    F: Fn(&str) -> bool,
+{
+    let cursor = history.position()?;
+    let len = history.len();
+    let index = (cursor + 1..len)
+        .find(|index| {
+            history.entry(*index).is_some_and(|entry| {
+                is_visible(history, &entry) && matches(&entry.value)
+            })
+        })
+        .unwrap_or(len);
+    Some(index)
+}
+
+/// Move the cursor of a history to an index and get the
+/// item at the new position.
+fn seek<H: History + ?Sized>(
+    history: &mut H,
+    index: usize,
+) -> Option<Cow<'_, str>> {
+    let mut cursor = history.position()?;
+    while cursor != index {
+        let amount = if index > cursor {
+            (index - cursor).min(i16::MAX as usize) as i16
+        } else {
+            -((cursor - index).min(i16::MAX as usize) as i16)
+        };
+        history.move_by(amount);
+        cursor = cursor.wrapping_add_signed(amount as isize);
+    }
+    history.get()
+}
+
+/// Iterator over the entries in a history.
+pub struct Entries<'a> {
+    history: &'a dyn History,
+    range: Range<usize>,
+}
+
+impl<'a> Entries<'a> {
+    /// Iterate the entries of a history.
+    pub fn new(history: &'a dyn History) -> Self {
+        Self {
+            history,
+            range: 0..history.len(),
+        }
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Cow<'a, HistoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let history = self.history;
+        self.range.find_map(|index| history.entry(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Entries missing from the backend are skipped
+        (0, Some(self.range.len()))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Entries<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let history = self.history;
+        self.range
+            .by_ref()
+            .rev()
+            .find_map(|index| history.entry(index))
+    }
+}
+
+impl<'h> dyn History + 'h {
+    /// Iterate the entries from oldest to newest.
+    pub fn iter(&self) -> Entries<'_> {
+        Entries::new(self)
+    }
+}
+
+/// Entries visited when navigating a history.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Match {
+    /// Visit every entry.
+    Any,
+    /// Visit entries starting with the text before the cursor.
+    Prefix,
+    /// Visit entries containing the text of the line.
+    Substring,
+}
+
+/// Line and cursor position to show in a prompt.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Draft {
+    /// The line.
+    pub line: String,
+    /// Cursor as a grapheme index into the line.
+    pub cursor: usize,
+}
+
+impl Draft {
+    /// Create a draft with the cursor at the end of the line.
+    fn at_end(line: String) -> Self {
+        let cursor = line.graphemes(true).count();
+        Self { line, cursor }
+    }
+}
+
+/// Navigates a history on behalf of a prompt.
+///
+/// The line being edited when navigation starts is kept so it
+/// is restored, with the cursor where it was, when navigating
+/// past the newest entry.
+#[derive(Debug, Default)]
+pub struct HistoryNavigator {
+    draft: Option<Draft>,
+    substring: Option<(String, String)>,
+}
+
+impl HistoryNavigator {
+    /// Create a navigator.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Move to the previous matching entry.
+    ///
+    /// Returns the line to show or `None` when there is
+    /// no such entry.
+    pub fn previous(
+        &mut self,
+        history: &mut dyn History,
+        kind: Match,
+        line: &str,
+        cursor: usize,
+    ) -> Option<Draft> {
+        let text = self.text(kind, line, cursor);
+        if history.is_last() {
+            self.draft = Some(Draft {
+                line: line.to_string(),
+                cursor,
+            });
+        }
+        let value = match kind {
+            Match::Any => history.previous(),
+            Match::Prefix => history.previous_with_prefix(&text),
+            Match::Substring => history.previous_containing(&text),
+        }?
+        .into_owned();
+        Some(self.show(kind, text, value, cursor))
+    }
+
+    /// Move to the next matching entry.
+    ///
+    /// Moving past the newest entry restores the line that
+    /// was being edited; returns `None` when already there.
+    pub fn next(
+        &mut self,
+        history: &mut dyn History,
+        kind: Match,
+        line: &str,
+        cursor: usize,
+    ) -> Option<Draft> {
+        if history.is_last() {
+            return None;
+        }
+        let text = self.text(kind, line, cursor);
+        let value = match kind {
+            Match::Any => history.next(),
+            Match::Prefix => history.next_with_prefix(&text),
+            Match::Substring => history.next_containing(&text),
+        }
+        .map(Cow::into_owned);
+        match value {
+            Some(value) => Some(self.show(kind, text, value, cursor)),
+            None => {
+                self.substring = None;
+                Some(self.draft.take().unwrap_or_default())
+            }
+        }
+    }
+
+    /// Move to the oldest entry.
+    ///
+    /// Returns `None` when there is no entry to visit.
+    pub fn first(
+        &mut self,
+        history: &mut dyn History,
+        line: &str,
+        cursor: usize,
+    ) -> Option<Draft> {
+        let index = (0..history.len()).find(|index| {
+            history
+                .entry(*index)
+                .is_some_and(|entry| is_visible(history, &entry))
+        })?;
+        if history.is_last() {
+            self.draft = Some(Draft {
+                line: line.to_string(),
+                cursor,
+            });
+        }
+        let value = seek(history, index)?.into_owned();
+        Some(self.show(Match::Any, String::new(), value, cursor))
+    }
+
+    /// Move past the newest entry, restoring the line that
+    /// was being edited.
+    ///
+    /// Returns `None` when already there.
+    pub fn last(
+        &mut self,
+        history: &mut dyn History,
+        line: &str,
+        cursor: usize,
+    ) -> Option<Draft> {
+        let mut draft = None;
+        while let Some(next) = self.next(history, Match::Any, line, cursor) {
+            draft = Some(next);
+        }
+        draft
+    }
+
+    /// Text to match entries against.
+    ///
+    /// Repeating a substring search keeps the original text
+    /// while the line still shows the previous match.
+    fn text(&self, kind: Match, line: &str, cursor: usize) -> String {
+        match (kind, &self.substring) {
+            (Match::Any, _) => String::new(),
+            (Match::Prefix, _) => line.graphemes(true).take(cursor).collect(),
+            (Match::Substring, Some((text, shown))) if shown == line => {
+                text.clone()
+            }
+            (Match::Substring, _) => line.to_string(),
+        }
+    }
+
+    /// Draft for a matching entry, a prefix search keeps
+    /// the cursor after the prefix.
+    fn show(
+        &mut self,
+        kind: Match,
+        text: String,
+        value: String,
+        cursor: usize,
+    ) -> Draft {
+        match kind {
+            Match::Prefix => Draft {
+                line: value,
+                cursor,
+            },
+            Match::Substring => {
+                self.substring = Some((text, value.clone()));
+                Draft::at_end(value)
+            }
+            Match::Any => Draft::at_end(value),
+        }
+    }
 }
 
 /// Stores history in memory.
 #[derive(Default)]
 pub struct MemoryHistory {
-    items: Vec<String>,
+    items: VecDeque<HistoryEntry>,
     options: HistoryOptions,
     cursor: Option<usize>,
+    namespace: Option<String>,
+    widened: bool,
 }
 
 impl MemoryHistory {
     /// Create a new in-memory history.
     pub fn new(options: HistoryOptions) -> Self {
         Self {
-            items: vec![],
+            items: VecDeque::new(),
             cursor: None,
             options,
+            namespace: None,
+            widened: false,
+        }
+    }
+
+    /// Determine if the item at an index is visible
+    /// in the current namespace.
+    fn is_visible(&self, index: usize) -> bool {
+        self.widened || self.items[index].namespace == self.namespace
+    }
+
+    /// Determine if the item at an index has the same
+    /// value and namespace as an entry.
+    fn is_same(&self, index: usize, entry: &HistoryEntry) -> bool {
+        let item = &self.items[index];
+        item.value == entry.value && item.namespace == entry.namespace
+    }
+
+    /// Move the cursor to the previous visible entry
+    /// matching a predicate.
+    fn previous_matching<F>(&mut self, matches: F) -> Option<Cow<'_, str>>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let cursor = self.cursor?.min(self.items.len());
+        let index = (0..cursor)
+            .rev()
+            .find(|i| self.is_visible(*i) && matches(&self.items[*i].value))?;
+        self.cursor = Some(index);
+        self.get()
+    }
+
+    /// Move the cursor to the next visible entry matching
+    /// a predicate or past the last entry.
+    fn next_matching<F>(&mut self, matches: F) -> Option<Cow<'_, str>>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let cursor = self.cursor?;
+        let index = (cursor + 1..self.items.len())
+            .find(|i| self.is_visible(*i) && matches(&self.items[*i].value))
+            .unwrap_or(self.items.len());
+        self.cursor = Some(index);
+        self.get()
+    }
+
+    /// Add an entry returning the entry that was stored.
+    fn insert(&mut self, entry: HistoryEntry) -> Option<&HistoryEntry> {
+        self.insert_with(entry, &mut |_| {})
+    }
+
+    /// Add an entry returning the entry that was stored, `removed`
+    /// is called with the index of each entry that is removed to
+    /// make room for it.
+    fn insert_with(
+        &mut self,
+        mut entry: HistoryEntry,
+        removed: &mut dyn FnMut(usize),
+    ) -> Option<&HistoryEntry> {
+        if entry.namespace.is_none() {
+            entry.namespace = self.namespace.clone();
+        }
+
+        if let Some(on_add) = &self.options.on_add {
+            entry = match (on_add)(entry) {
+                Some(entry) => entry,
+                None => {
+                    self.cursor = Some(self.items.len());
+                    return None;
+                }
+            };
+        }
+
+        if self.options.is_ignored(&entry.value) {
+            self.cursor = Some(self.items.len());
+            return None;
+        }
+
+        match self.options.dedup {
+            DedupPolicy::None => {}
+            DedupPolicy::IgnoreConsecutive => {
+                if !self.items.is_empty()
+                    && self.is_same(self.items.len() - 1, &entry)
+                {
+                    self.cursor = Some(self.items.len());
+                    return None;
+                }
+            }
+            DedupPolicy::EraseAll => {
+                let mut index = 0;
+                while index < self.items.len() {
+                    if self.is_same(index, &entry) {
+                        self.items.remove(index);
+                        removed(index);
+                    } else {
+                        index += 1;
+                    }
+                }
+            }
+        }
+
+        self.items.push_back(entry);
+
+        if let Some(maximum_size) = self.options.maximum_size {
+            while self.items.len() > maximum_size {
+                self.items.pop_front();
+                removed(0);
+            }
         }
+        self.cursor = Some(self.items.len());
+        self.items.back()
     }
 }
 
 impl History for MemoryHistory {
-    fn items(&self) -> &Vec<String> {
-        &self.items
+    fn entry(&self, index: usize) -> Option<Cow<'_, HistoryEntry>> {
+        self.items.get(index).map(Cow::Borrowed)
     }
 
-    fn position(&self) -> &Option<usize> {
-        &self.cursor
+    fn position(&self) -> Option<usize> {
+        self.cursor
     }
 
     fn len(&self) -> usize {
@@ -97,62 +793,86 @@ impl History for MemoryHistory {
     }
 
     fn clear(&mut self) {
-        self.items = vec![];
+        self.items.clear();
         self.cursor = None;
     }
 
-    fn get(&self) -> Option<&String> {
+    fn retain(&mut self, keep: &mut dyn FnMut(&HistoryEntry) -> bool) {
+        self.items.retain(|entry| keep(entry));
+        self.cursor = Some(self.items.len());
+    }
+
+    fn remove(&mut self, index: usize) -> Option<HistoryEntry> {
+        let entry = self.items.remove(index);
+        self.cursor = Some(self.items.len());
+        entry
+    }
+
+    fn get(&self) -> Option<Cow<'_, str>> {
         if let Some(cursor) = self.cursor {
-            self.items.get(cursor)
+            self.items
+                .get(cursor)
+                .map(|entry| Cow::Borrowed(entry.value.as_str()))
         } else {
             None
         }
     }
 
-    fn push(&mut self, item: String) {
-        self.items.push(item);
+    fn push_entry(&mut self, entry: HistoryEntry) {
+        self.insert(entry);
+    }
 
-        if self.items.len() > self.options.maximum_size as usize {
-            self.items.remove(0);
+    fn update_last(&mut self, update: &mut dyn FnMut(&mut HistoryEntry)) {
+        if let Some(entry) = self.items.back_mut() {
+            update(entry);
         }
-        self.cursor = Some(self.items.len());
     }
 
-    fn previous(&mut self) -> Option<&String> {
+    fn previous(&mut self) -> Option<Cow<'_, str>> {
         if let Some(cursor) = self.cursor {
-            if cursor > 0 {
-                if cursor > self.items.len() - 1 {
-                    self.cursor = Some(self.items.len() - 1);
-                    self.get()
-                } else {
-                    self.move_by(-1)
-                }
-            } else {
-                self.cursor = Some(0);
-                self.get()
+            let cursor = cursor.min(self.items.len());
+            if let Some(index) = (0..cursor).rev().find(|i| self.is_visible(*i))
+            {
+                self.cursor = Some(index);
             }
+            self.get()
         } else {
             None
         }
     }
 
-    fn next(&mut self) -> Option<&String> {
+    fn next(&mut self) -> Option<Cow<'_, str>> {
         if let Some(cursor) = self.cursor {
-            if cursor < self.items.len() - 1 {
-                self.move_by(1)
-            } else {
-                self.cursor = Some(self.items.len());
-                self.get()
-            }
+            let index = (cursor + 1..self.items.len())
+                .find(|i| self.is_visible(*i))
+                .unwrap_or(self.items.len());
+            self.cursor = Some(index);
+            self.get()
         } else {
             None
         }
     }
 
-    fn move_by(&mut self, amount: i16) -> Option<&String> {
+    fn previous_with_prefix(&mut self, prefix: &str) -> Option<Cow<'_, str>> {
+        self.previous_matching(|value| value.starts_with(prefix))
+    }
+
+    fn next_with_prefix(&mut self, prefix: &str) -> Option<Cow<'_, str>> {
+        self.next_matching(|value| value.starts_with(prefix))
+    }
+
+    fn previous_containing(&mut self, text: &str) -> Option<Cow<'_, str>> {
+        self.previous_matching(|value| value.contains(text))
+    }
+
+    fn next_containing(&mut self, text: &str) -> Option<Cow<'_, str>> {
+        self.next_matching(|value| value.contains(text))
+    }
+
+    fn move_by(&mut self, amount: i16) -> Option<Cow<'_, str>> {
         if let Some(cursor) = self.cursor {
             let new_pos = if amount.is_negative() {
-                cursor - amount.wrapping_abs() as i16 as usize
+                cursor - amount.wrapping_abs() as usize
             } else {
                 cursor + amount as usize
             };
@@ -160,28 +880,1557 @@ impl History for MemoryHistory {
         }
         self.get()
     }
+
+    fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    fn set_namespace(&mut self, namespace: Option<String>) {
+        self.namespace = namespace;
+    }
+
+    fn is_widened(&self) -> bool {
+        self.widened
+    }
+
+    fn set_widened(&mut self, widened: bool) {
+        self.widened = widened;
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Stores history in memory and appends each new item to a file.
+///
+/// Items are written as soon as they are added so the history
+/// survives a crash or panic. Each item is stored on a single
+/// line with newlines and backslashes escaped, followed by a
+/// line starting with `\#` for its namespace and each field of
+/// metadata; files without these lines are read as items without
+/// metadata. Metadata attached with [History::update_last] is
+/// appended to the file as it changes.
+pub struct FileHistory {
+    memory: MemoryHistory,
+    path: PathBuf,
+    file: File,
+    sync: bool,
+    error: Option<io::Error>,
+}
 
-    #[test]
-    fn history_basic() {
-        let mut history = MemoryHistory::new(Default::default());
-        history.push("foo".to_string());
-        assert!(!history.is_empty());
-        assert_eq!(1, history.len());
+impl FileHistory {
+    /// Open a history file loading any existing items,
+    /// the file is created when it does not exist.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        options: HistoryOptions,
+    ) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path)?;
 
-        history.push("baz".to_string());
-        assert_eq!(&Some(2), history.position());
+        let mut memory = MemoryHistory::new(options);
+        for entry in read_entries(BufReader::new(&file))? {
+            memory.insert(entry);
+        }
 
-        assert_eq!(Some(&("baz".to_string())), history.previous());
-        assert_eq!(Some(&("foo".to_string())), history.previous());
-        assert_eq!(Some(&("baz".to_string())), history.next());
-        assert_eq!(None, history.next());
+        Ok(Self {
+            memory,
+            path,
+            file,
+            sync: false,
+            error: None,
+        })
+    }
 
-        assert_eq!(&Some(2), history.position());
-        assert_eq!(None, history.get());
+    /// Flush each item to disk with `fsync` after it is written.
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Take the last error that occurred writing to the file.
+    ///
+    /// Errors cannot be returned when an item is pushed so the
+    /// most recent one is kept until it is taken.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    /// Import the entries of a bash history file.
+    ///
+    /// Returns the number of entries that were added.
+    pub fn import_bash<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> io::Result<usize> {
+        let text = fs::read_to_string(path)?;
+        self.import(import::bash(&text))
+    }
+
+    /// Import the entries of a zsh history file.
+    ///
+    /// Returns the number of entries that were added.
+    pub fn import_zsh<P: AsRef<Path>>(&mut self, path: P) -> io::Result<usize> {
+        let bytes = fs::read(path)?;
+        self.import(import::zsh(&bytes))
+    }
+
+    /// Import the entries of a fish history file.
+    ///
+    /// Returns the number of entries that were added.
+    pub fn import_fish<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> io::Result<usize> {
+        let text = fs::read_to_string(path)?;
+        self.import(import::fish(&text))
+    }
+
+    /// Add imported entries writing them to the file.
+    fn import(&mut self, entries: Vec<HistoryEntry>) -> io::Result<usize> {
+        let mut count = 0;
+        for entry in entries {
+            if let Some(entry) = self.memory.insert(entry) {
+                let entry = entry.clone();
+                self.append(&entry)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Replace the contents of the file with the items
+    /// in memory.
+    ///
+    /// The items are written to a temporary file which is moved
+    /// over the file, so the file is never left partially written.
+    fn rewrite(&mut self) -> io::Result<()> {
+        let mut contents = String::new();
+        for entry in &self.memory.items {
+            write_entry(&mut contents, entry);
+        }
+
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+        let mut temp = File::create(&temp_path)?;
+        temp.set_permissions(self.file.metadata()?.permissions())?;
+        temp.write_all(contents.as_bytes())?;
+        temp.sync_all()?;
+        drop(temp);
+        fs::rename(&temp_path, &self.path)?;
+
+        self.file = open_append(&self.path)?;
+        Ok(())
+    }
+
+    /// Append an entry to the file.
+    fn append(&mut self, entry: &HistoryEntry) -> io::Result<()> {
+        let mut lines = String::new();
+        write_entry(&mut lines, entry);
+        self.write_lines(&lines)
+    }
+
+    /// Append lines to the file.
+    fn write_lines(&mut self, lines: &str) -> io::Result<()> {
+        self.file.write_all(lines.as_bytes())?;
+        if self.sync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+impl History for FileHistory {
+    fn entry(&self, index: usize) -> Option<Cow<'_, HistoryEntry>> {
+        self.memory.entry(index)
+    }
+
+    fn position(&self) -> Option<usize> {
+        self.memory.position()
+    }
+
+    fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+    }
+
+    fn is_last(&self) -> bool {
+        self.memory.is_last()
+    }
+
+    fn clear(&mut self) {
+        self.memory.clear();
+        if let Err(e) = self.rewrite() {
+            self.error = Some(e);
+        }
+    }
+
+    fn retain(&mut self, keep: &mut dyn FnMut(&HistoryEntry) -> bool) {
+        self.memory.retain(keep);
+        if let Err(e) = self.rewrite() {
+            self.error = Some(e);
+        }
+    }
+
+    fn remove(&mut self, index: usize) -> Option<HistoryEntry> {
+        let entry = self.memory.remove(index)?;
+        if let Err(e) = self.rewrite() {
+            self.error = Some(e);
+        }
+        Some(entry)
+    }
+
+    fn get(&self) -> Option<Cow<'_, str>> {
+        self.memory.get()
+    }
+
+    fn push_entry(&mut self, entry: HistoryEntry) {
+        if let Some(entry) = self.memory.insert(entry) {
+            let entry = entry.clone();
+            if let Err(e) = self.append(&entry) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn update_last(&mut self, update: &mut dyn FnMut(&mut HistoryEntry)) {
+        let Some(before) = self.memory.items.back().cloned() else {
+            return;
+        };
+        self.memory.update_last(update);
+        let Some(after) = self.memory.items.back() else {
+            return;
+        };
+        let result = if after.value == before.value {
+            // Fields written after an entry replace its fields
+            let mut lines = String::new();
+            write_changed_fields(&mut lines, &before, after);
+            self.write_lines(&lines)
+        } else {
+            self.rewrite()
+        };
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    fn previous(&mut self) -> Option<Cow<'_, str>> {
+        self.memory.previous()
+    }
+
+    fn next(&mut self) -> Option<Cow<'_, str>> {
+        self.memory.next()
+    }
+
+    fn previous_with_prefix(&mut self, prefix: &str) -> Option<Cow<'_, str>> {
+        self.memory.previous_with_prefix(prefix)
+    }
+
+    fn next_with_prefix(&mut self, prefix: &str) -> Option<Cow<'_, str>> {
+        self.memory.next_with_prefix(prefix)
+    }
+
+    fn previous_containing(&mut self, text: &str) -> Option<Cow<'_, str>> {
+        self.memory.previous_containing(text)
+    }
+
+    fn next_containing(&mut self, text: &str) -> Option<Cow<'_, str>> {
+        self.memory.next_containing(text)
+    }
+
+    fn move_by(&mut self, amount: i16) -> Option<Cow<'_, str>> {
+        self.memory.move_by(amount)
+    }
+
+    fn namespace(&self) -> Option<&str> {
+        self.memory.namespace()
+    }
+
+    fn set_namespace(&mut self, namespace: Option<String>) {
+        self.memory.set_namespace(namespace);
+    }
+
+    fn is_widened(&self) -> bool {
+        self.memory.is_widened()
+    }
+
+    fn set_widened(&mut self, widened: bool) {
+        self.memory.set_widened(widened);
+    }
+}
+
+/// History for a single session backed by a shared history.
+///
+/// Navigation visits the entries of the shared history at the
+/// time the session started followed by the entries added during
+/// the session. New entries are only added to the shared history
+/// when the session is merged or dropped so that the entries of
+/// each session stay together, wrap a [FileHistory] to write them
+/// to disk on exit. Entries of the snapshot that are removed from
+/// the session are removed from the shared history right away.
+pub struct SessionHistory {
+    memory: MemoryHistory,
+    shared: SharedHistory,
+    tail: Vec<HistoryEntry>,
+    /// Number of entries at the start of memory from the snapshot.
+    snapshot: usize,
+    /// Index into the tail of each entry in memory after the snapshot.
+    session: Vec<usize>,
+}
+
+impl SessionHistory {
+    /// Start a session with a snapshot of a shared history.
+    pub fn new(shared: SharedHistory, options: HistoryOptions) -> Self {
+        let mut memory = MemoryHistory::new(options);
+        {
+            let shared = lock(&shared);
+            for entry in shared.iter() {
+                memory.insert(entry.into_owned());
+            }
+        }
+        Self {
+            snapshot: memory.len(),
+            memory,
+            shared,
+            tail: Vec::new(),
+            session: Vec::new(),
+        }
+    }
+
+    /// Entries added during the session that have not been merged.
+    pub fn tail(&self) -> &[HistoryEntry] {
+        &self.tail
+    }
+
+    /// Add the entries of this session to the shared history.
+    pub fn merge(&mut self) {
+        let mut shared = lock(&self.shared);
+        for entry in self.tail.drain(..) {
+            shared.push_entry(entry);
+        }
+        // Merged entries are handled like entries of the snapshot
+        self.snapshot = self.memory.len();
+        self.session.clear();
+    }
+
+    /// Remove an entry of the snapshot from the shared history,
+    /// `rank` is the number of equal entries before it.
+    fn remove_shared(&self, entry: &HistoryEntry, rank: usize) {
+        let mut shared = lock(&self.shared);
+        let index = (0..shared.len())
+            .filter(|index| shared.entry(*index).is_some_and(|e| *e == *entry))
+            .nth(rank);
+        if let Some(index) = index {
+            shared.remove(index);
+        }
+    }
+}
+
+impl Drop for SessionHistory {
+    fn drop(&mut self) {
+        self.merge();
+    }
+}
+
+impl History for SessionHistory {
+    fn entry(&self, index: usize) -> Option<Cow<'_, HistoryEntry>> {
+        self.memory.entry(index)
+    }
+
+    fn position(&self) -> Option<usize> {
+        self.memory.position()
+    }
+
+    fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+    }
+
+    fn is_last(&self) -> bool {
+        self.memory.is_last()
+    }
+
+    fn clear(&mut self) {
+        self.memory.clear();
+        self.tail.clear();
+        self.snapshot = 0;
+        self.session.clear();
+    }
+
+    fn retain(&mut self, keep: &mut dyn FnMut(&HistoryEntry) -> bool) {
+        let removed: Vec<usize> = (0..self.memory.len())
+            .filter(|index| {
+                self.memory.entry(*index).is_some_and(|entry| !keep(&entry))
+            })
+            .collect();
+        for index in removed.into_iter().rev() {
+            self.remove(index);
+        }
+        self.memory.cursor = Some(self.memory.len());
+    }
+
+    fn remove(&mut self, index: usize) -> Option<HistoryEntry> {
+        let entry = self.memory.remove(index)?;
+        if index < self.snapshot {
+            self.snapshot -= 1;
+            let rank = (0..index)
+                .filter(|i| self.memory.entry(*i).is_some_and(|e| *e == entry))
+                .count();
+            self.remove_shared(&entry, rank);
+        } else {
+            // Do not merge an entry removed from this session
+            let position = self.session.remove(index - self.snapshot);
+            self.tail.remove(position);
+            for other in &mut self.session {
+                if *other > position {
+                    *other -= 1;
+                }
+            }
+        }
+        Some(entry)
+    }
+
+    fn get(&self) -> Option<Cow<'_, str>> {
+        self.memory.get()
+    }
+
+    fn push_entry(&mut self, entry: HistoryEntry) {
+        let (snapshot, session) = (&mut self.snapshot, &mut self.session);
+        let stored = self.memory.insert_with(entry, &mut |index| {
+            if index < *snapshot {
+                *snapshot -= 1;
+            } else {
+                // Still merged, the shared history removes duplicates
+                session.remove(index - *snapshot);
+            }
+        });
+        if let Some(entry) = stored {
+            self.tail.push(entry.clone());
+            self.session.push(self.tail.len() - 1);
+        }
+    }
+
+    fn update_last(&mut self, update: &mut dyn FnMut(&mut HistoryEntry)) {
+        // Only an entry added during the session can be changed,
+        // the change is merged with the entry
+        if let Some(&position) = self.session.last() {
+            let entry = &mut self.tail[position];
+            update(entry);
+            let entry = entry.clone();
+            self.memory.update_last(&mut |last| *last = entry.clone());
+        }
+    }
+
+    fn previous(&mut self) -> Option<Cow<'_, str>> {
+        self.memory.previous()
+    }
+
+    fn next(&mut self) -> Option<Cow<'_, str>> {
+        self.memory.next()
+    }
+
+    fn previous_with_prefix(&mut self, prefix: &str) -> Option<Cow<'_, str>> {
+        self.memory.previous_with_prefix(prefix)
+    }
+
+    fn next_with_prefix(&mut self, prefix: &str) -> Option<Cow<'_, str>> {
+        self.memory.next_with_prefix(prefix)
+    }
+
+    fn previous_containing(&mut self, text: &str) -> Option<Cow<'_, str>> {
+        self.memory.previous_containing(text)
+    }
+
+    fn next_containing(&mut self, text: &str) -> Option<Cow<'_, str>> {
+        self.memory.next_containing(text)
+    }
+
+    fn move_by(&mut self, amount: i16) -> Option<Cow<'_, str>> {
+        self.memory.move_by(amount)
+    }
+
+    fn namespace(&self) -> Option<&str> {
+        self.memory.namespace()
+    }
+
+    fn set_namespace(&mut self, namespace: Option<String>) {
+        self.memory.set_namespace(namespace);
+    }
+
+    fn is_widened(&self) -> bool {
+        self.memory.is_widened()
+    }
+
+    fn set_widened(&mut self, widened: bool) {
+        self.memory.set_widened(widened);
+    }
+}
+
+/// Get the last word of an entry as it was typed.
+///
+/// Quotes and escapes are kept so the word can be inserted
+/// into another line unchanged.
+pub fn last_argument(entry: &HistoryEntry) -> Option<String> {
+    crate::shell::tokenize(&entry.value)
+        .pop()
+        .map(|token| entry.value[token.start..token.end].to_string())
+}
+
+/// Token inserted from the history by the last key press.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InsertedToken {
+    /// Index of the extractor, `None` for the last argument.
+    pub extractor: Option<usize>,
+    /// Number of entries before the newest the token came from.
+    pub offset: usize,
+    /// Grapheme index the token was inserted at.
+    pub start: usize,
+    /// Number of graphemes in the token.
+    pub len: usize,
+}
+
+/// Open a history file for reading and appending,
+/// creating it when it does not exist.
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)
+}
+
+/// Start of a line in a history file that sets a field of the
+/// entry on the lines before it, escaped items never start with
+/// a backslash followed by `#`.
+const FIELD_PREFIX: &str = "\\#";
+
+/// Read the entries of a history file.
+fn read_entries<R: BufRead>(reader: R) -> io::Result<Vec<HistoryEntry>> {
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        match line.strip_prefix(FIELD_PREFIX) {
+            Some(field) => {
+                if let Some(entry) = entries.last_mut() {
+                    read_field(entry, field);
+                }
+            }
+            None => entries.push(HistoryEntry::new(unescape_line(&line))),
+        }
+    }
+    Ok(entries)
+}
+
+/// Set a field of an entry from a `name=value` line, an empty
+/// value clears the field.
+fn read_field(entry: &mut HistoryEntry, field: &str) {
+    let (name, value) = field.split_once('=').unwrap_or((field, ""));
+    let value = Some(unescape_line(value)).filter(|v| !v.is_empty());
+    match name {
+        "namespace" => entry.namespace = value,
+        "exit_status" => {
+            entry.exit_status = value.and_then(|v| v.parse().ok());
+        }
+        "duration" => {
+            entry.duration =
+                value.and_then(|v| v.parse().ok()).map(Duration::from_nanos);
+        }
+        "working_directory" => {
+            entry.working_directory = value.map(PathBuf::from);
+        }
+        // Fields written by newer versions are ignored
+        _ => {}
+    }
+}
+
+/// Values of the fields of an entry as they are written,
+/// the duration is written in nanoseconds.
+fn fields(entry: &HistoryEntry) -> [(&'static str, Option<String>); 4] {
+    [
+        ("namespace", entry.namespace.clone()),
+        ("exit_status", entry.exit_status.map(|s| s.to_string())),
+        ("duration", entry.duration.map(|d| d.as_nanos().to_string())),
+        (
+            "working_directory",
+            entry
+                .working_directory
+                .as_ref()
+                .map(|p| p.to_string_lossy().into_owned()),
+        ),
+    ]
+}
+
+/// Write the lines for the fields that changed in an entry,
+/// a field that was removed is written without a value.
+fn write_changed_fields(
+    lines: &mut String,
+    before: &HistoryEntry,
+    after: &HistoryEntry,
+) {
+    for ((name, before), (_, after)) in
+        fields(before).into_iter().zip(fields(after))
+    {
+        if before != after {
+            write_field(lines, name, after.as_deref().unwrap_or_default());
+        }
+    }
+}
+
+/// Write the lines for an entry and its fields.
+fn write_entry(lines: &mut String, entry: &HistoryEntry) {
+    lines.push_str(&escape_line(&entry.value));
+    lines.push('\n');
+    for (name, value) in fields(entry) {
+        if let Some(value) = value {
+            write_field(lines, name, &value);
+        }
+    }
+}
+
+/// Write the line for a field of the entry before it.
+fn write_field(lines: &mut String, name: &str, value: &str) {
+    lines.push_str(FIELD_PREFIX);
+    lines.push_str(name);
+    lines.push('=');
+    lines.push_str(&escape_line(value));
+    lines.push('\n');
+}
+
+/// Escape an item so it can be written on a single line.
+fn escape_line(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Reverse the escaping of an item read from a line.
+fn unescape_line(line: &str) -> String {
+    let mut value = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                value.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                value.push('\\');
+                chars.next();
+            }
+            _ => value.push(c),
+        }
+    }
+    value
+}
+
+/// Registry of named histories for a session.
+///
+/// Prompts within a session can select a history by name so
+/// that, for example, a sub-mode keeps its own history.
+#[derive(Default)]
+pub struct HistoryRegistry {
+    histories: HashMap<String, SharedHistory>,
+}
+
+impl HistoryRegistry {
+    /// Create a new history registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a named history, replacing any existing history
+    /// with the same name.
+    pub fn insert<S: Into<String>>(&mut self, name: S, history: SharedHistory) {
+        self.histories.insert(name.into(), history);
+    }
+
+    /// Get a named history.
+    pub fn get(&self, name: &str) -> Option<SharedHistory> {
+        self.histories.get(name).cloned()
+    }
+
+    /// Get a named history creating an in-memory history
+    /// when it does not exist.
+    pub fn get_or_create(
+        &mut self,
+        name: &str,
+        options: HistoryOptions,
+    ) -> SharedHistory {
+        self.histories
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                Arc::new(Mutex::new(MemoryHistory::new(options)))
+            })
+            .clone()
+    }
+
+    /// Remove a named history.
+    pub fn remove(&mut self, name: &str) -> Option<SharedHistory> {
+        self.histories.remove(name)
+    }
+}
+
+/// Error for a history designator that does not match an entry.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExpansionError {
+    /// The designator without the leading `!`.
+    pub designator: String,
+}
+
+impl fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "!{}: event not found", self.designator)
+    }
+}
+
+impl std::error::Error for ExpansionError {}
+
+/// Expand history designators in a line.
+///
+/// Supports `!!` for the last entry, `!n` for entry `n` counting
+/// from one, `!-n` for the entry `n` back and `!prefix` for the
+/// most recent entry starting with `prefix`. Designators inside
+/// single quotes or escaped with a backslash are not expanded.
+///
+/// Returns `None` when the line contains no designators.
+pub fn expand(
+    line: &str,
+    history: &dyn History,
+) -> Result<Option<String>, ExpansionError> {
+    let mut expanded = String::with_capacity(line.len());
+    let mut changed = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        match (quote, c) {
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                expanded.push(c);
+                if let Some((_, next)) = chars.next() {
+                    expanded.push(next);
+                }
+                continue;
+            }
+            (_, '!') => {
+                let rest = &line[index + 1..];
+                let designator = if rest.starts_with('!') {
+                    "!"
+                } else {
+                    let end = rest
+                        .find(|c: char| {
+                            c.is_whitespace() || "!;|&()<>'\"=".contains(c)
+                        })
+                        .unwrap_or(rest.len());
+                    &rest[..end]
+                };
+                if !designator.is_empty() {
+                    expanded.push_str(
+                        &lookup(designator, history).ok_or_else(|| {
+                            ExpansionError {
+                                designator: designator.to_string(),
+                            }
+                        })?,
+                    );
+                    changed = true;
+                    for _ in designator.chars() {
+                        chars.next();
+                    }
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        expanded.push(c);
+    }
+
+    Ok(if changed { Some(expanded) } else { None })
+}
+
+/// Find the entry for a history designator.
+fn lookup(designator: &str, history: &dyn History) -> Option<String> {
+    let mut visible = history.iter().rev().filter(|entry| {
+        history.is_widened()
+            || entry.namespace.as_deref() == history.namespace()
+    });
+
+    if designator == "!" {
+        return visible.next().map(|entry| entry.value.clone());
+    }
+
+    if let Some(back) = designator.strip_prefix('-') {
+        if let Ok(back) = back.parse::<usize>() {
+            return visible
+                .nth(back.checked_sub(1)?)
+                .map(|entry| entry.value.clone());
+        }
+    } else if let Ok(number) = designator.parse::<usize>() {
+        return history
+            .entry(number.checked_sub(1)?)
+            .map(|entry| entry.value.clone());
+    }
+
+    visible
+        .find(|entry| entry.value.starts_with(designator))
+        .map(|entry| entry.value.clone())
+}
+
+/// Match text against a glob pattern.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Position to resume from after the last star
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some('\\') if pattern.get(p + 1) == Some(&text[t]) => {
+                p += 2;
+                t += 1;
+                continue;
+            }
+            Some(c) if *c != '\\' && *c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        match backtrack {
+            Some((star_p, star_t)) => {
+                backtrack = Some((star_p, star_t + 1));
+                p = star_p;
+                t = star_t + 1;
+            }
+            None => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(history: &MemoryHistory) -> Vec<String> {
+        Entries::new(history).map(|e| e.value.clone()).collect()
+    }
+
+    #[test]
+    fn history_basic() {
+        let mut history = MemoryHistory::new(Default::default());
+        history.push("foo".to_string());
+        assert!(!history.is_empty());
+        assert_eq!(1, history.len());
+
+        history.push("baz".to_string());
+        assert_eq!(Some(2), history.position());
+
+        assert_eq!(Some("baz"), history.previous().as_deref());
+        assert_eq!(Some("foo"), history.previous().as_deref());
+        assert_eq!(Some("baz"), history.next().as_deref());
+        assert_eq!(None, history.next());
+
+        assert_eq!(Some(2), history.position());
+        assert_eq!(None, history.get());
+    }
+
+    #[test]
+    fn history_dedup() {
+        let items = ["ls", "ls", "pwd", "ls"];
+        let pushed = |dedup| {
+            let mut history = MemoryHistory::new(HistoryOptions {
+                dedup,
+                ..Default::default()
+            });
+            for item in items {
+                history.push(item.to_string());
+            }
+            assert_eq!(Some(history.len()), history.position());
+            values(&history)
+        };
+
+        assert_eq!(items.to_vec(), pushed(DedupPolicy::None));
+        assert_eq!(
+            vec!["ls", "pwd", "ls"],
+            pushed(DedupPolicy::IgnoreConsecutive)
+        );
+        assert_eq!(vec!["pwd", "ls"], pushed(DedupPolicy::EraseAll));
+    }
+
+    #[test]
+    fn history_maximum_size() {
+        let mut history = MemoryHistory::new(HistoryOptions {
+            maximum_size: Some(2),
+            ..Default::default()
+        });
+        for item in ["a", "b", "c"] {
+            history.push(item.to_string());
+        }
+        assert_eq!(vec!["b", "c"], values(&history));
+
+        let mut history = MemoryHistory::new(HistoryOptions {
+            maximum_size: None,
+            ..Default::default()
+        });
+        for i in 0..5000 {
+            history.push(i.to_string());
+        }
+        assert_eq!(5000, history.len());
+    }
+
+    #[test]
+    fn history_ignore() {
+        assert!(glob_match("exit", "exit"));
+        assert!(!glob_match("exit", "exit 1"));
+        assert!(glob_match("ls*", "ls -la"));
+        assert!(glob_match("*token=*", "curl -d token=abc url"));
+        assert!(glob_match("?d", "cd"));
+        assert!(!glob_match("?d", "d"));
+        assert!(glob_match("a\\*", "a*"));
+        assert!(!glob_match("a\\*", "ab"));
+
+        let mut history = MemoryHistory::new(HistoryOptions {
+            ignore: vec!["exit".to_string(), "*secret*".to_string()],
+            ..Default::default()
+        });
+        for item in ["ls", "exit", "export SECRET=1", "echo secret"] {
+            history.push(item.to_string());
+        }
+        assert_eq!(vec!["ls", "export SECRET=1"], values(&history));
+        assert_eq!(Some(2), history.position());
+    }
+
+    #[test]
+    fn history_ignore_leading_space() {
+        let options = HistoryOptions {
+            ignore_leading_space: true,
+            ..Default::default()
+        };
+        assert!(options.is_ignored(" ls"));
+        assert!(options.is_ignored("  "));
+        assert!(!options.is_ignored("ls "));
+        assert!(!options.is_ignored("\tls"));
+        assert!(!HistoryOptions::default().is_ignored(" ls"));
+
+        let mut history = MemoryHistory::new(options);
+        for item in ["ls", " export TOKEN=1", "pwd "] {
+            history.push(item.to_string());
+        }
+        assert_eq!(vec!["ls", "pwd "], values(&history));
+        assert_eq!(Some(2), history.position());
+    }
+
+    #[test]
+    fn history_on_add() {
+        let mut history = MemoryHistory::new(HistoryOptions {
+            on_add: Some(Box::new(|mut entry| {
+                if entry.value == "clear" {
+                    return None;
+                }
+                if let Some(index) = entry.value.find("token=") {
+                    entry.value.replace_range(index + 6.., "***");
+                }
+                Some(entry)
+            })),
+            ..Default::default()
+        });
+        for item in ["ls", "clear", "curl -d token=abc"] {
+            history.push(item.to_string());
+        }
+        assert_eq!(vec!["ls", "curl -d token=***"], values(&history));
+        assert!(history.is_last());
+    }
+
+    #[test]
+    fn history_entry_metadata() {
+        let mut history = MemoryHistory::new(Default::default());
+        history.set_namespace(Some("sql".to_string()));
+        history.push("SELECT 1".to_string());
+        history.update_last(&mut |entry| {
+            entry.exit_status = Some(0);
+            entry.duration = Some(Duration::from_millis(5));
+        });
+        let entry = history.entry(0).unwrap();
+        assert_eq!(Some("sql"), entry.namespace.as_deref());
+        assert_eq!(Some(0), entry.exit_status);
+        assert_eq!(Some(Duration::from_millis(5)), entry.duration);
+    }
+
+    #[test]
+    fn history_namespaces() {
+        let mut history = MemoryHistory::new(Default::default());
+        history.push("ls".to_string());
+        history.set_namespace(Some("sql".to_string()));
+        history.push("SELECT 1".to_string());
+        history.set_namespace(None);
+        history.push("pwd".to_string());
+
+        assert_eq!(Some("pwd"), history.previous().as_deref());
+        assert_eq!(Some("ls"), history.previous().as_deref());
+        assert_eq!(Some("ls"), history.previous().as_deref());
+        assert_eq!(Some("pwd"), history.next().as_deref());
+        assert_eq!(None, history.next());
+
+        history.set_namespace(Some("sql".to_string()));
+        assert_eq!(Some("SELECT 1"), history.previous().as_deref());
+        assert_eq!(Some("SELECT 1"), history.previous().as_deref());
+
+        history.set_widened(true);
+        assert_eq!(Some("ls"), history.previous().as_deref());
+        assert_eq!(Some("SELECT 1"), history.next().as_deref());
+    }
+
+    /// History relying on the default searches.
+    struct Minimal(MemoryHistory);
+
+    impl History for Minimal {
+        fn entry(&self, index: usize) -> Option<Cow<'_, HistoryEntry>> {
+            self.0.entry(index)
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        fn is_last(&self) -> bool {
+            self.0.is_last()
+        }
+
+        fn clear(&mut self) {
+            self.0.clear()
+        }
+
+        fn retain(&mut self, keep: &mut dyn FnMut(&HistoryEntry) -> bool) {
+            self.0.retain(keep)
+        }
+
+        fn remove(&mut self, index: usize) -> Option<HistoryEntry> {
+            self.0.remove(index)
+        }
+
+        fn push_entry(&mut self, entry: HistoryEntry) {
+            self.0.push_entry(entry)
+        }
+
+        fn update_last(&mut self, update: &mut dyn FnMut(&mut HistoryEntry)) {
+            self.0.update_last(update)
+        }
+
+        fn get(&self) -> Option<Cow<'_, str>> {
+            self.0.get()
+        }
+
+        fn move_by(&mut self, amount: i16) -> Option<Cow<'_, str>> {
+            self.0.move_by(amount)
+        }
+
+        fn position(&self) -> Option<usize> {
+            self.0.position()
+        }
+
+        fn previous(&mut self) -> Option<Cow<'_, str>> {
+            self.0.previous()
+        }
+
+        fn next(&mut self) -> Option<Cow<'_, str>> {
+            self.0.next()
+        }
+
+        fn namespace(&self) -> Option<&str> {
+            self.0.namespace()
+        }
+
+        fn set_namespace(&mut self, namespace: Option<String>) {
+            self.0.set_namespace(namespace)
+        }
+
+        fn is_widened(&self) -> bool {
+            self.0.is_widened()
+        }
+
+        fn set_widened(&mut self, widened: bool) {
+            self.0.set_widened(widened)
+        }
+    }
+
+    #[test]
+    fn history_prefix() {
+        check_prefix(&mut MemoryHistory::new(Default::default()));
+        check_prefix(&mut Minimal(MemoryHistory::new(Default::default())));
+
+        let mut history = Minimal(MemoryHistory::new(Default::default()));
+        history.push("git status".to_string());
+        history.set_namespace(Some("sql".to_string()));
+        history.push("git log".to_string());
+        history.set_namespace(None);
+        assert_eq!(
+            Some("git status"),
+            history.previous_with_prefix("git").as_deref()
+        );
+        assert_eq!(None, history.next_with_prefix("git"));
+    }
+
+    fn check_prefix(history: &mut dyn History) {
+        for item in ["git status", "ls", "git log", "cargo test"] {
+            history.push(item.to_string());
+        }
+
+        assert_eq!(
+            Some("git log"),
+            history.previous_with_prefix("git").as_deref()
+        );
+        assert_eq!(
+            Some("git status"),
+            history.previous_with_prefix("git").as_deref()
+        );
+        assert_eq!(None, history.previous_with_prefix("git"));
+        assert_eq!(Some("git status"), history.get().as_deref());
+        assert_eq!(Some("git log"), history.next_with_prefix("git").as_deref());
+        assert_eq!(None, history.next_with_prefix("git"));
+        assert!(history.is_last());
+    }
+
+    #[test]
+    fn history_substring() {
+        check_substring(&mut MemoryHistory::new(Default::default()));
+        check_substring(&mut Minimal(MemoryHistory::new(Default::default())));
+
+        let mut history = Minimal(MemoryHistory::new(Default::default()));
+        history.push("cargo test".to_string());
+        history.set_namespace(Some("sql".to_string()));
+        history.push("SELECT test".to_string());
+        history.set_namespace(None);
+        assert_eq!(
+            Some("cargo test"),
+            history.previous_containing("test").as_deref()
+        );
+        assert_eq!(None, history.next_containing("test"));
+    }
+
+    fn check_substring(history: &mut dyn History) {
+        for item in ["cargo test", "ls", "git log --stat", "cargo build"] {
+            history.push(item.to_string());
+        }
+
+        assert_eq!(
+            Some("git log --stat"),
+            history.previous_containing("st").as_deref()
+        );
+        assert_eq!(
+            Some("cargo test"),
+            history.previous_containing("st").as_deref()
+        );
+        assert_eq!(None, history.previous_containing("st"));
+        assert_eq!(
+            Some("git log --stat"),
+            history.next_containing("st").as_deref()
+        );
+        assert_eq!(None, history.next_containing("st"));
+        assert!(history.is_last());
+    }
+
+    #[test]
+    fn history_navigator_draft() {
+        let mut history = MemoryHistory::new(Default::default());
+        for item in ["git status", "ls", "git log"] {
+            history.push(item.to_string());
+        }
+
+        let mut nav = HistoryNavigator::new();
+        assert_eq!(None, nav.next(&mut history, Match::Any, "gi", 1));
+        let draft = nav.previous(&mut history, Match::Prefix, "gi", 1);
+        assert_eq!(
+            Some(Draft {
+                line: "git log".to_string(),
+                cursor: 1
+            }),
+            draft
+        );
+        let draft = nav.previous(&mut history, Match::Any, "git log", 7);
+        assert_eq!(Some(Draft::at_end("ls".to_string())), draft);
+        nav.next(&mut history, Match::Any, "ls", 2);
+        let draft = nav.next(&mut history, Match::Any, "git log", 7);
+        assert_eq!(
+            Some(Draft {
+                line: "gi".to_string(),
+                cursor: 1
+            }),
+            draft
+        );
+        assert!(history.is_last());
+
+        let draft = nav.first(&mut history, "draft", 2);
+        assert_eq!(Some(Draft::at_end("git status".to_string())), draft);
+        let draft = nav.first(&mut history, "git status", 10);
+        assert_eq!(Some(Draft::at_end("git status".to_string())), draft);
+        let draft = nav.last(&mut history, "git status", 10);
+        assert_eq!(
+            Some(Draft {
+                line: "draft".to_string(),
+                cursor: 2
+            }),
+            draft
+        );
+        assert_eq!(None, nav.last(&mut history, "draft", 2));
+    }
+
+    #[test]
+    fn history_navigator_first() {
+        let mut history = MemoryHistory::new(Default::default());
+        history.set_namespace(Some("sql".to_string()));
+        history.push("SELECT 1".to_string());
+        history.set_namespace(None);
+        for item in ["ls", "pwd"] {
+            history.push(item.to_string());
+        }
+
+        let mut nav = HistoryNavigator::new();
+        let draft = nav.first(&mut history, "draft", 2);
+        assert_eq!(Some(Draft::at_end("ls".to_string())), draft);
+        assert_eq!(Some(1), history.position());
+        let draft = nav.next(&mut history, Match::Any, "ls", 2);
+        assert_eq!(Some(Draft::at_end("pwd".to_string())), draft);
+        let draft = nav.next(&mut history, Match::Any, "pwd", 3);
+        assert_eq!(
+            Some(Draft {
+                line: "draft".to_string(),
+                cursor: 2
+            }),
+            draft
+        );
+
+        history.set_namespace(Some("json".to_string()));
+        assert_eq!(None, nav.first(&mut history, "", 0));
+    }
+
+    #[test]
+    fn history_last_argument() {
+        let entry = HistoryEntry::new("cp a.txt 'my dir/'");
+        assert_eq!(Some("'my dir/'".to_string()), last_argument(&entry));
+        assert_eq!(None, last_argument(&HistoryEntry::new("  ")));
+    }
+
+    #[test]
+    fn history_expand() {
+        let mut history = MemoryHistory::new(Default::default());
+        for item in ["git status", "ls -la", "echo hi"] {
+            history.push(item.to_string());
+        }
+
+        let expand = |line| expand(line, &history);
+        assert_eq!(Ok(None), expand("echo 'no!!' \\!! !"));
+        assert_eq!(Ok(Some("echo hi | wc".to_string())), expand("!! | wc"));
+        assert_eq!(Ok(Some("sudo git status".to_string())), expand("sudo !1"));
+        assert_eq!(Ok(Some("ls -la;echo hi".to_string())), expand("!-2;!e"));
+        assert_eq!(Ok(Some("git status".to_string())), expand("!git"));
+        assert_eq!(
+            Err(ExpansionError {
+                designator: "4".to_string()
+            }),
+            expand("!4")
+        );
+        assert_eq!(
+            "!cargo: event not found",
+            expand("!cargo").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn history_file_append() -> io::Result<()> {
+        let path = std::env::temp_dir()
+            .join(format!("crossterm-prompt-{}.history", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let options = || HistoryOptions {
+            dedup: DedupPolicy::IgnoreConsecutive,
+            ..Default::default()
+        };
+
+        let mut history = FileHistory::open(&path, options())?.sync(true);
+        history.push("ls".to_string());
+        history.push("ls".to_string());
+        history.push("echo 'a\\nb'\nwc -l".to_string());
+
+        // Items are on disk before the history is dropped
+        let reopened = FileHistory::open(&path, options())?;
+        assert_eq!(vec!["ls", "echo 'a\\nb'\nwc -l"], values(&reopened.memory));
+        assert!(history.take_error().is_none());
+
+        history.push("cargo test".to_string());
+        history.retain(&mut |entry| !entry.value.contains('\n'));
+        assert_eq!(Some(HistoryEntry::new("ls")), history.remove(0));
+        history.push("make".to_string());
+        let reopened = FileHistory::open(&path, options())?;
+        assert_eq!(vec!["cargo test", "make"], values(&reopened.memory));
+        assert!(history.take_error().is_none());
+        // The file is replaced by the rewritten file
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+        assert!(!Path::new(&temp_path).exists());
+        drop(history);
+
+        std::fs::remove_file(&path)
+    }
+
+    #[test]
+    fn history_file_namespace() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!(
+            "crossterm-prompt-{}-namespace.history",
+            std::process::id()
+        ));
+        // Items written before namespaces were stored
+        std::fs::write(&path, "ls\n\\\\#not a field\n")?;
+
+        let mut history = FileHistory::open(&path, Default::default())?;
+        history.set_namespace(Some("sql".to_string()));
+        history.push("SELECT 1".to_string());
+        history.set_namespace(Some("a=b\nc".to_string()));
+        history.push("pwd".to_string());
+        drop(history);
+
+        let mut reopened = FileHistory::open(&path, Default::default())?;
+        assert_eq!(
+            vec!["ls", "\\#not a field", "SELECT 1", "pwd"],
+            values(&reopened.memory)
+        );
+        let namespaces: Vec<_> = reopened
+            .memory
+            .items
+            .iter()
+            .map(|e| e.namespace.clone())
+            .collect();
+        assert_eq!(
+            vec![
+                None,
+                None,
+                Some("sql".to_string()),
+                Some("a=b\nc".to_string())
+            ],
+            namespaces
+        );
+        reopened.set_namespace(Some("sql".to_string()));
+        assert_eq!(Some("SELECT 1"), reopened.previous().as_deref());
+        assert_eq!(Some("SELECT 1"), reopened.previous().as_deref());
+        drop(reopened);
+
+        std::fs::remove_file(&path)
+    }
+
+    #[test]
+    fn history_file_metadata() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!(
+            "crossterm-prompt-{}-metadata.history",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let mut history = FileHistory::open(&path, Default::default())?;
+        history.push_entry(HistoryEntry {
+            value: "make".to_string(),
+            working_directory: Some(PathBuf::from("/src")),
+            ..Default::default()
+        });
+        history.update_last(&mut |entry| {
+            entry.exit_status = Some(2);
+            entry.duration = Some(Duration::from_micros(1500));
+        });
+        history.push("ls".to_string());
+        history.update_last(&mut |entry| entry.exit_status = Some(0));
+        history.update_last(&mut |entry| entry.exit_status = None);
+        assert!(history.take_error().is_none());
+        drop(history);
+
+        let reopened = FileHistory::open(&path, Default::default())?;
+        let entries: Vec<_> = reopened.memory.items.iter().cloned().collect();
+        assert_eq!(
+            vec![
+                HistoryEntry {
+                    value: "make".to_string(),
+                    exit_status: Some(2),
+                    duration: Some(Duration::from_micros(1500)),
+                    working_directory: Some(PathBuf::from("/src")),
+                    ..Default::default()
+                },
+                HistoryEntry::new("ls"),
+            ],
+            entries
+        );
+
+        std::fs::remove_file(&path)
+    }
+
+    #[test]
+    fn history_handles() {
+        let options = crate::PromptOptions::new()
+            .history(RefCell::new(MemoryHistory::new(Default::default())));
+        options.acquire_history().unwrap().push("ls".to_string());
+        assert_eq!(1, options.acquire_history().unwrap().len());
+
+        let local =
+            Rc::new(RefCell::new(MemoryHistory::new(Default::default())));
+        let options = crate::PromptOptions::new().history(Rc::clone(&local));
+        options.acquire_history().unwrap().push("pwd".to_string());
+        assert_eq!(1, local.borrow().len());
+
+        let shared: SharedHistory =
+            Arc::new(Mutex::new(MemoryHistory::new(Default::default())));
+        shared.acquire().push("cd".to_string());
+        assert_eq!(1, lock(&shared).len());
+    }
+
+    #[test]
+    fn history_session_merge() {
+        let shared: SharedHistory =
+            Arc::new(Mutex::new(MemoryHistory::new(Default::default())));
+        shared.lock().unwrap().push("ls".to_string());
+
+        let mut first = SessionHistory::new(shared.clone(), Default::default());
+        let mut second =
+            SessionHistory::new(shared.clone(), Default::default());
+        first.push("make".to_string());
+        second.push("vim".to_string());
+        first.push("make test".to_string());
+
+        assert_eq!(vec!["ls", "make", "make test"], values(&first.memory));
+        assert_eq!(1, shared.lock().unwrap().len());
+
+        drop(second);
+        drop(first);
+        let shared = shared.lock().unwrap();
+        let merged: Vec<String> =
+            shared.iter().map(|e| e.value.clone()).collect();
+        assert_eq!(vec!["ls", "vim", "make", "make test"], merged);
+    }
+
+    #[test]
+    fn history_session_retain() {
+        let shared: SharedHistory =
+            Arc::new(Mutex::new(MemoryHistory::new(Default::default())));
+        shared.lock().unwrap().push("ls".to_string());
+
+        let mut session =
+            SessionHistory::new(shared.clone(), Default::default());
+        for item in ["make", "rm -rf target", "make test"] {
+            session.push(item.to_string());
+        }
+        let mut calls = 0;
+        session.retain(&mut |entry| {
+            calls += 1;
+            !entry.value.starts_with("rm")
+        });
+        assert_eq!(4, calls);
+        assert_eq!(vec!["ls", "make", "make test"], values(&session.memory));
+        let tail: Vec<_> = session.tail().iter().map(|e| &e.value).collect();
+        assert_eq!(vec!["make", "make test"], tail);
+    }
+
+    #[test]
+    fn history_session_remove() {
+        let shared: SharedHistory =
+            Arc::new(Mutex::new(MemoryHistory::new(Default::default())));
+        for item in ["make", "ls", "make"] {
+            shared.lock().unwrap().push(item.to_string());
+        }
+
+        // Removing the snapshot entry keeps the session entry
+        let mut session =
+            SessionHistory::new(shared.clone(), Default::default());
+        session.push("make".to_string());
+        session.push("ls".to_string());
+        assert_eq!(
+            Some("make"),
+            session.remove(2).as_ref().map(|e| &e.value[..])
+        );
+        assert_eq!(vec!["make", "ls", "make", "ls"], values(&session.memory));
+        assert_eq!(2, session.tail().len());
+        assert_eq!(2, lock(&shared).len());
+
+        // Removing the session entry keeps the snapshot entry
+        assert_eq!(
+            Some("ls"),
+            session.remove(3).as_ref().map(|e| &e.value[..])
+        );
+        let tail: Vec<_> = session.tail().iter().map(|e| &e.value).collect();
+        assert_eq!(vec!["make"], tail);
+        drop(session);
+
+        let session = SessionHistory::new(shared.clone(), Default::default());
+        assert_eq!(vec!["make", "ls", "make"], values(&session.memory));
+    }
+
+    #[test]
+    fn history_session_retain_shared() {
+        let shared: SharedHistory =
+            Arc::new(Mutex::new(MemoryHistory::new(Default::default())));
+        for item in ["ls", "rm -rf /tmp/a", "pwd"] {
+            shared.lock().unwrap().push(item.to_string());
+        }
+        let mut session =
+            SessionHistory::new(shared.clone(), Default::default());
+        session.push("rm -rf /tmp/b".to_string());
+        session.retain(&mut |entry| !entry.value.starts_with("rm"));
+        assert_eq!(vec!["ls", "pwd"], values(&session.memory));
+        drop(session);
+
+        // The removed entries do not come back in the next session
+        let session = SessionHistory::new(shared.clone(), Default::default());
+        assert_eq!(vec!["ls", "pwd"], values(&session.memory));
+    }
+
+    #[test]
+    fn history_session_poisoned() {
+        let shared: SharedHistory =
+            Arc::new(Mutex::new(MemoryHistory::new(Default::default())));
+        let mut session =
+            SessionHistory::new(shared.clone(), Default::default());
+        session.push("ls".to_string());
+
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = shared.lock().unwrap();
+            panic!("poison the history");
+        }));
+        assert!(shared.is_poisoned());
+
+        // Merged when dropped without panicking
+        drop(session);
+        assert_eq!(1, lock(&shared).len());
+    }
+
+    #[test]
+    fn history_registry() {
+        let mut registry = HistoryRegistry::new();
+        let shell = registry.get_or_create("shell", Default::default());
+        let sql = registry.get_or_create("sql", Default::default());
+        shell.lock().unwrap().push("ls".to_string());
+        sql.lock().unwrap().push("SELECT 1".to_string());
+
+        let shell = registry.get("shell").unwrap();
+        assert_eq!(1, shell.lock().unwrap().len());
+        let sql = registry.get_or_create("sql", Default::default());
+        assert_eq!("SELECT 1", sql.lock().unwrap().entry(0).unwrap().value);
+
+        assert!(registry.remove("sql").is_some());
+        assert!(registry.get("sql").is_none());
     }
 }
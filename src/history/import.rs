@@ -0,0 +1,148 @@
+//! Parsers for the history files of other shells.
+use std::time::Duration;
+
+use super::HistoryEntry;
+
+/// Parse a bash history file.
+///
+/// Timestamp comments written when `HISTTIMEFORMAT` is set
+/// are skipped.
+pub(crate) fn bash(text: &str) -> Vec<HistoryEntry> {
+    text.lines()
+        .filter(|line| !line.is_empty() && !is_timestamp(line))
+        .map(HistoryEntry::new)
+        .collect()
+}
+
+/// Parse a zsh history file.
+///
+/// Both the plain and the extended (`: start:elapsed;command`)
+/// formats are supported; lines ending in a backslash continue
+/// the command on the next line and metafied bytes are decoded.
+pub(crate) fn zsh(bytes: &[u8]) -> Vec<HistoryEntry> {
+    let text = String::from_utf8_lossy(&unmetafy(bytes)).into_owned();
+    let mut entries = Vec::new();
+    let mut lines = text.lines();
+
+    while let Some(line) = lines.next() {
+        let mut command = line.to_string();
+        while command.ends_with('\\') {
+            command.pop();
+            command.push('\n');
+            match lines.next() {
+                Some(next) => command.push_str(next),
+                None => break,
+            }
+        }
+
+        let mut entry = match extended(&command) {
+            Some((elapsed, value)) => {
+                let mut entry = HistoryEntry::new(value);
+                entry.duration = Some(Duration::from_secs(elapsed));
+                entry
+            }
+            None => HistoryEntry::new(command),
+        };
+        if !entry.value.is_empty() {
+            entry.value = entry.value.trim_end_matches('\n').to_string();
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+/// Parse a fish history file.
+///
+/// Only the `- cmd:` lines of the YAML-like format are used,
+/// escaped newlines and backslashes are decoded.
+pub(crate) fn fish(text: &str) -> Vec<HistoryEntry> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("- cmd: "))
+        .map(|command| {
+            let mut value = String::with_capacity(command.len());
+            let mut chars = command.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some('n')) => {
+                        value.push('\n');
+                        chars.next();
+                    }
+                    ('\\', Some('\\')) => {
+                        value.push('\\');
+                        chars.next();
+                    }
+                    _ => value.push(c),
+                }
+            }
+            HistoryEntry::new(value)
+        })
+        .collect()
+}
+
+/// Determine if a bash history line is a timestamp comment.
+fn is_timestamp(line: &str) -> bool {
+    match line.strip_prefix('#') {
+        Some(digits) => {
+            !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// Split a zsh extended history line into the elapsed
+/// seconds and the command.
+fn extended(line: &str) -> Option<(u64, &str)> {
+    let rest = line.strip_prefix(": ")?;
+    let (header, command) = rest.split_once(';')?;
+    let (_start, elapsed) = header.split_once(':')?;
+    Some((elapsed.parse().ok()?, command))
+}
+
+/// Decode the bytes zsh escapes with the meta character.
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    const META: u8 = 0x83;
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    while let Some(byte) = bytes.next() {
+        if *byte == META {
+            if let Some(next) = bytes.next() {
+                decoded.push(next ^ 32);
+            }
+        } else {
+            decoded.push(*byte);
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(entries: Vec<HistoryEntry>) -> Vec<String> {
+        entries.into_iter().map(|e| e.value).collect()
+    }
+
+    #[test]
+    fn import_bash() {
+        let text = "#1609459200\nls -la\n\n#1609459201\ncd /tmp\n# comment\n";
+        assert_eq!(vec!["ls -la", "cd /tmp", "# comment"], values(bash(text)));
+    }
+
+    #[test]
+    fn import_zsh() {
+        let mut bytes =
+            b": 1609459200:3;make\n: 1609459210:0;echo a\\\nb\n".to_vec();
+        bytes.extend_from_slice(b"echo \xe2\x82\x83\xa3\n");
+        let entries = zsh(&bytes);
+        assert_eq!(Some(Duration::from_secs(3)), entries[0].duration);
+        assert_eq!(vec!["make", "echo a\nb", "echo \u{2083}"], values(entries));
+    }
+
+    #[test]
+    fn import_fish() {
+        let text = "- cmd: echo a\\nb\n  when: 1609459200\n  paths:\n    \
+                    - foo\n- cmd: ls C:\\\\\n  when: 1609459201\n";
+        assert_eq!(vec!["echo a\nb", "ls C:\\"], values(fish(text)));
+    }
+}
@@ -0,0 +1,113 @@
+//! Hyperlinks using the OSC 8 escape sequence.
+//!
+//! A [Hyperlink] can be formatted into a prompt prefix or into a
+//! message shown below the prompt; when the terminal does not
+//! support hyperlinks only the text is written.
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Text that links to a URL.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Hyperlink {
+    url: String,
+    text: String,
+    enabled: bool,
+}
+
+impl Hyperlink {
+    /// Create a hyperlink, the escape sequence is only written
+    /// when the terminal is detected to support hyperlinks.
+    pub fn new<U: Into<String>, T: Into<String>>(url: U, text: T) -> Self {
+        Self {
+            url: url.into(),
+            text: text.into(),
+            enabled: is_supported(),
+        }
+    }
+
+    /// Override whether the escape sequence is written.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+}
+
+impl fmt::Display for Hyperlink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.enabled {
+            write!(f, "\x1B]8;;{}\x1B\\{}\x1B]8;;\x1B\\", self.url, self.text)
+        } else {
+            write!(f, "{}", self.text)
+        }
+    }
+}
+
+/// Determine if the terminal supports hyperlinks.
+///
+/// Detection uses the environment and is performed once, set
+/// `FORCE_HYPERLINK` to `1` or `0` to override the result.
+pub fn is_supported() -> bool {
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+    *SUPPORTED.get_or_init(|| detect(|name| std::env::var(name).ok()))
+}
+
+/// Detect hyperlink support from environment variables.
+fn detect<F: Fn(&str) -> Option<String>>(var: F) -> bool {
+    if let Some(force) = var("FORCE_HYPERLINK") {
+        return force != "0";
+    }
+    let term = var("TERM").unwrap_or_default();
+    if term == "dumb" {
+        return false;
+    }
+    if var("WT_SESSION").is_some() || var("KONSOLE_VERSION").is_some() {
+        return true;
+    }
+    if let Some(version) = var("VTE_VERSION") {
+        return version.parse::<u32>().is_ok_and(|v| v >= 5000);
+    }
+    if let Some(program) = var("TERM_PROGRAM") {
+        if matches!(&program[..], "iTerm.app" | "WezTerm" | "vscode" | "Hyper")
+        {
+            return true;
+        }
+    }
+    ["kitty", "alacritty", "foot", "wezterm"]
+        .iter()
+        .any(|name| term.contains(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(
+        vars: &'a [(&'a str, &'a str)],
+    ) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn hyperlink_detect() {
+        assert!(detect(env(&[("TERM", "xterm-kitty")])));
+        assert!(detect(env(&[("VTE_VERSION", "6003")])));
+        assert!(!detect(env(&[("VTE_VERSION", "4200")])));
+        assert!(!detect(env(&[("TERM", "xterm-256color")])));
+        assert!(!detect(env(&[("TERM", "dumb"), ("WT_SESSION", "1")])));
+        assert!(detect(env(&[("TERM", "dumb"), ("FORCE_HYPERLINK", "1")])));
+    }
+
+    #[test]
+    fn hyperlink_fallback() {
+        let link = Hyperlink::new("https://example.com/port", "port");
+        assert_eq!(
+            "\x1B]8;;https://example.com/port\x1B\\port\x1B]8;;\x1B\\",
+            link.clone().enabled(true).to_string()
+        );
+        assert_eq!("port", link.enabled(false).to_string());
+    }
+}
@@ -0,0 +1,533 @@
+//! Key bindings read from a readline `inputrc` file.
+//!
+//! Bindings from the file of the user take precedence over the
+//! default bindings so prompts behave like other readline programs:
+//!
+//! ```no_run
+//! use crossterm_prompt::{inputrc::Inputrc, PromptOptions};
+//!
+//! let inputrc = Inputrc::load()?;
+//! let options = PromptOptions::new().bindings(inputrc.key_bindings());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Only bindings to named commands are used, macros are skipped.
+//! Bindings are read for the emacs keymap, they are ignored while
+//! `editing-mode` or `keymap` select a vi keymap. Conditional
+//! blocks are supported for the `mode` test, other tests such as
+//! the terminal or application name are treated as false.
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::{KeyAction, KeyBindings};
+
+/// Variables and key bindings of an `inputrc` file.
+#[derive(Debug, Default)]
+pub struct Inputrc {
+    variables: HashMap<String, String>,
+    bindings: Vec<(Vec<KeyEvent>, KeyAction)>,
+}
+
+impl Inputrc {
+    /// Path of the `inputrc` file of the user.
+    ///
+    /// Uses the `INPUTRC` environment variable, then `~/.inputrc`
+    /// and finally `/etc/inputrc`, the first file that exists
+    /// is returned.
+    pub fn path() -> Option<PathBuf> {
+        let mut candidates = Vec::new();
+        if let Some(path) = std::env::var_os("INPUTRC") {
+            candidates.push(PathBuf::from(path));
+        }
+        if let Some(home) = std::env::var_os("HOME") {
+            candidates.push(PathBuf::from(home).join(".inputrc"));
+        }
+        candidates.push(PathBuf::from("/etc/inputrc"));
+        candidates.into_iter().find(|path| path.is_file())
+    }
+
+    /// Load the `inputrc` file of the user.
+    ///
+    /// When there is no file the result has no bindings.
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) => Ok(Self::parse(&std::fs::read_to_string(path)?)),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Parse the contents of an `inputrc` file.
+    ///
+    /// Lines that cannot be parsed and unknown commands are
+    /// skipped like readline does.
+    pub fn parse(text: &str) -> Self {
+        let mut inputrc = Self::default();
+        // Whether the lines of each enclosing conditional are used
+        let mut conditions: Vec<bool> = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(directive) = line.strip_prefix('$') {
+                let (name, argument) = split_word(directive);
+                match name {
+                    "if" => {
+                        let test = inputrc.test(argument);
+                        conditions.push(test);
+                    }
+                    "else" => {
+                        if let Some(condition) = conditions.last_mut() {
+                            *condition = !*condition;
+                        }
+                    }
+                    "endif" => {
+                        conditions.pop();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            if conditions.iter().any(|condition| !condition) {
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("set ") {
+                let (name, value) = split_word(rest.trim_start());
+                inputrc
+                    .variables
+                    .insert(name.to_lowercase(), value.to_string());
+            } else if inputrc.is_emacs() {
+                if let Some(binding) = parse_binding(line) {
+                    inputrc.bindings.retain(|(keys, _)| *keys != binding.0);
+                    inputrc.bindings.push(binding);
+                }
+            }
+        }
+        inputrc
+    }
+
+    /// Value of a variable assigned with `set`.
+    ///
+    /// Variable names are not case sensitive.
+    pub fn variable(&self, name: &str) -> Option<&str> {
+        self.variables.get(&name.to_lowercase()).map(|v| &v[..])
+    }
+
+    /// Keys and the actions they are bound to.
+    pub fn bindings(&self) -> &[(Vec<KeyEvent>, KeyAction)] {
+        &self.bindings
+    }
+
+    /// Create key bindings with the bindings of this file taking
+    /// precedence over the default bindings.
+    pub fn key_bindings(&self) -> KeyBindings {
+        self.bindings
+            .iter()
+            .fold(KeyBindings::builder(), |builder, (keys, action)| {
+                builder.bind_sequence(keys, *action)
+            })
+            .extend(KeyBindings::default())
+            .build()
+    }
+
+    /// Determine if bindings apply to the emacs keymap.
+    fn is_emacs(&self) -> bool {
+        match self.variable("keymap") {
+            Some(keymap) => keymap.starts_with("emacs"),
+            None => self.variable("editing-mode") != Some("vi"),
+        }
+    }
+
+    /// Evaluate the test of an `$if` directive.
+    fn test(&self, argument: &str) -> bool {
+        match argument.trim().strip_prefix("mode=") {
+            Some("vi") => !self.is_emacs(),
+            Some("emacs") => self.is_emacs(),
+            _ => false,
+        }
+    }
+}
+
+/// Split the first whitespace separated word from a string.
+fn split_word(text: &str) -> (&str, &str) {
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+/// Parse a key binding in the `inputrc` format.
+///
+/// Can be used for a `bind` builtin like the one of bash:
+///
+/// ```
+/// use crossterm_prompt::{inputrc::parse_binding, PromptOptions};
+///
+/// let options = PromptOptions::new();
+/// if let Some((keys, action)) = parse_binding(r#""\C-t": yank"#) {
+///     options.key_bindings().bind(&keys, action);
+/// }
+/// ```
+pub fn parse_binding(line: &str) -> Option<(Vec<KeyEvent>, KeyAction)> {
+    let (keys, rest) = if let Some(quoted) = line.strip_prefix('"') {
+        let end = closing_quote(quoted)?;
+        let rest = quoted[end + 1..].trim_start().strip_prefix(':')?;
+        (sequence(&quoted[..end])?, rest)
+    } else {
+        let (name, rest) = line.split_once(':')?;
+        (vec![key_name(name.trim())?], rest)
+    };
+
+    // Macros start with a quote
+    let (command, _) = split_word(rest.trim());
+    let action = KeyAction::from_name(command)?;
+    (!keys.is_empty()).then_some((keys, action))
+}
+
+/// Byte offset of the closing quote of a quoted string.
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(index),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Parse a key name such as `Control-u` or `Meta-Rubout`.
+fn key_name(name: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = name;
+    while let Some((prefix, rest)) = name.split_once('-') {
+        if rest.is_empty() {
+            break;
+        }
+        modifiers |= match &prefix.to_lowercase()[..] {
+            "c" | "control" => KeyModifiers::CONTROL,
+            "m" | "meta" => KeyModifiers::ALT,
+            _ => return None,
+        };
+        name = rest;
+    }
+    named_key(name, modifiers)
+}
+
+/// Event for a symbolic key name or a single character.
+fn named_key(name: &str, modifiers: KeyModifiers) -> Option<KeyEvent> {
+    let code = match &name.to_lowercase()[..] {
+        "del" | "rubout" => KeyCode::Backspace,
+        "esc" | "escape" => KeyCode::Esc,
+        "lfd" | "newline" | "ret" | "return" => KeyCode::Enter,
+        "spc" | "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => return Some(char_key(c, modifiers)),
+                _ => return None,
+            }
+        }
+    };
+    Some(KeyEvent { code, modifiers })
+}
+
+/// Event for a character typed with modifiers.
+///
+/// Control characters are reported by the terminal as the
+/// letter with the control modifier.
+fn char_key(c: char, modifiers: KeyModifiers) -> KeyEvent {
+    let (code, modifiers) = match c {
+        '\r' | '\n' => (KeyCode::Enter, modifiers),
+        '\t' => (KeyCode::Tab, modifiers),
+        '\x1B' => (KeyCode::Esc, modifiers),
+        '\x08' | '\x7F' => (KeyCode::Backspace, modifiers),
+        '\0' => (KeyCode::Char(' '), modifiers | KeyModifiers::CONTROL),
+        '\x01'..='\x1A' => (
+            KeyCode::Char((c as u8 - 1 + b'a') as char),
+            modifiers | KeyModifiers::CONTROL,
+        ),
+        '\x1C'..='\x1F' => (
+            KeyCode::Char((c as u8 - 0x1C + b'4') as char),
+            modifiers | KeyModifiers::CONTROL,
+        ),
+        c if modifiers.contains(KeyModifiers::CONTROL) => {
+            (KeyCode::Char(c.to_ascii_lowercase()), modifiers)
+        }
+        c if c.is_uppercase() => {
+            (KeyCode::Char(c), modifiers | KeyModifiers::SHIFT)
+        }
+        c => (KeyCode::Char(c), modifiers),
+    };
+    KeyEvent { code, modifiers }
+}
+
+/// Parse a quoted key sequence into key events.
+fn sequence(text: &str) -> Option<Vec<KeyEvent>> {
+    let chars = unescape(text)?;
+    let mut events = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let (event, used) = match &chars[index..] {
+            ['\x1B', '[', rest @ ..] => csi(rest)?,
+            ['\x1B', 'O', c, ..] => (ss3(*c)?, 3),
+            ['\x1B', c, ..] => (char_key(*c, KeyModifiers::ALT), 2),
+            [c, ..] => (char_key(*c, KeyModifiers::NONE), 1),
+            [] => unreachable!(),
+        };
+        events.push(event);
+        index += used;
+    }
+    Some(events)
+}
+
+/// Decode the escapes of a quoted key sequence.
+fn unescape(text: &str) -> Option<Vec<char>> {
+    let mut decoded = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        let c = chars.next()?;
+        match c {
+            'C' | 'M' if chars.peek() == Some(&'-') => {
+                chars.next();
+                let mut key = chars.next()?;
+                if key == '\\' {
+                    key = match chars.next()? {
+                        'e' => '\x1B',
+                        other => other,
+                    };
+                }
+                if c == 'C' {
+                    decoded.push(control(key));
+                } else {
+                    decoded.push('\x1B');
+                    decoded.push(key);
+                }
+            }
+            'e' => decoded.push('\x1B'),
+            'a' => decoded.push('\x07'),
+            'b' => decoded.push('\x08'),
+            'd' => decoded.push('\x7F'),
+            'f' => decoded.push('\x0C'),
+            'n' => decoded.push('\n'),
+            'r' => decoded.push('\r'),
+            't' => decoded.push('\t'),
+            'v' => decoded.push('\x0B'),
+            '0'..='7' => {
+                let mut value = c.to_digit(8)?;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                decoded.push(char::from_u32(value)?);
+            }
+            'x' => {
+                let mut value = 0;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(16)) {
+                        Some(digit) => {
+                            value = value * 16 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                decoded.push(char::from_u32(value)?);
+            }
+            other => decoded.push(other),
+        }
+    }
+    Some(decoded)
+}
+
+/// Control character for a key typed with the control modifier.
+fn control(key: char) -> char {
+    match key {
+        '?' => '\x7F',
+        key if key.is_ascii() => {
+            ((key.to_ascii_uppercase() as u8) & 0x1F) as char
+        }
+        key => key,
+    }
+}
+
+/// Parse a CSI sequence after `ESC [`, returning the event and
+/// the number of characters used including `ESC [`.
+fn csi(rest: &[char]) -> Option<(KeyEvent, usize)> {
+    let end = rest.iter().position(|c| ('@'..='~').contains(c))?;
+    let params: String = rest[..end].iter().collect();
+    let mut params = params.split(';');
+    let first = params.next().filter(|p| !p.is_empty());
+    let modifiers = params
+        .next()
+        .and_then(|mask| mask.parse::<u8>().ok())
+        .map(modifiers)
+        .unwrap_or(KeyModifiers::NONE);
+
+    let code = match (rest[end], first) {
+        ('A', _) => KeyCode::Up,
+        ('B', _) => KeyCode::Down,
+        ('C', _) => KeyCode::Right,
+        ('D', _) => KeyCode::Left,
+        ('F', _) => KeyCode::End,
+        ('H', _) => KeyCode::Home,
+        (c @ 'P'..='S', _) => KeyCode::F(1 + c as u8 - b'P'),
+        ('~', Some(number)) => match number.parse::<u8>().ok()? {
+            1 | 7 => KeyCode::Home,
+            2 => KeyCode::Insert,
+            3 => KeyCode::Delete,
+            4 | 8 => KeyCode::End,
+            5 => KeyCode::PageUp,
+            6 => KeyCode::PageDown,
+            number @ 11..=15 => KeyCode::F(number - 10),
+            number @ 17..=21 => KeyCode::F(number - 11),
+            number @ 23..=24 => KeyCode::F(number - 12),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((KeyEvent { code, modifiers }, end + 3))
+}
+
+/// Key for an SS3 sequence after `ESC O`.
+fn ss3(c: char) -> Option<KeyEvent> {
+    let code = match c {
+        'A' => KeyCode::Up,
+        'B' => KeyCode::Down,
+        'C' => KeyCode::Right,
+        'D' => KeyCode::Left,
+        'F' => KeyCode::End,
+        'H' => KeyCode::Home,
+        'P'..='S' => KeyCode::F(1 + c as u8 - b'P'),
+        _ => return None,
+    };
+    Some(code.into())
+}
+
+/// Modifiers encoded in the parameter of a CSI sequence.
+fn modifiers(mask: u8) -> KeyModifiers {
+    let mask = mask.saturating_sub(1);
+    let mut modifiers = KeyModifiers::NONE;
+    if mask & 1 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if mask & 2 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    if mask & 4 != 0 {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+    modifiers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyMatch;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent { code, modifiers }
+    }
+
+    #[test]
+    fn inputrc_bindings() {
+        let text = r#"
+# comment
+set editing-mode emacs
+set Bell-Style none
+Control-u: kill-line
+M-Rubout: backward-kill-word
+"\C-t": accept-line
+"\e[1;5C": end-of-line
+"\ed": kill-line
+"\C-x\C-r": accept-line
+"\C-o": "macro"
+"\C-a": no-such-command
+$if mode=vi
+"\C-e": accept-line
+$else
+"\C-b": beginning-of-line
+$endif
+$if Bash
+"\C-f": accept-line
+$endif
+"\C-t": yank
+"#;
+        let inputrc = Inputrc::parse(text);
+        assert_eq!(Some("none"), inputrc.variable("bell-style"));
+        assert_eq!(Some("emacs"), inputrc.variable("editing-mode"));
+        let ctrl = |c| key(KeyCode::Char(c), KeyModifiers::CONTROL);
+        assert_eq!(
+            vec![
+                (vec![ctrl('u')], KeyAction::EraseToLineEnd),
+                (
+                    vec![key(KeyCode::Backspace, KeyModifiers::ALT)],
+                    KeyAction::ErasePreviousWord
+                ),
+                (
+                    vec![key(KeyCode::Right, KeyModifiers::CONTROL)],
+                    KeyAction::MoveToLineEnd
+                ),
+                (
+                    vec![key(KeyCode::Char('d'), KeyModifiers::ALT)],
+                    KeyAction::EraseToLineEnd
+                ),
+                (vec![ctrl('x'), ctrl('r')], KeyAction::SubmitLine),
+                (vec![ctrl('b')], KeyAction::MoveToLineBegin),
+                (vec![ctrl('t')], KeyAction::Yank),
+            ],
+            inputrc.bindings()
+        );
+
+        let bindings = inputrc.key_bindings();
+        let actions = bindings.first(&ctrl('u')).unwrap();
+        assert_eq!(vec![KeyAction::EraseToLineEnd], actions);
+        let actions = bindings.first(&ctrl('k')).unwrap();
+        assert_eq!(vec![KeyAction::EraseToLineEnd], actions);
+        assert_eq!(
+            KeyMatch::Actions(vec![KeyAction::SubmitLine]),
+            bindings.lookup(&[ctrl('x'), ctrl('r')])
+        );
+    }
+
+    #[test]
+    fn inputrc_vi_mode() {
+        let inputrc = Inputrc::parse(
+            "set editing-mode vi\n\"\\C-t\": yank\nset keymap emacs\n\
+             \"\\C-y\": yank\n",
+        );
+        let ctrl_y = key(KeyCode::Char('y'), KeyModifiers::CONTROL);
+        assert_eq!(vec![(vec![ctrl_y], KeyAction::Yank)], inputrc.bindings());
+    }
+
+    #[test]
+    fn inputrc_function_keys() {
+        for (line, code, modifiers) in [
+            (r#""\eOP": help"#, KeyCode::F(1), KeyModifiers::NONE),
+            (r#""\e[1;5P": help"#, KeyCode::F(1), KeyModifiers::CONTROL),
+            (r#""\e[15~": help"#, KeyCode::F(5), KeyModifiers::NONE),
+            (r#""\e[24;2~": help"#, KeyCode::F(12), KeyModifiers::SHIFT),
+        ] {
+            assert_eq!(
+                Some((vec![key(code, modifiers)], KeyAction::Help)),
+                parse_binding(line)
+            );
+        }
+    }
+}
@@ -1,5 +1,7 @@
 //! Type for declaring key bindings.
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+use std::str::FromStr;
 
 /// Wraps a key event to distinguish between named
 /// key codes and arbitrary input.
@@ -9,10 +11,28 @@ enum KeyType {
     Named,
     /// An arbitrary character.
     Char,
-    /// A function key modifier.
+    /// A function key with any modifiers.
     Func,
 }
 
+impl KeyType {
+    /// Determine the kind of a key event.
+    fn of(event: &KeyEvent) -> Self {
+        match event.code {
+            // Shift is part of the typed character
+            KeyCode::Char(_) => {
+                if (event.modifiers - KeyModifiers::SHIFT).is_empty() {
+                    KeyType::Char
+                } else {
+                    KeyType::Named
+                }
+            }
+            KeyCode::F(_) => KeyType::Func,
+            _ => KeyType::Named,
+        }
+    }
+}
+
 type KeyActionHandler = Box<dyn Fn(&KeyEvent) -> Vec<KeyAction>>;
 
 /// Definition of a key event with associated actions.
@@ -22,27 +42,63 @@ struct KeyDefinition {
     pub actions: KeyActionHandler,
 }
 
+/// Definition of a sequence of key events with associated actions.
+struct KeySequence {
+    keys: Vec<KeyEvent>,
+    actions: KeyActionHandler,
+}
+
+/// Result of looking up typed keys in the key bindings.
+#[derive(Debug, Eq, PartialEq)]
+pub enum KeyMatch {
+    /// The keys are bound to actions.
+    Actions(Vec<KeyAction>),
+    /// The keys start a longer key sequence.
+    Pending,
+    /// The keys are not bound.
+    Unbound,
+}
+
 /// Actions that keys may trigger.
-#[derive(Debug, Clone, Copy)]
+///
+/// Actions that do not take a value can be looked up by their
+/// readline-style name using [KeyAction::from_name] or by parsing
+/// a string, see [KeyAction::names] for the available names.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum KeyAction {
     /// Write the character to the terminal.
     WriteChar(char),
     /// Submit the line.
     SubmitLine,
+    /// Submit the line and show it again in the next prompt
+    /// using the same options.
+    AcceptAndHold,
     /// Move cursor left.
     MoveCursorLeft,
     /// Move cursor right.
     MoveCursorRight,
     /// Erase the last character.
     EraseCharacter,
+    /// Erase the character under the cursor.
+    EraseNextCharacter,
+    /// Erase the character under the cursor or end the input
+    /// when the line is empty or multiline input is finished.
+    EraseNextCharacterOrEndOfFile,
     /// Clear the screen.
     ClearScreen,
     /// Abort the prompt.
-    AbortPrompt,
+    AbortPrompt(AbortReason),
     /// Move to beginning of the line.
     MoveToLineBegin,
     /// Move to end of the line.
     MoveToLineEnd,
+    /// Move to the end of the next word.
+    ForwardWord,
+    /// Move to the start of the previous word.
+    BackwardWord,
+    /// Move to a grapheme index in the line, an index past
+    /// the end of the line moves to the end.
+    MoveToColumn(usize),
 
     /// Erase to the beginning of the line.
     EraseToLineBegin,
@@ -50,9 +106,119 @@ pub enum KeyAction {
     /// Erase to the end of the line.
     EraseToLineEnd,
 
+    /// Erase the whole line regardless of the cursor.
+    EraseLine,
+
     /// Erase the previous word.
     ErasePreviousWord,
 
+    /// Erase the next word.
+    EraseNextWord,
+
+    /// Convert to the end of the word to upper case.
+    UpcaseWord,
+
+    /// Convert to the end of the word to lower case.
+    DowncaseWord,
+
+    /// Capitalize the word at or after the cursor.
+    CapitalizeWord,
+
+    /// Insert the most recently erased text.
+    Yank,
+
+    /// Set the mark at the cursor, the text between the mark
+    /// and the cursor is the region.
+    SetMark,
+
+    /// Move the cursor to the mark and the mark to the cursor.
+    ExchangePointAndMark,
+
+    /// Erase the region.
+    KillRegion,
+
+    /// Copy the region so it can be inserted with [KeyAction::Yank].
+    CopyRegion,
+
+    /// Copy the region, or the line when the mark is not active,
+    /// to the system clipboard, see
+    /// [PromptOptions::clipboard](crate::PromptOptions::clipboard).
+    CopyToClipboard,
+
+    /// Insert the next key typed as a character instead of
+    /// performing its actions, for example a tab.
+    QuotedInsert,
+
+    /// Undo the last change to the line.
+    Undo,
+
+    /// Redo the last undone change to the line.
+    Redo,
+
+    /// Edit the line in the editor named by the `VISUAL` or
+    /// `EDITOR` environment variable and submit the edited line.
+    ///
+    /// The line is kept and not submitted when the editor
+    /// exits unsuccessfully.
+    EditAndExecuteCommand,
+
+    /// Toggle showing the value of a password.
+    TogglePasswordReveal,
+
+    /// Start recording keys into a keyboard macro.
+    StartMacro,
+
+    /// Stop recording the keyboard macro.
+    EndMacro,
+
+    /// Replay the most recently recorded keyboard macro.
+    CallLastMacro,
+
+    /// Add a digit to the numeric argument that repeats
+    /// the next action.
+    DigitArgument(u8),
+
+    /// Start the numeric argument at four or multiply it by four,
+    /// digits typed next are added to the argument.
+    ///
+    /// Not bound by default because Ctrl+u erases to the start of
+    /// the line as in Readline, use
+    /// [KeyBindings::with_universal_argument] to bind it to Ctrl+u.
+    UniversalArgument,
+
+    /// Run the command registered at an index with
+    /// [PromptOptions::command](crate::PromptOptions::command).
+    Command(usize),
+    /// Run the help handler set with
+    /// [PromptOptions::help](crate::PromptOptions::help).
+    Help,
+
+    /// Select the next entry in a mode such as the completion menu.
+    ///
+    /// This and the other commands of [CommandCategory::Mode]
+    /// are only used in the key bindings of a mode, bound for
+    /// the line itself they do nothing.
+    SelectNext,
+
+    /// Select the previous entry in a mode such as the completion menu.
+    SelectPrevious,
+
+    /// Accept the selected entry and leave the active mode.
+    AcceptSelection,
+
+    /// Leave the active mode discarding any changes.
+    CancelMode,
+
+    /// Complete the word before the cursor.
+    #[cfg(any(feature = "completion", doc))]
+    #[doc(cfg(feature = "completion"))]
+    Complete,
+
+    /// Toggle the debug overlay.
+    #[cfg(any(feature = "debug", doc))]
+    #[doc(cfg(feature = "debug"))]
+    ToggleDebug,
+
     /// Go to previous history item.
     #[cfg(any(feature = "history", doc))]
     #[doc(cfg(feature = "history"))]
@@ -62,46 +228,598 @@ pub enum KeyAction {
     #[cfg(any(feature = "history", doc))]
     #[doc(cfg(feature = "history"))]
     HistoryNext,
+
+    /// Go to the oldest history item.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    HistoryFirst,
+
+    /// Go back to the line being edited after the newest
+    /// history item.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    HistoryLast,
+
+    /// Go to the previous history item starting with the
+    /// text before the cursor.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    HistorySearchBackward,
+
+    /// Go to the next history item starting with the
+    /// text before the cursor.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    HistorySearchForward,
+
+    /// Go to the previous history item containing the
+    /// text of the line.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    HistorySubstringSearchBackward,
+
+    /// Go to the next history item containing the
+    /// text of the line.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    HistorySubstringSearchForward,
+
+    /// Insert a token taken from the history using the extractor
+    /// registered at an index with
+    /// [PromptOptions::history_token](crate::PromptOptions::history_token).
+    ///
+    /// Repeating the action replaces the inserted token with one
+    /// from an older entry.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    InsertHistoryToken(usize),
+
+    /// Submit the line and show the history entry after it
+    /// in the next prompt using the same options.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    OperateAndGetNext,
+
+    /// Insert the last word of the previous history entry,
+    /// repeating the action inserts the word from an older entry.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    YankLastArg,
+
+    /// Search backwards through the history.
+    #[cfg(any(feature = "search", doc))]
+    #[doc(cfg(feature = "search"))]
+    ReverseSearch,
+
+    /// Search forwards through the history.
+    ///
+    /// Bound to Ctrl+s which some terminals use for flow control
+    /// (XON/XOFF); raw mode turns flow control off while the prompt
+    /// is active so the key reaches the prompt.
+    #[cfg(any(feature = "search", doc))]
+    #[doc(cfg(feature = "search"))]
+    ForwardSearch,
+
+    /// Toggle history navigation between the current
+    /// namespace and all namespaces.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    ToggleHistoryScope,
+}
+
+/// Trigger that caused a prompt to be aborted.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AbortReason {
+    /// The interrupt key was pressed (Ctrl+c).
+    Interrupt,
+    /// The end of file key was pressed (Ctrl+d on an empty line).
+    EndOfFile,
+    /// The escape key was pressed, Esc is not bound by default.
+    Escape,
+    /// The prompt was cancelled with a
+    /// [CancelToken](crate::CancelToken).
+    Cancelled,
+    /// There was no input before the prompt timeout.
+    Timeout,
+}
+
+/// Categories of actions that may be disabled for a prompt.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CommandCategory {
+    /// Submitting or aborting the prompt.
+    Control,
+    /// Inserting typed characters.
+    Insert,
+    /// Moving the cursor.
+    Motion,
+    /// Erasing text.
+    Erase,
+    /// Inserting previously erased text.
+    Paste,
+    /// Setting the mark and erasing or copying the region
+    /// or line.
+    Region,
+    /// Undoing and redoing changes.
+    Undo,
+    /// Changing the case of words.
+    Case,
+    /// Clearing the screen.
+    ClearScreen,
+    /// Editing the line in an external editor.
+    Editor,
+    /// Revealing a password.
+    Reveal,
+    /// Recording and replaying keyboard macros.
+    Macro,
+    /// Typing numeric arguments.
+    Argument,
+    /// Commands defined by the application.
+    Custom,
+    /// Selecting and leaving entries in sub-modes, these
+    /// commands do nothing outside of a mode.
+    Mode,
+    /// Completing words.
+    Completion,
+    /// Navigating and searching the history.
+    History,
+    /// Showing debug information.
+    Debug,
+}
+
+impl KeyAction {
+    /// Category this action belongs to.
+    pub fn category(&self) -> CommandCategory {
+        match self {
+            Self::SubmitLine | Self::AcceptAndHold | Self::AbortPrompt(_) => {
+                CommandCategory::Control
+            }
+            Self::WriteChar(_) | Self::QuotedInsert => CommandCategory::Insert,
+            Self::MoveCursorLeft
+            | Self::MoveCursorRight
+            | Self::MoveToLineBegin
+            | Self::MoveToLineEnd
+            | Self::ForwardWord
+            | Self::BackwardWord
+            | Self::MoveToColumn(_) => CommandCategory::Motion,
+            Self::EraseCharacter
+            | Self::EraseNextCharacter
+            | Self::EraseNextCharacterOrEndOfFile
+            | Self::EraseToLineBegin
+            | Self::EraseToLineEnd
+            | Self::EraseLine
+            | Self::ErasePreviousWord
+            | Self::EraseNextWord => CommandCategory::Erase,
+            Self::Yank => CommandCategory::Paste,
+            Self::SetMark
+            | Self::ExchangePointAndMark
+            | Self::KillRegion
+            | Self::CopyRegion
+            | Self::CopyToClipboard => CommandCategory::Region,
+            Self::Undo | Self::Redo => CommandCategory::Undo,
+            Self::UpcaseWord | Self::DowncaseWord | Self::CapitalizeWord => {
+                CommandCategory::Case
+            }
+            Self::ClearScreen => CommandCategory::ClearScreen,
+            Self::EditAndExecuteCommand => CommandCategory::Editor,
+            Self::TogglePasswordReveal => CommandCategory::Reveal,
+            Self::StartMacro | Self::EndMacro | Self::CallLastMacro => {
+                CommandCategory::Macro
+            }
+            Self::DigitArgument(_) | Self::UniversalArgument => {
+                CommandCategory::Argument
+            }
+            Self::Command(_) | Self::Help => CommandCategory::Custom,
+            Self::SelectNext
+            | Self::SelectPrevious
+            | Self::AcceptSelection
+            | Self::CancelMode => CommandCategory::Mode,
+            #[cfg(feature = "completion")]
+            Self::Complete => CommandCategory::Completion,
+            #[cfg(feature = "debug")]
+            Self::ToggleDebug => CommandCategory::Debug,
+            #[cfg(feature = "history")]
+            Self::HistoryPrevious
+            | Self::HistoryNext
+            | Self::HistoryFirst
+            | Self::HistoryLast
+            | Self::HistorySearchBackward
+            | Self::HistorySearchForward
+            | Self::HistorySubstringSearchBackward
+            | Self::HistorySubstringSearchForward
+            | Self::InsertHistoryToken(_)
+            | Self::YankLastArg
+            | Self::OperateAndGetNext
+            | Self::ToggleHistoryScope => CommandCategory::History,
+            #[cfg(feature = "search")]
+            Self::ReverseSearch | Self::ForwardSearch => {
+                CommandCategory::History
+            }
+        }
+    }
+}
+
+/// Names of actions that may be bound from strings.
+///
+/// Where an action has several names the first is used
+/// by [KeyAction::name].
+const NAMES: &[(&str, KeyAction)] = &[
+    ("accept-line", KeyAction::SubmitLine),
+    ("accept-and-hold", KeyAction::AcceptAndHold),
+    ("abort", KeyAction::AbortPrompt(AbortReason::Interrupt)),
+    (
+        "end-of-file",
+        KeyAction::AbortPrompt(AbortReason::EndOfFile),
+    ),
+    ("backward-char", KeyAction::MoveCursorLeft),
+    ("forward-char", KeyAction::MoveCursorRight),
+    ("beginning-of-line", KeyAction::MoveToLineBegin),
+    ("end-of-line", KeyAction::MoveToLineEnd),
+    ("forward-word", KeyAction::ForwardWord),
+    ("backward-word", KeyAction::BackwardWord),
+    ("backward-delete-char", KeyAction::EraseCharacter),
+    ("delete-char", KeyAction::EraseNextCharacter),
+    (
+        "delete-char-or-eof",
+        KeyAction::EraseNextCharacterOrEndOfFile,
+    ),
+    ("unix-line-discard", KeyAction::EraseToLineBegin),
+    ("backward-kill-line", KeyAction::EraseToLineBegin),
+    ("kill-line", KeyAction::EraseToLineEnd),
+    ("kill-whole-line", KeyAction::EraseLine),
+    ("backward-kill-word", KeyAction::ErasePreviousWord),
+    ("unix-word-rubout", KeyAction::ErasePreviousWord),
+    ("kill-word", KeyAction::EraseNextWord),
+    ("upcase-word", KeyAction::UpcaseWord),
+    ("downcase-word", KeyAction::DowncaseWord),
+    ("capitalize-word", KeyAction::CapitalizeWord),
+    ("yank", KeyAction::Yank),
+    ("set-mark", KeyAction::SetMark),
+    ("exchange-point-and-mark", KeyAction::ExchangePointAndMark),
+    ("kill-region", KeyAction::KillRegion),
+    ("copy-region-as-kill", KeyAction::CopyRegion),
+    ("copy-to-clipboard", KeyAction::CopyToClipboard),
+    ("quoted-insert", KeyAction::QuotedInsert),
+    ("undo", KeyAction::Undo),
+    ("redo", KeyAction::Redo),
+    ("clear-screen", KeyAction::ClearScreen),
+    ("edit-and-execute-command", KeyAction::EditAndExecuteCommand),
+    ("toggle-password-reveal", KeyAction::TogglePasswordReveal),
+    ("start-kbd-macro", KeyAction::StartMacro),
+    ("end-kbd-macro", KeyAction::EndMacro),
+    ("call-last-kbd-macro", KeyAction::CallLastMacro),
+    ("universal-argument", KeyAction::UniversalArgument),
+    ("help", KeyAction::Help),
+    ("select-next", KeyAction::SelectNext),
+    ("select-previous", KeyAction::SelectPrevious),
+    ("accept-selection", KeyAction::AcceptSelection),
+    ("cancel-mode", KeyAction::CancelMode),
+    #[cfg(feature = "completion")]
+    ("complete", KeyAction::Complete),
+    #[cfg(feature = "debug")]
+    ("toggle-debug", KeyAction::ToggleDebug),
+    #[cfg(feature = "history")]
+    ("previous-history", KeyAction::HistoryPrevious),
+    #[cfg(feature = "history")]
+    ("next-history", KeyAction::HistoryNext),
+    #[cfg(feature = "history")]
+    ("beginning-of-history", KeyAction::HistoryFirst),
+    #[cfg(feature = "history")]
+    ("end-of-history", KeyAction::HistoryLast),
+    #[cfg(feature = "history")]
+    ("history-search-backward", KeyAction::HistorySearchBackward),
+    #[cfg(feature = "history")]
+    ("history-search-forward", KeyAction::HistorySearchForward),
+    #[cfg(feature = "history")]
+    (
+        "history-substring-search-backward",
+        KeyAction::HistorySubstringSearchBackward,
+    ),
+    #[cfg(feature = "history")]
+    (
+        "history-substring-search-forward",
+        KeyAction::HistorySubstringSearchForward,
+    ),
+    #[cfg(feature = "history")]
+    ("toggle-history-scope", KeyAction::ToggleHistoryScope),
+    #[cfg(feature = "history")]
+    ("yank-last-arg", KeyAction::YankLastArg),
+    #[cfg(feature = "history")]
+    ("operate-and-get-next", KeyAction::OperateAndGetNext),
+    #[cfg(feature = "search")]
+    ("reverse-search-history", KeyAction::ReverseSearch),
+    #[cfg(feature = "search")]
+    ("forward-search-history", KeyAction::ForwardSearch),
+];
+
+impl KeyAction {
+    /// Determine if a numeric argument repeats this action.
+    pub(crate) fn is_repeated(&self) -> bool {
+        matches!(
+            self.category(),
+            CommandCategory::Insert
+                | CommandCategory::Motion
+                | CommandCategory::Erase
+                | CommandCategory::Paste
+                | CommandCategory::Undo
+                | CommandCategory::Case
+        )
+    }
+
+    /// Find the action with a readline-style name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, action)| *action)
+    }
+
+    /// Readline-style name of this action.
+    ///
+    /// Actions that take a value such as [KeyAction::WriteChar]
+    /// do not have a name.
+    pub fn name(&self) -> Option<&'static str> {
+        NAMES
+            .iter()
+            .find(|(_, action)| action == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// Names of all actions that may be bound from strings.
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        NAMES.iter().map(|(name, _)| *name)
+    }
+}
+
+impl FromStr for KeyAction {
+    type Err = UnknownCommand;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or_else(|| UnknownCommand {
+            name: s.to_string(),
+        })
+    }
+}
+
+/// Error for a command name that does not match an action.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnknownCommand {
+    /// The name that was not found.
+    pub name: String,
+}
+
+impl fmt::Display for UnknownCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: unknown command", self.name)
+    }
+}
+
+impl std::error::Error for UnknownCommand {}
+
+/// Keys bound to actions, see [KeyBindings::iter].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Binding {
+    keys: Vec<KeyEvent>,
+    actions: Vec<KeyAction>,
+}
+
+impl Binding {
+    /// Keys that trigger the actions.
+    pub fn keys(&self) -> &[KeyEvent] {
+        &self.keys
+    }
+
+    /// Actions performed for the keys.
+    pub fn actions(&self) -> &[KeyAction] {
+        &self.actions
+    }
+
+    /// Keys as text such as `Ctrl+x Ctrl+e`.
+    pub fn key_string(&self) -> String {
+        crate::keymap::format_keys(&self.keys)
+    }
+
+    /// Name of the command when the keys perform a single
+    /// action that has a name.
+    pub fn command_name(&self) -> Option<&'static str> {
+        match &self.actions[..] {
+            [action] => action.name(),
+            _ => None,
+        }
+    }
 }
 
 /// Collection of key bindings.
 pub struct KeyBindings {
     bindings: Vec<KeyDefinition>,
+    sequences: Vec<KeySequence>,
 }
 
 impl KeyBindings {
+    /// Number of key definitions.
+    #[cfg(feature = "debug")]
+    pub(crate) fn len(&self) -> usize {
+        self.bindings.len() + self.sequences.len()
+    }
+
+    /// Create a builder starting without any key definitions.
+    pub fn builder() -> KeyBindingsBuilder {
+        KeyBindingsBuilder {
+            inner: KeyBindings {
+                bindings: Vec::new(),
+                sequences: Vec::new(),
+            },
+        }
+    }
+
+    /// Default key bindings with Ctrl+u bound to
+    /// [KeyAction::UniversalArgument] as in Emacs instead of
+    /// erasing to the start of the line.
+    pub fn with_universal_argument() -> Self {
+        let mut bindings = Self::default();
+        bindings.bind(
+            &[KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL)],
+            KeyAction::UniversalArgument,
+        );
+        bindings
+    }
+
+    /// Key bindings that only write typed characters.
+    #[cfg(feature = "serde")]
+    pub(crate) fn typing() -> Self {
+        Self {
+            bindings: vec![chars()],
+            sequences: Vec::new(),
+        }
+    }
+
+    /// Bind a sequence of keys to an action, replacing any
+    /// existing binding for the keys.
+    pub fn bind(&mut self, keys: &[KeyEvent], action: KeyAction) {
+        self.bind_fn(keys, move |_| vec![action]);
+    }
+
+    /// Bind a sequence of keys to a closure returning the actions
+    /// to perform, the closure receives the last key.
+    pub fn bind_fn<F>(&mut self, keys: &[KeyEvent], actions: F)
+    where
+        F: Fn(&KeyEvent) -> Vec<KeyAction> + 'static,
+    {
+        match keys {
+            [] => {}
+            [event] => {
+                self.bindings.retain(|d| d.event.as_ref() != Some(event));
+                self.bindings.insert(
+                    0,
+                    KeyDefinition {
+                        kind: KeyType::of(event),
+                        event: Some(*event),
+                        actions: Box::new(actions),
+                    },
+                );
+            }
+            _ => {
+                self.sequences.retain(|s| s.keys != keys);
+                self.sequences.insert(
+                    0,
+                    KeySequence {
+                        keys: keys.to_vec(),
+                        actions: Box::new(actions),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Remove the binding for a sequence of keys.
+    ///
+    /// A single key is ignored after it is unbound even when it
+    /// is handled by a catch-all binding such as typed characters.
+    pub fn unbind(&mut self, keys: &[KeyEvent]) {
+        match keys {
+            [_] => self.bind_fn(keys, |_| vec![]),
+            _ => self.sequences.retain(|s| s.keys != keys),
+        }
+    }
+
+    /// Look up the keys typed so far.
+    ///
+    /// When the keys start a longer key sequence the result is
+    /// [KeyMatch::Pending] even if the keys are bound on their own,
+    /// the prompt waits for the next key and falls back to the
+    /// individual keys when the sequence is not completed.
+    pub fn lookup(&self, keys: &[KeyEvent]) -> KeyMatch {
+        let last = match keys.last() {
+            Some(last) => last,
+            None => return KeyMatch::Unbound,
+        };
+        if let Some(sequence) = self.sequences.iter().find(|s| s.keys == keys) {
+            return KeyMatch::Actions((sequence.actions)(last));
+        }
+        if self
+            .sequences
+            .iter()
+            .any(|s| s.keys.len() > keys.len() && s.keys.starts_with(keys))
+        {
+            return KeyMatch::Pending;
+        }
+        match keys {
+            [event] => self
+                .first(event)
+                .map_or(KeyMatch::Unbound, KeyMatch::Actions),
+            _ => KeyMatch::Unbound,
+        }
+    }
+
+    /// Iterate the key sequences and then the single keys
+    /// that are bound to actions.
+    ///
+    /// Keys handled by a catch-all binding such as typed
+    /// characters, unbound keys and bindings replaced by an
+    /// earlier binding for the same keys are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = Binding> + '_ {
+        let sequences = self.sequences.iter().enumerate().filter_map(
+            move |(index, sequence)| {
+                if self.sequences[..index]
+                    .iter()
+                    .any(|s| s.keys == sequence.keys)
+                {
+                    return None;
+                }
+                let last = sequence.keys.last()?;
+                Some(Binding {
+                    keys: sequence.keys.clone(),
+                    actions: (sequence.actions)(last),
+                })
+            },
+        );
+        let keys = self.bindings.iter().enumerate().filter_map(
+            move |(index, definition)| {
+                let event = definition.event.as_ref()?;
+                if self.bindings[..index]
+                    .iter()
+                    .any(|d| d.event.as_ref() == Some(event))
+                {
+                    return None;
+                }
+                Some(Binding {
+                    keys: vec![*event],
+                    actions: (definition.actions)(event),
+                })
+            },
+        );
+        sequences.chain(keys).filter(|b| !b.actions.is_empty())
+    }
+
     /// Find the actions for the first key definition
     /// that matches the given key event.
+    ///
+    /// A named key with Shift that is not bound falls back to
+    /// the binding for the key without Shift, so for example
+    /// Shift+Ctrl+Left moves like Ctrl+Left.
     pub fn first(&self, event: &KeyEvent) -> Option<Vec<KeyAction>> {
-        let kind = match event.code {
-            KeyCode::Char(_) => {
-                if event.modifiers.intersects(KeyModifiers::CONTROL)
-                    || event.modifiers.intersects(KeyModifiers::ALT)
-                {
-                    KeyType::Named
-                } else {
-                    KeyType::Char
-                }
-            }
-            KeyCode::F(_) => KeyType::Func,
-            _ => KeyType::Named,
-        };
+        let found = self.find(event);
+        let shifted = event.modifiers.contains(KeyModifiers::SHIFT)
+            && KeyType::of(event) == KeyType::Named
+            && !matches!(event.code, KeyCode::Char(_));
+        if found.is_none() && shifted {
+            let modifiers = event.modifiers - KeyModifiers::SHIFT;
+            return self.find(&KeyEvent::new(event.code, modifiers));
+        }
+        found
+    }
 
+    /// Find the actions for the first key definition
+    /// that matches the key event exactly.
+    fn find(&self, event: &KeyEvent) -> Option<Vec<KeyAction>> {
+        let kind = KeyType::of(event);
         self.bindings.iter().find_map(|d| {
             if d.kind == kind {
-                match kind {
-                    KeyType::Named => {
-                        if let Some(ev) = &d.event {
-                            if ev == event {
-                                Some((d.actions)(event))
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
+                match (&d.event, &kind) {
+                    (Some(ev), _) if ev == event => Some((d.actions)(event)),
+                    (Some(_), _) | (None, KeyType::Named) => None,
+                    (None, KeyType::Char | KeyType::Func) => {
+                        Some((d.actions)(event))
                     }
-                    KeyType::Char | KeyType::Func => Some((d.actions)(event)),
                 }
             } else {
                 None
@@ -110,18 +828,193 @@ impl KeyBindings {
     }
 }
 
+/// Builder for customized key bindings.
+///
+/// Keys bound with the builder take precedence over bindings
+/// added with [KeyBindingsBuilder::extend] so the defaults can
+/// be changed for a few keys:
+///
+/// ```
+/// use crossterm_prompt::{KeyAction, KeyBindings};
+/// use crossterm_prompt::prelude::{KeyCode, KeyEvent, KeyModifiers};
+///
+/// let bindings = KeyBindings::builder()
+///     .bind(
+///         KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL),
+///         KeyAction::SubmitLine,
+///     )
+///     .unbind(KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL))
+///     .extend(KeyBindings::default())
+///     .build();
+/// ```
+pub struct KeyBindingsBuilder {
+    inner: KeyBindings,
+}
+
+impl KeyBindingsBuilder {
+    /// Bind a key to an action.
+    pub fn bind(self, event: KeyEvent, action: KeyAction) -> Self {
+        self.bind_sequence(&[event], action)
+    }
+
+    /// Bind a key to a closure returning the actions to perform.
+    pub fn bind_fn<F>(self, event: KeyEvent, actions: F) -> Self
+    where
+        F: Fn(&KeyEvent) -> Vec<KeyAction> + 'static,
+    {
+        self.bind_sequence_fn(&[event], actions)
+    }
+
+    /// Bind a sequence of keys to an action.
+    ///
+    /// The keys must be typed one after the other, for example
+    /// Ctrl+x followed by Ctrl+e.
+    pub fn bind_sequence(self, keys: &[KeyEvent], action: KeyAction) -> Self {
+        self.bind_sequence_fn(keys, move |_| vec![action])
+    }
+
+    /// Bind a sequence of keys to a closure returning the actions
+    /// to perform, the closure receives the last key.
+    pub fn bind_sequence_fn<F>(mut self, keys: &[KeyEvent], actions: F) -> Self
+    where
+        F: Fn(&KeyEvent) -> Vec<KeyAction> + 'static,
+    {
+        self.inner.bind_fn(keys, actions);
+        self
+    }
+
+    /// Remove the binding for a key.
+    ///
+    /// The key is ignored even when bindings added with
+    /// [KeyBindingsBuilder::extend] would handle it.
+    pub fn unbind(mut self, event: KeyEvent) -> Self {
+        self.inner.unbind(&[event]);
+        self
+    }
+
+    /// Add key bindings with a lower precedence than the keys
+    /// bound using this builder.
+    pub fn extend(mut self, bindings: KeyBindings) -> Self {
+        self.inner.bindings.extend(bindings.bindings);
+        self.inner.sequences.extend(bindings.sequences);
+        self
+    }
+
+    /// Create the key bindings.
+    pub fn build(self) -> KeyBindings {
+        self.inner
+    }
+}
+
+#[cfg(any(feature = "completion", feature = "search"))]
+impl KeyBindings {
+    /// Key bindings for the completion menu.
+    #[cfg(feature = "completion")]
+    pub(crate) fn completion_menu() -> Self {
+        let bindings = vec![
+            chars(),
+            named(KeyCode::Tab, KeyModifiers::NONE, KeyAction::SelectNext),
+            named(KeyCode::Down, KeyModifiers::NONE, KeyAction::SelectNext),
+            named(
+                KeyCode::BackTab,
+                KeyModifiers::NONE,
+                KeyAction::SelectPrevious,
+            ),
+            named(
+                KeyCode::BackTab,
+                KeyModifiers::SHIFT,
+                KeyAction::SelectPrevious,
+            ),
+            named(KeyCode::Up, KeyModifiers::NONE, KeyAction::SelectPrevious),
+            named(
+                KeyCode::Enter,
+                KeyModifiers::NONE,
+                KeyAction::AcceptSelection,
+            ),
+            named(KeyCode::Esc, KeyModifiers::NONE, KeyAction::CancelMode),
+            named(
+                KeyCode::Backspace,
+                KeyModifiers::NONE,
+                KeyAction::EraseCharacter,
+            ),
+        ];
+        Self {
+            bindings,
+            sequences: Vec::new(),
+        }
+    }
+
+    /// Key bindings for incremental history search.
+    #[cfg(feature = "search")]
+    pub(crate) fn search() -> Self {
+        let bindings = vec![
+            chars(),
+            named(
+                KeyCode::Char('r'),
+                KeyModifiers::CONTROL,
+                KeyAction::ReverseSearch,
+            ),
+            named(
+                KeyCode::Char('s'),
+                KeyModifiers::CONTROL,
+                KeyAction::ForwardSearch,
+            ),
+            named(
+                KeyCode::Char('g'),
+                KeyModifiers::CONTROL,
+                KeyAction::CancelMode,
+            ),
+            named(KeyCode::Esc, KeyModifiers::NONE, KeyAction::CancelMode),
+            named(
+                KeyCode::Backspace,
+                KeyModifiers::NONE,
+                KeyAction::EraseCharacter,
+            ),
+            named(KeyCode::Tab, KeyModifiers::NONE, KeyAction::MoveToLineEnd),
+            named(
+                KeyCode::Char('e'),
+                KeyModifiers::CONTROL,
+                KeyAction::MoveToLineEnd,
+            ),
+        ];
+        Self {
+            bindings,
+            sequences: Vec::new(),
+        }
+    }
+}
+
+/// Definition that writes any unmodified character.
+fn chars() -> KeyDefinition {
+    KeyDefinition {
+        kind: KeyType::Char,
+        event: None,
+        actions: Box::new(|event| match event.code {
+            KeyCode::Char(c) => vec![KeyAction::WriteChar(c)],
+            _ => unreachable!(),
+        }),
+    }
+}
+
+/// Definition for a named key triggering a single action.
+#[cfg(any(feature = "completion", feature = "search"))]
+fn named(
+    code: KeyCode,
+    modifiers: KeyModifiers,
+    action: KeyAction,
+) -> KeyDefinition {
+    KeyDefinition {
+        kind: KeyType::Named,
+        event: Some(KeyEvent { code, modifiers }),
+        actions: Box::new(move |_| vec![action]),
+    }
+}
+
 impl Default for KeyBindings {
     fn default() -> Self {
-        let bindings = vec![
+        let mut bindings = vec![
             // Char(c)
-            KeyDefinition {
-                kind: KeyType::Char,
-                event: None,
-                actions: Box::new(|event| match event.code {
-                    KeyCode::Char(c) => vec![KeyAction::WriteChar(c)],
-                    _ => unreachable!(),
-                }),
-            },
+            chars(),
             // Enter
             KeyDefinition {
                 kind: KeyType::Named,
@@ -158,6 +1051,62 @@ impl Default for KeyBindings {
                 }),
                 actions: Box::new(|_| vec![KeyAction::EraseCharacter]),
             },
+            // Delete
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Delete,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                actions: Box::new(|_| vec![KeyAction::EraseNextCharacter]),
+            },
+            // Home
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Home,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                actions: Box::new(|_| vec![KeyAction::MoveToLineBegin]),
+            },
+            // End
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::End,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                actions: Box::new(|_| vec![KeyAction::MoveToLineEnd]),
+            },
+            // F1
+            KeyDefinition {
+                kind: KeyType::Func,
+                event: Some(KeyEvent {
+                    code: KeyCode::F(1),
+                    modifiers: KeyModifiers::NONE,
+                }),
+                actions: Box::new(|_| vec![KeyAction::Help]),
+            },
+            #[cfg(any(feature = "completion", doc))]
+            // Tab
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Tab,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                actions: Box::new(|_| vec![KeyAction::Complete]),
+            },
+            #[cfg(any(feature = "debug", doc))]
+            // Ctrl+Alt+d
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('d'),
+                    modifiers: KeyModifiers::CONTROL | KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::ToggleDebug]),
+            },
             #[cfg(any(feature = "history", doc))]
             // Up
             KeyDefinition {
@@ -178,6 +1127,140 @@ impl Default for KeyBindings {
                 }),
                 actions: Box::new(|_| vec![KeyAction::HistoryNext]),
             },
+            #[cfg(any(feature = "history", doc))]
+            // PageUp
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::PageUp,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                actions: Box::new(|_| vec![KeyAction::HistoryFirst]),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // PageDown
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::PageDown,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                actions: Box::new(|_| vec![KeyAction::HistoryLast]),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // Alt+p
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('p'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::HistorySearchBackward]),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // Alt+n
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('n'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::HistorySearchForward]),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // Ctrl+Up
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Up,
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::HistoryPrevious]),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // Ctrl+Down
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Down,
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::HistoryNext]),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // Alt+Up
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Up,
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| {
+                    vec![KeyAction::HistorySubstringSearchBackward]
+                }),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // Alt+Down
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Down,
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| {
+                    vec![KeyAction::HistorySubstringSearchForward]
+                }),
+            },
+            #[cfg(any(feature = "search", doc))]
+            // Ctrl+r
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('r'),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::ReverseSearch]),
+            },
+            #[cfg(any(feature = "search", doc))]
+            // Ctrl+s
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('s'),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::ForwardSearch]),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // Alt+a
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('a'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::ToggleHistoryScope]),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // Ctrl+o
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('o'),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::OperateAndGetNext]),
+            },
+            #[cfg(any(feature = "history", doc))]
+            // Alt+.
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('.'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::YankLastArg]),
+            },
             // Ctrl+c
             KeyDefinition {
                 kind: KeyType::Named,
@@ -185,7 +1268,9 @@ impl Default for KeyBindings {
                     code: KeyCode::Char('c'),
                     modifiers: KeyModifiers::CONTROL,
                 }),
-                actions: Box::new(|_| vec![KeyAction::AbortPrompt]),
+                actions: Box::new(|_| {
+                    vec![KeyAction::AbortPrompt(AbortReason::Interrupt)]
+                }),
             },
             // Ctrl+d
             KeyDefinition {
@@ -194,7 +1279,9 @@ impl Default for KeyBindings {
                     code: KeyCode::Char('d'),
                     modifiers: KeyModifiers::CONTROL,
                 }),
-                actions: Box::new(|_| vec![KeyAction::AbortPrompt]),
+                actions: Box::new(|_| {
+                    vec![KeyAction::EraseNextCharacterOrEndOfFile]
+                }),
             },
             // Ctrl+l
             KeyDefinition {
@@ -236,8 +1323,8 @@ impl Default for KeyBindings {
             KeyDefinition {
                 kind: KeyType::Named,
                 event: Some(KeyEvent {
-                    code: KeyCode::Char('u'),
-                    modifiers: KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+                    code: KeyCode::Char('k'),
+                    modifiers: KeyModifiers::CONTROL,
                 }),
                 actions: Box::new(|_| vec![KeyAction::EraseToLineEnd]),
             },
@@ -250,8 +1337,459 @@ impl Default for KeyBindings {
                 }),
                 actions: Box::new(|_| vec![KeyAction::ErasePreviousWord]),
             },
+            // Alt+Backspace
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Backspace,
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::ErasePreviousWord]),
+            },
+            // Ctrl+Backspace, where the terminal distinguishes it
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Backspace,
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::ErasePreviousWord]),
+            },
+            // Alt+d
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('d'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::EraseNextWord]),
+            },
+            // Ctrl+Space
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char(' '),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::SetMark]),
+            },
+            // Alt+w
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('w'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::CopyRegion]),
+            },
+            // Alt+u
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('u'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::UpcaseWord]),
+            },
+            // Alt+l
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('l'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::DowncaseWord]),
+            },
+            // Alt+c
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::CapitalizeWord]),
+            },
+            // Alt+f
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('f'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::ForwardWord]),
+            },
+            // Ctrl+Right
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Right,
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::ForwardWord]),
+            },
+            // Alt+b
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('b'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::BackwardWord]),
+            },
+            // Ctrl+Left
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Left,
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::BackwardWord]),
+            },
+            // Ctrl+y
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('y'),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::Yank]),
+            },
+            // Ctrl+v
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('v'),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::QuotedInsert]),
+            },
+            // Ctrl+_
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('_'),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::Undo]),
+            },
+            // Ctrl+_ as reported by most terminals
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('7'),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                actions: Box::new(|_| vec![KeyAction::Undo]),
+            },
+            // Alt+r
+            KeyDefinition {
+                kind: KeyType::Named,
+                event: Some(KeyEvent {
+                    code: KeyCode::Char('r'),
+                    modifiers: KeyModifiers::ALT,
+                }),
+                actions: Box::new(|_| vec![KeyAction::TogglePasswordReveal]),
+            },
         ];
 
-        Self { bindings }
+        // Alt+0 to Alt+9
+        bindings.extend((0..=9).map(|digit| KeyDefinition {
+            kind: KeyType::Named,
+            event: Some(KeyEvent {
+                code: KeyCode::Char((b'0' + digit) as char),
+                modifiers: KeyModifiers::ALT,
+            }),
+            actions: Box::new(move |_| vec![KeyAction::DigitArgument(digit)]),
+        }));
+
+        let ctrl_x = KeyEvent {
+            code: KeyCode::Char('x'),
+            modifiers: KeyModifiers::CONTROL,
+        };
+        let sequence = |c, action| KeySequence {
+            keys: vec![
+                ctrl_x,
+                KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE),
+            ],
+            actions: Box::new(move |_| vec![action]),
+        };
+        let sequences = vec![
+            // Ctrl+x (
+            sequence('(', KeyAction::StartMacro),
+            // Ctrl+x )
+            sequence(')', KeyAction::EndMacro),
+            // Ctrl+x e
+            sequence('e', KeyAction::CallLastMacro),
+            // Ctrl+x Ctrl+u
+            KeySequence {
+                keys: vec![
+                    ctrl_x,
+                    KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL),
+                ],
+                actions: Box::new(|_| vec![KeyAction::Undo]),
+            },
+            // Ctrl+x Ctrl+x
+            KeySequence {
+                keys: vec![ctrl_x, ctrl_x],
+                actions: Box::new(|_| vec![KeyAction::ExchangePointAndMark]),
+            },
+            // Ctrl+x Ctrl+e
+            KeySequence {
+                keys: vec![
+                    ctrl_x,
+                    KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL),
+                ],
+                actions: Box::new(|_| vec![KeyAction::EditAndExecuteCommand]),
+            },
+        ];
+
+        Self {
+            bindings,
+            sequences,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn builder_precedence() {
+        let ctrl_l = key(KeyCode::Char('l'), KeyModifiers::CONTROL);
+        let ctrl_j = key(KeyCode::Char('j'), KeyModifiers::CONTROL);
+        let plus = key(KeyCode::Char('+'), KeyModifiers::NONE);
+        let bindings = KeyBindings::builder()
+            .bind(ctrl_j, KeyAction::MoveCursorLeft)
+            .bind(ctrl_j, KeyAction::SubmitLine)
+            .bind_fn(plus, |_| {
+                vec![KeyAction::WriteChar(' '), KeyAction::WriteChar('+')]
+            })
+            .unbind(ctrl_l)
+            .extend(KeyBindings::default())
+            .build();
+
+        let actions = bindings.first(&ctrl_j).unwrap();
+        assert!(matches!(actions[..], [KeyAction::SubmitLine]));
+        assert_eq!(2, bindings.first(&plus).unwrap().len());
+        assert!(bindings.first(&ctrl_l).unwrap().is_empty());
+
+        let a = key(KeyCode::Char('a'), KeyModifiers::NONE);
+        let actions = bindings.first(&a).unwrap();
+        assert!(matches!(actions[..], [KeyAction::WriteChar('a')]));
+        assert!(KeyBindings::builder().build().first(&a).is_none());
+    }
+
+    #[test]
+    fn command_names() {
+        assert_eq!(
+            Some(KeyAction::ErasePreviousWord),
+            KeyAction::from_name("backward-kill-word")
+        );
+        assert_eq!(
+            Some("backward-kill-word"),
+            KeyAction::ErasePreviousWord.name()
+        );
+        assert_eq!(Ok(KeyAction::SubmitLine), "accept-line".parse());
+        assert_eq!(
+            Ok(KeyAction::EditAndExecuteCommand),
+            "edit-and-execute-command".parse()
+        );
+        let err = "no-such-command".parse::<KeyAction>().unwrap_err();
+        assert_eq!("no-such-command: unknown command", err.to_string());
+        assert_eq!(None, KeyAction::WriteChar('a').name());
+        assert_eq!(None, KeyAction::MoveToColumn(3).name());
+        assert_eq!(
+            CommandCategory::Motion,
+            KeyAction::MoveToColumn(3).category()
+        );
+        for name in KeyAction::names() {
+            assert!(KeyAction::from_name(name).unwrap().name().is_some());
+        }
+    }
+
+    #[test]
+    fn sequence_lookup() {
+        let ctrl_x = key(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        let ctrl_e = key(KeyCode::Char('e'), KeyModifiers::CONTROL);
+        let open = key(KeyCode::Char('('), KeyModifiers::NONE);
+        let bindings = KeyBindings::builder()
+            .bind(ctrl_x, KeyAction::Yank)
+            .bind_sequence(&[ctrl_x, ctrl_e], KeyAction::MoveToLineEnd)
+            .extend(KeyBindings::default())
+            .build();
+
+        assert_eq!(KeyMatch::Pending, bindings.lookup(&[ctrl_x]));
+        assert_eq!(
+            KeyMatch::Actions(vec![KeyAction::MoveToLineEnd]),
+            bindings.lookup(&[ctrl_x, ctrl_e])
+        );
+        assert_eq!(
+            KeyMatch::Actions(vec![KeyAction::StartMacro]),
+            bindings.lookup(&[ctrl_x, open])
+        );
+        assert_eq!(
+            KeyMatch::Unbound,
+            bindings
+                .lookup(&[ctrl_x, key(KeyCode::Char('z'), KeyModifiers::NONE)])
+        );
+        assert_eq!(KeyMatch::Unbound, bindings.lookup(&[]));
+        assert_eq!(Some(vec![KeyAction::Yank]), bindings.first(&ctrl_x));
+    }
+
+    #[test]
+    fn iterate_bindings() {
+        let ctrl_a = key(KeyCode::Char('a'), KeyModifiers::CONTROL);
+        let ctrl_e = key(KeyCode::Char('e'), KeyModifiers::CONTROL);
+        let bindings = KeyBindings::builder()
+            .bind(ctrl_a, KeyAction::MoveToLineEnd)
+            .unbind(ctrl_e)
+            .extend(KeyBindings::default())
+            .build();
+        let all = bindings.iter().collect::<Vec<_>>();
+        let ctrl_a = all.iter().filter(|b| b.keys() == [ctrl_a]);
+        assert_eq!(
+            vec![Some("end-of-line")],
+            ctrl_a.map(Binding::command_name).collect::<Vec<_>>()
+        );
+        assert!(all.iter().all(|b| b.keys() != [ctrl_e]));
+        assert!(all.iter().any(|b| b.key_string() == "Ctrl+x ("
+            && b.command_name() == Some("start-kbd-macro")));
+        assert_eq!(
+            Some("Alt+3"),
+            all.iter()
+                .find(|b| b.actions() == [KeyAction::DigitArgument(3)])
+                .map(|b| b.key_string())
+                .as_deref()
+        );
+    }
+
+    #[test]
+    fn universal_argument_binding() {
+        let ctrl_u = key(KeyCode::Char('u'), KeyModifiers::CONTROL);
+        assert_eq!(
+            KeyMatch::Actions(vec![KeyAction::EraseToLineBegin]),
+            KeyBindings::default().lookup(&[ctrl_u])
+        );
+        assert_eq!(
+            KeyMatch::Actions(vec![KeyAction::UniversalArgument]),
+            KeyBindings::with_universal_argument().lookup(&[ctrl_u])
+        );
+    }
+
+    #[test]
+    fn function_keys() {
+        let f1 = key(KeyCode::F(1), KeyModifiers::NONE);
+        let f5 = key(KeyCode::F(5), KeyModifiers::NONE);
+        let ctrl_f5 = key(KeyCode::F(5), KeyModifiers::CONTROL);
+        let mut bindings = KeyBindings::default();
+        assert_eq!(Some(vec![KeyAction::Help]), bindings.first(&f1));
+        assert_eq!(None, bindings.first(&f5));
+
+        bindings.bind(&[f5], KeyAction::Yank);
+        bindings.bind(&[ctrl_f5], KeyAction::ClearScreen);
+        assert_eq!(Some(vec![KeyAction::Yank]), bindings.first(&f5));
+        assert_eq!(
+            Some(vec![KeyAction::ClearScreen]),
+            bindings.first(&ctrl_f5)
+        );
+        assert_eq!(
+            None,
+            bindings.first(&key(KeyCode::F(6), KeyModifiers::NONE))
+        );
+    }
+
+    #[test]
+    fn word_motion_keys() {
+        let bindings = KeyBindings::default();
+        for (code, modifiers, action) in [
+            (
+                KeyCode::Char('f'),
+                KeyModifiers::ALT,
+                KeyAction::ForwardWord,
+            ),
+            (
+                KeyCode::Char('b'),
+                KeyModifiers::ALT,
+                KeyAction::BackwardWord,
+            ),
+            (
+                KeyCode::Right,
+                KeyModifiers::CONTROL,
+                KeyAction::ForwardWord,
+            ),
+            (
+                KeyCode::Left,
+                KeyModifiers::CONTROL,
+                KeyAction::BackwardWord,
+            ),
+        ] {
+            assert_eq!(
+                Some(vec![action]),
+                bindings.first(&key(code, modifiers))
+            );
+        }
+    }
+
+    #[test]
+    fn backward_kill_word_keys() {
+        let bindings = KeyBindings::default();
+        for modifiers in [KeyModifiers::ALT, KeyModifiers::CONTROL] {
+            assert_eq!(
+                Some(vec![KeyAction::ErasePreviousWord]),
+                bindings.first(&key(KeyCode::Backspace, modifiers))
+            );
+        }
+    }
+
+    #[test]
+    fn modified_named_keys() {
+        let bindings = KeyBindings::default();
+        let ctrl_shift = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+        assert_eq!(
+            Some(vec![KeyAction::BackwardWord]),
+            bindings.first(&key(KeyCode::Left, ctrl_shift))
+        );
+        assert_eq!(
+            Some(vec![KeyAction::MoveCursorRight]),
+            bindings.first(&key(KeyCode::Right, KeyModifiers::SHIFT))
+        );
+        assert_eq!(None, bindings.first(&key(KeyCode::Char('q'), ctrl_shift)));
+        assert_eq!(
+            Some(vec![KeyAction::WriteChar('Q')]),
+            bindings.first(&key(KeyCode::Char('Q'), KeyModifiers::SHIFT))
+        );
+        #[cfg(feature = "history")]
+        assert_eq!(
+            Some(vec![KeyAction::HistoryPrevious]),
+            bindings.first(&key(KeyCode::Up, KeyModifiers::CONTROL))
+        );
+    }
+
+    #[test]
+    fn rebind() {
+        let ctrl_t = key(KeyCode::Char('t'), KeyModifiers::CONTROL);
+        let ctrl_x = key(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        let open = key(KeyCode::Char('('), KeyModifiers::NONE);
+        let a = key(KeyCode::Char('a'), KeyModifiers::NONE);
+        let mut bindings = KeyBindings::default();
+
+        bindings.bind(&[ctrl_t], KeyAction::Yank);
+        bindings.bind(&[ctrl_t], KeyAction::MoveToLineEnd);
+        assert_eq!(
+            Some(vec![KeyAction::MoveToLineEnd]),
+            bindings.first(&ctrl_t)
+        );
+
+        bindings.unbind(&[a]);
+        assert_eq!(Some(vec![]), bindings.first(&a));
+
+        bindings.unbind(&[ctrl_x, open]);
+        assert_eq!(KeyMatch::Pending, bindings.lookup(&[ctrl_x]));
+        assert_eq!(KeyMatch::Unbound, bindings.lookup(&[ctrl_x, open]));
     }
 }
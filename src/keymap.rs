@@ -0,0 +1,319 @@
+//! Keys written as text and keymaps loaded from configuration.
+//!
+//! Keys are written as the modifiers and the key joined with `+`,
+//! for example `Ctrl+a`, `Alt+Backspace` or `F5`; the keys of a
+//! sequence are separated by spaces such as `Ctrl+x Ctrl+e`.
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+
+#[cfg(any(feature = "serde", doc))]
+use crate::{KeyBindings, UnknownCommand};
+#[cfg(any(feature = "serde", doc))]
+use std::collections::BTreeMap;
+
+/// Error for text that is not a valid key.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InvalidKey {
+    /// The text that could not be parsed.
+    pub key: String,
+}
+
+impl fmt::Display for InvalidKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: invalid key", self.key)
+    }
+}
+
+impl std::error::Error for InvalidKey {}
+
+/// Parse a sequence of keys separated by spaces.
+pub fn parse_keys(text: &str) -> Result<Vec<KeyEvent>, InvalidKey> {
+    let keys = text
+        .split_whitespace()
+        .map(parse_key)
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(InvalidKey {
+            key: text.to_string(),
+        });
+    }
+    Ok(keys)
+}
+
+/// Parse a single key such as `Ctrl+a`.
+pub fn parse_key(text: &str) -> Result<KeyEvent, InvalidKey> {
+    let invalid = || InvalidKey {
+        key: text.to_string(),
+    };
+    // A trailing `+` is the plus key
+    let (modifiers, key) = match text.strip_suffix('+') {
+        Some(modifiers) if modifiers.is_empty() || modifiers.ends_with('+') => {
+            (modifiers, "+")
+        }
+        _ => text.rsplit_once('+').unwrap_or(("", text)),
+    };
+
+    let mut event =
+        KeyEvent::new(code(key).ok_or_else(invalid)?, KeyModifiers::NONE);
+    for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+        event.modifiers |= match &modifier.to_lowercase()[..] {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" | "meta" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(invalid()),
+        };
+    }
+
+    // Typed upper case letters are reported with shift
+    if let KeyCode::Char(c) = event.code {
+        if c.is_uppercase() {
+            event.modifiers |= KeyModifiers::SHIFT;
+        }
+    }
+    Ok(event)
+}
+
+/// Format a sequence of keys as text that [parse_keys] accepts.
+pub fn format_keys(keys: &[KeyEvent]) -> String {
+    keys.iter().map(format_key).collect::<Vec<_>>().join(" ")
+}
+
+/// Format a key as text that [parse_key] accepts, such as `Ctrl+a`.
+pub fn format_key(event: &KeyEvent) -> String {
+    let mut text = String::new();
+    if event.modifiers.contains(KeyModifiers::CONTROL) {
+        text.push_str("Ctrl+");
+    }
+    if event.modifiers.contains(KeyModifiers::ALT) {
+        text.push_str("Alt+");
+    }
+    let shifted = matches!(event.code, KeyCode::Char(c) if c.is_uppercase());
+    if event.modifiers.contains(KeyModifiers::SHIFT) && !shifted {
+        text.push_str("Shift+");
+    }
+    match event.code {
+        KeyCode::Char(' ') => text.push_str("Space"),
+        KeyCode::Char(c) => text.push(c),
+        KeyCode::F(number) => text.push_str(&format!("F{}", number)),
+        code => text.push_str(&format!("{:?}", code)),
+    }
+    text
+}
+
+/// Key code for the name of a key.
+fn code(name: &str) -> Option<KeyCode> {
+    let code = match &name.to_lowercase()[..] {
+        "backspace" => KeyCode::Backspace,
+        "enter" | "return" => KeyCode::Enter,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "esc" | "escape" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        lower => match lower.strip_prefix('f').map(str::parse::<u8>) {
+            Some(Ok(number)) if (1..=24).contains(&number) => {
+                KeyCode::F(number)
+            }
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return None,
+                }
+            }
+        },
+    };
+    Some(code)
+}
+
+/// Key bindings listed in a configuration file.
+///
+/// Each binding maps keys to the name of an action, see
+/// [KeyAction::names](crate::KeyAction::names); an empty name
+/// unbinds the keys. For example in TOML:
+///
+/// ```toml
+/// [bindings]
+/// "Ctrl+x Ctrl+e" = "end-of-line"
+/// "Alt+Backspace" = "backward-kill-word"
+/// "Ctrl+l" = ""
+/// ```
+#[cfg(any(feature = "serde", doc))]
+#[doc(cfg(feature = "serde"))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeymapConfig {
+    /// Whether the default bindings are used for keys
+    /// that are not listed.
+    ///
+    /// Without the defaults typed characters are still written
+    /// so only the listed keys need to be bound.
+    #[cfg_attr(feature = "serde", serde(default = "defaults"))]
+    pub defaults: bool,
+    /// Keys and the names of the actions they are bound to.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bindings: BTreeMap<String, String>,
+}
+
+#[cfg(feature = "serde")]
+fn defaults() -> bool {
+    true
+}
+
+#[cfg(any(feature = "serde", doc))]
+impl Default for KeymapConfig {
+    fn default() -> Self {
+        Self {
+            defaults: true,
+            bindings: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(any(feature = "serde", doc))]
+impl KeymapConfig {
+    /// Create key bindings from the configuration.
+    pub fn key_bindings(&self) -> Result<KeyBindings, KeymapError> {
+        let mut bindings = if self.defaults {
+            KeyBindings::default()
+        } else {
+            KeyBindings::typing()
+        };
+        for (keys, name) in &self.bindings {
+            let keys = parse_keys(keys).map_err(KeymapError::Key)?;
+            if name.is_empty() {
+                bindings.unbind(&keys);
+            } else {
+                let action = name.parse().map_err(KeymapError::Command)?;
+                bindings.bind(&keys, action);
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+/// Error for a keymap configuration that cannot be used.
+#[cfg(any(feature = "serde", doc))]
+#[doc(cfg(feature = "serde"))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum KeymapError {
+    /// A key is not valid.
+    Key(InvalidKey),
+    /// A command name is not known.
+    Command(UnknownCommand),
+}
+
+#[cfg(any(feature = "serde", doc))]
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(e) => e.fmt(f),
+            Self::Command(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(any(feature = "serde", doc))]
+impl std::error::Error for KeymapError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_key_text() {
+        assert_eq!(
+            Ok(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL)),
+            parse_key("Ctrl+a")
+        );
+        assert_eq!(
+            Ok(KeyEvent::new(
+                KeyCode::Char('+'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            )),
+            parse_key("ctrl+alt++")
+        );
+        assert_eq!(
+            Ok(KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT)),
+            parse_key("A")
+        );
+        assert_eq!(Ok(KeyEvent::from(KeyCode::F(5))), parse_key("F5"));
+        assert_eq!(Ok(KeyEvent::from(KeyCode::Char('+'))), parse_key("+"));
+        assert_eq!(2, parse_keys("Ctrl+x  PageUp").unwrap().len());
+        assert!(parse_key("Hyper+a").is_err());
+        assert!(parse_key("Ctrl+ab").is_err());
+        assert_eq!(
+            "   : invalid key",
+            parse_keys("   ").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn format_key_text() {
+        for text in ["Ctrl+a", "Alt+Backspace", "Ctrl+Alt++", "A", "Space"] {
+            assert_eq!(text, format_key(&parse_key(text).unwrap()));
+        }
+        assert_eq!(
+            "Shift+Left PageDown F12",
+            format_keys(&parse_keys("shift+left pagedown f12").unwrap())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn keymap_config() {
+        use crate::{KeyAction, KeyMatch};
+
+        let config: KeymapConfig = toml::from_str(
+            r#"
+            [bindings]
+            "Ctrl+x Ctrl+e" = "end-of-line"
+            "Ctrl+t" = "yank"
+            "Ctrl+l" = ""
+            "#,
+        )
+        .unwrap();
+        assert!(config.defaults);
+        let bindings = config.key_bindings().unwrap();
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        assert_eq!(
+            KeyMatch::Actions(vec![KeyAction::MoveToLineEnd]),
+            bindings.lookup(&[ctrl('x'), ctrl('e')])
+        );
+        assert_eq!(Some(vec![KeyAction::Yank]), bindings.first(&ctrl('t')));
+        assert_eq!(Some(vec![]), bindings.first(&ctrl('l')));
+        assert!(bindings.first(&ctrl('a')).is_some());
+
+        let config: KeymapConfig = toml::from_str(
+            "defaults = false\n[bindings]\nEnter = \"accept-line\"",
+        )
+        .unwrap();
+        let bindings = config.key_bindings().unwrap();
+        assert_eq!(
+            Some(vec![KeyAction::WriteChar('a')]),
+            bindings.first(&KeyEvent::from(KeyCode::Char('a')))
+        );
+        assert_eq!(
+            Some(vec![KeyAction::SubmitLine]),
+            bindings.first(&KeyEvent::from(KeyCode::Enter))
+        );
+        assert_eq!(None, bindings.first(&ctrl('a')));
+
+        let config: KeymapConfig =
+            toml::from_str("defaults = false\n[bindings]\nEnter = \"submit\"")
+                .unwrap();
+        assert_eq!(
+            "submit: unknown command",
+            config.key_bindings().err().unwrap().to_string()
+        );
+    }
+}
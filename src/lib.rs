@@ -1,67 +1,274 @@
 #![deny(missing_docs)]
 #![feature(doc_cfg)]
-#![feature(thread_id_value)]
+#![cfg_attr(feature = "panic", feature(thread_id_value))]
 
 //! Prompt library for crossterm.
+//!
+//! Optional subsystems are enabled with features so that
+//! only the code and dependencies in use are compiled:
+//!
+//! * `completion` - completion menu for the word before the cursor.
+//! * `debug` - overlay showing the state of the prompt.
+//! * `history` - history navigation, expansion and persistence.
+//! * `inputrc` - key bindings read from a readline `inputrc` file.
+//! * `panic` - panic hooks that restore the terminal.
+//! * `search` - incremental history search, enables `history`.
+//! * `serde` - keymaps loaded from configuration files.
+//! * `shell` - [shell()] loop, enables all of the above
+//!   except `debug`, `panic` and `styling`.
+//! * `styling` - highlighters, themes and styled prefixes.
+//!
+//! No features are enabled by default.
 use anyhow::{bail, Result};
 use crossterm::{
     cursor,
-    event::{read, Event},
+    event::{poll, read, Event},
     terminal::{disable_raw_mode, enable_raw_mode, size, Clear, ClearType},
     ExecutableCommand, QueueableCommand,
 };
 use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::error::Error;
-use std::io::Write;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
 
+mod argument;
+mod clipboard;
+mod editor;
+mod escape;
+mod external;
+mod frame;
+pub mod hyperlink;
 mod key_binding;
+pub mod keymap;
+mod macros;
+mod message;
 mod options;
 
-#[cfg(any(feature = "panic", doc))]
-#[doc(cfg(feature = "panic"))]
+#[cfg(feature = "panic")]
 mod panic;
 
 #[cfg(feature = "panic")]
 pub use panic::{stderr_panic_hook, stdout_panic_hook};
 
 mod terminal_buffer;
+mod title;
+mod undo;
 
+pub use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+pub use editor::{CommandOutcome, Editor};
 pub use key_binding::*;
 pub use options::*;
-use terminal_buffer::TerminalBuffer;
+use style::CursorStyle;
+pub use terminal_buffer::TerminalBuffer;
+use terminal_buffer::WordCase;
 
 #[cfg(any(feature = "history", doc))]
 #[doc(cfg(feature = "history"))]
 pub mod history;
 
+#[cfg(feature = "completion")]
+mod completion;
+
+#[cfg(any(feature = "inputrc", doc))]
+#[doc(cfg(feature = "inputrc"))]
+pub mod inputrc;
+
+#[cfg(feature = "debug")]
+mod debug;
+
+#[cfg(any(feature = "completion", feature = "search"))]
+mod mode;
+
+#[cfg(feature = "search")]
+mod search;
+
+pub mod prelude;
+
+#[cfg(any(feature = "history", feature = "completion", doc))]
+#[doc(cfg(any(feature = "history", feature = "completion")))]
+pub mod shell;
+
+pub mod step;
+pub mod style;
+
 #[cfg(any(feature = "shell", doc))]
 #[doc(cfg(feature = "shell"))]
-/// Run an infinite shell prompt.
-pub fn shell<'a, P, W, O, E, H>(
+/// Run a shell prompt until the end of input.
+///
+/// The prefix and options are requested for each line so they
+/// may change between lines, the options can be borrowed or
+/// created for each line, for example to enable password mode
+/// for a single command.
+///
+/// A line submitted with [KeyAction::AcceptAndHold] is shown
+/// again in the next prompt and the history entry following a line
+/// submitted with [KeyAction::OperateAndGetNext] is shown in the
+/// next prompt, even when its options are created for each line.
+///
+/// A line aborted with Ctrl+c is discarded and the next prompt
+/// is shown, like a shell.
+///
+/// Returns when Ctrl+d is pressed on an empty line; when the prompt
+/// is aborted for another reason, for example by a [CancelToken] or
+/// the prompt timeout, the [Aborted] error is returned.
+pub fn shell<P, S, W, O, R, E, H>(
     prefix: P,
-    writer: &'a mut W,
+    writer: &mut W,
     options: O,
     handler: H,
 ) -> Result<()>
 where
-    P: Fn() -> &'a str,
+    P: Fn(&shell::ShellContext) -> S,
+    S: AsRef<str>,
     W: Write,
-    O: Fn() -> &'a PromptOptions,
+    O: Fn(&shell::ShellContext) -> R,
+    R: std::borrow::Borrow<PromptOptions>,
     E: Error + Send + Sync + 'static,
     H: Fn(String) -> std::result::Result<(), E>,
 {
+    let mut context = shell::ShellContext::default();
+    let mut carried = Carried::default();
     loop {
-        let prompt_prefix = (prefix)();
-        let opts = (options)();
-        let value = prompt(prompt_prefix, writer, opts)?;
+        let prompt_prefix = (prefix)(&context);
+        let opts = (options)(&context);
+        let opts = opts.borrow();
+        opts.in_shell.set(true);
+        carried.restore(opts);
+        let value = prompt(prompt_prefix.as_ref(), writer, opts);
+        opts.in_shell.set(false);
+        carried.take(opts);
+        let value = match value {
+            Err(e) if e.is::<EndOfInput>() => return Ok(()),
+            Err(e)
+                if e.downcast_ref::<Aborted>()
+                    == Some(&Aborted(AbortReason::Interrupt)) =>
+            {
+                continue;
+            }
+            value => value?,
+        };
+        let started = std::time::Instant::now();
+        let line = value.clone();
         (handler)(value)?;
+
+        // Record how long the command took on the history entry
+        if let Some(history) = &opts.history {
+            let elapsed = started.elapsed();
+            history.acquire().update_last(&mut |entry| {
+                if entry.value == line && entry.duration.is_none() {
+                    entry.duration = Some(elapsed);
+                }
+            });
+        }
+
+        context.lines += 1;
+        context.last_line = Some(line);
+    }
+}
+
+/// State of a prompt carried to the next prompt of the shell,
+/// the options may be created for each line.
+#[cfg(feature = "shell")]
+#[derive(Debug, Default)]
+struct Carried {
+    /// Line held by [KeyAction::AcceptAndHold].
+    held: Option<String>,
+    /// History entry following the line submitted by
+    /// [KeyAction::OperateAndGetNext].
+    next: Option<(String, usize)>,
+}
+
+#[cfg(feature = "shell")]
+impl Carried {
+    /// Take the state left by a prompt from its options.
+    fn take(&mut self, options: &PromptOptions) {
+        self.held = options.held_line.take();
+        self.next = options.next_line.take();
+    }
+
+    /// Move the state to the options of the next prompt.
+    fn restore(&mut self, options: &PromptOptions) {
+        if let Some(line) = self.held.take() {
+            *options.held_line.borrow_mut() = Some(line);
+        }
+        if let Some(next) = self.next.take() {
+            *options.next_line.borrow_mut() = Some(next);
+        }
+    }
+}
+
+/// Error returned when the input ends without a line,
+/// for example when Ctrl+d is pressed on an empty line.
+///
+/// Use [anyhow::Error::is] to tell the end of input apart
+/// from other errors:
+///
+/// ```no_run
+/// use crossterm_prompt::{prompt, EndOfInput, PromptOptions};
+///
+/// let mut stdout = std::io::stdout();
+/// match prompt("> ", &mut stdout, &PromptOptions::new()) {
+///     Ok(line) => println!("{}", line),
+///     Err(e) if e.is::<EndOfInput>() => println!("bye"),
+///     Err(e) => eprintln!("{}", e),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct EndOfInput;
+
+impl std::fmt::Display for EndOfInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unexpected end of input")
+    }
+}
+
+impl Error for EndOfInput {}
+
+/// Error returned when a prompt is aborted before a line
+/// is submitted, with the trigger that aborted it.
+///
+/// Use [anyhow::Error::downcast_ref] to tell the user changing
+/// their mind apart from the prompt being cancelled:
+///
+/// ```no_run
+/// use crossterm_prompt::{prompt, AbortReason, Aborted, PromptOptions};
+///
+/// let mut stdout = std::io::stdout();
+/// match prompt("> ", &mut stdout, &PromptOptions::new()) {
+///     Ok(line) => println!("{}", line),
+///     Err(e) => match e.downcast_ref::<Aborted>() {
+///         Some(Aborted(AbortReason::Interrupt)) => println!("^C"),
+///         Some(Aborted(reason)) => println!("aborted: {:?}", reason),
+///         None => eprintln!("{}", e),
+///     },
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Aborted(pub AbortReason);
+
+impl std::fmt::Display for Aborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            AbortReason::Interrupt => write!(f, "prompt interrupted"),
+            AbortReason::EndOfFile => write!(f, "unexpected end of file"),
+            AbortReason::Escape => write!(f, "prompt escaped"),
+            AbortReason::Cancelled => write!(f, "prompt cancelled"),
+            AbortReason::Timeout => write!(f, "prompt timed out"),
+        }
     }
 }
 
+impl Error for Aborted {}
+
 /// Show a prompt.
-pub fn prompt<'a, S: AsRef<str>, W>(
+///
+/// Returns an [EndOfInput] error when Ctrl+d is pressed
+/// on an empty line and an [Aborted] error when the prompt
+/// is aborted, for example by Ctrl+c or a [CancelToken].
+pub fn prompt<S: AsRef<str>, W>(
     prefix: S,
-    writer: &'a mut W,
+    writer: &mut W,
     options: &PromptOptions,
 ) -> Result<String>
 where
@@ -71,11 +278,138 @@ where
         bail!("prompt prefix is too long");
     }
 
+    if options.title.is_some() {
+        title::save(writer)?;
+    }
+    let value = required(prefix.as_ref(), writer, options, &mut run);
+    if options.title.is_some() {
+        title::restore(writer)?;
+    }
+    if options.cursor_styles.is_some() {
+        writer.execute(CursorStyle::Default)?;
+    }
+    value
+}
+
+/// Show a prompt and parse the value to another type.
+pub fn parse<T, W, S: AsRef<str>>(
+    prefix: S,
+    writer: &mut W,
+    options: &PromptOptions,
+) -> Result<T>
+where
+    T: std::str::FromStr,
+    <T as std::str::FromStr>::Err: Error + Sync + Send + 'static,
+    W: Write,
+{
+    let value: String = prompt(prefix.as_ref(), writer, options)?;
+    let value: T = value.parse::<T>()?;
+    Ok(value)
+}
+
+/// Show a prompt reading the line without entering raw mode.
+///
+/// The line is read from `reader` so the terminal handles editing,
+/// options for requiring, validating and transforming the value are
+/// applied the same as [prompt] but editing features such as key
+/// bindings, history and password masking are not available.
+///
+/// Returns an [EndOfInput] error when the reader reaches
+/// the end of input.
+pub fn simple_prompt<S, W, R>(
+    prefix: S,
+    writer: &mut W,
+    reader: &mut R,
+    options: &PromptOptions,
+) -> Result<String>
+where
+    S: AsRef<str>,
+    W: Write,
+    R: BufRead,
+{
+    if prefix.as_ref().len() > u16::MAX as usize {
+        bail!("prompt prefix is too long");
+    }
+
+    required(
+        prefix.as_ref(),
+        writer,
+        options,
+        &mut |prefix, writer, options| loop {
+            // Without an active prompt invalid lines are read again
+            let line = read_line(prefix, writer, reader)?;
+            match options.validation.as_ref().map(|v| (v.validate)(&line)) {
+                Some(Err(message)) => {
+                    if !message.is_empty() {
+                        writeln!(writer, "{}", message)?;
+                    }
+                }
+                _ => return Ok(line),
+            }
+        },
+    )
+}
+
+/// Show a prompt without entering raw mode and parse the
+/// value to another type.
+pub fn simple_parse<T, W, R, S>(
+    prefix: S,
+    writer: &mut W,
+    reader: &mut R,
+    options: &PromptOptions,
+) -> Result<T>
+where
+    T: std::str::FromStr,
+    <T as std::str::FromStr>::Err: Error + Sync + Send + 'static,
+    S: AsRef<str>,
+    W: Write,
+    R: BufRead,
+{
+    let value = simple_prompt(prefix, writer, reader, options)?;
+    Ok(value.parse::<T>()?)
+}
+
+/// Write the prefix and read a line without the line ending.
+fn read_line<W, R>(
+    prefix: &str,
+    writer: &mut W,
+    reader: &mut R,
+) -> Result<String>
+where
+    W: Write,
+    R: BufRead,
+{
+    writer.write_all(prefix.as_bytes())?;
+    writer.flush()?;
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(EndOfInput.into());
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(line)
+}
+
+/// Show a prompt until a required value is entered.
+fn required<W, F>(
+    prefix: &str,
+    writer: &mut W,
+    options: &PromptOptions,
+    read: &mut F,
+) -> Result<String>
+where
+    W: Write,
+    F: FnMut(&str, &mut W, &PromptOptions) -> Result<String>,
+{
     let value = if let Some(required) = &options.required {
         let mut value;
         let mut attempts = 0u16;
         loop {
-            value = validate(prefix.as_ref(), writer, options)?;
+            value = transform(prefix, writer, options, read)?;
             let check_value = if required.trim {
                 value.trim()
             } else {
@@ -91,46 +425,26 @@ where
         }
         value
     } else {
-        validate(prefix.as_ref(), writer, options)?
+        transform(prefix, writer, options, read)?
     };
 
     Ok(value)
 }
 
-/// Show a prompt and parse the value to another type.
-pub fn parse<'a, T, W, S: AsRef<str>>(
-    prefix: S,
-    writer: &'a mut W,
-    options: &PromptOptions,
-) -> Result<T>
-where
-    T: std::str::FromStr,
-    <T as std::str::FromStr>::Err: Error + Sync + Send + 'static,
-    W: Write,
-{
-    let value: String = prompt(prefix.as_ref(), writer, options)?;
-    let value: T = (&value[..]).parse::<T>()?;
-    Ok(value)
-}
-
-fn validate<'a, S: AsRef<str>, W>(
-    prefix: S,
-    writer: &'a mut W,
+/// Read a value and apply the transformer.
+///
+/// The value is validated by the prompt before it is returned.
+fn transform<W, F>(
+    prefix: &str,
+    writer: &mut W,
     options: &PromptOptions,
+    read: &mut F,
 ) -> Result<String>
 where
     W: Write,
+    F: FnMut(&str, &mut W, &PromptOptions) -> Result<String>,
 {
-    let mut value = if let Some(validation) = &options.validation {
-        let value = run(prefix.as_ref(), writer, options)?;
-        if (validation.validate)(&value) {
-            value
-        } else {
-            validate(prefix.as_ref(), writer, options)?
-        }
-    } else {
-        run(prefix.as_ref(), writer, options)?
-    };
+    let mut value = read(prefix, writer, options)?;
 
     if let Some(transformer) = &options.transformer {
         value = match (transformer.transform)(&value) {
@@ -142,9 +456,19 @@ where
     Ok(value)
 }
 
-fn run<'a, S: AsRef<str>, W>(
-    prefix: S,
-    writer: &'a mut W,
+/// How often to check for a cancelled prompt while waiting for input.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often to check for text to print above the prompt while
+/// waiting for input.
+const PRINT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long to wait for the next key of a key sequence by default.
+const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(500);
+
+fn run<W>(
+    prefix: &str,
+    writer: &mut W,
     options: &PromptOptions,
 ) -> Result<String>
 where
@@ -161,176 +485,1604 @@ where
     } else {
         None
     };
-    let mut buf = TerminalBuffer::new(prefix.as_ref(), echo);
+    let mut buf = TerminalBuffer::new(prefix, echo);
+    #[cfg(feature = "styling")]
+    buf.set_highlighter(options.highlighter.as_deref());
+    buf.set_placeholder(options.placeholder.as_deref());
+    buf.set_theme(options.theme);
+    buf.set_tab_style(options.tab_style);
+
+    let mask_after = options.password.as_ref().and_then(|p| p.mask_after);
+    let show_typed = options.password.as_ref().and_then(|p| p.show_typed);
+    let started = Instant::now();
+    let mut last_input = started;
+
+    #[cfg(feature = "history")]
+    let mut navigator = history::HistoryNavigator::new();
+
+    #[cfg(feature = "history")]
+    let mut inserted_token: Option<history::InsertedToken> = None;
+
+    #[cfg(feature = "history")]
+    if let Some(history) = &options.history {
+        let mut history = history.acquire();
+        history.set_namespace(options.history_namespace.clone());
+        history.set_widened(false);
+    }
+
+    #[cfg(any(feature = "completion", feature = "search"))]
+    let mut modes = mode::ModeStack::new();
 
+    #[cfg(feature = "debug")]
+    let mut debug_overlay = false;
+
+    let mut title = title::Title::default();
+    // Shape of the cursor last set for the mode
+    let mut cursor_style = None;
+    let mut recorder = macros::Recorder::default();
+    let mut argument = argument::Argument::default();
+    let mut undo = undo::UndoStack::default();
+    let mut frame = frame::Frame::default();
+    // Whether the last key typed characters, which are undone together
+    let mut typing = false;
+
+    // Set while a dangerous command waits to be confirmed,
+    // whether the line is held for the next prompt when accepted
     #[cfg(feature = "history")]
-    let mut history_buffer = String::new();
+    let mut confirming: Option<bool> = None;
+
+    // Event read while coalescing typed characters
+    let mut pending: Option<Event> = None;
+
+    // Keys typed so far of a key sequence
+    let mut keys: Vec<KeyEvent> = Vec::new();
+    // Keys of an incomplete sequence to handle again, the
+    // first of them is looked up on its own
+    let mut unmatched: VecDeque<KeyEvent> = VecDeque::new();
+    let mut alone = false;
+    let sequence_timeout = options.sequence_timeout.unwrap_or(SEQUENCE_TIMEOUT);
+    // Esc waiting for the next key
+    let mut escape = escape::Escape::new(options.escape_timeout);
+    // Whether the next key is inserted as a character
+    let mut quoted = false;
+    // Region last drawn highlighted
+    let mut drawn_region = None;
+    // Cursor when the value was last drawn for a highlighter
+    let mut drawn_cursor = 0;
+    // Password character last drawn as it was typed
+    let mut drawn_shown = None;
+
+    // The position is queried once and tracked by the buffer,
+    // when the terminal does not report it the prompt starts on
+    // a new line assumed to be the last row
+    let (width, height) = size()?;
+    buf.set_size((width, height));
+    let reports_position = match position(writer)? {
+        Some((_, row)) => {
+            buf.move_to_row(row);
+            true
+        }
+        None => {
+            writer.write_all(b"\r\n")?;
+            buf.move_to_row(height.saturating_sub(1));
+            false
+        }
+    };
+    buf.redraw(writer, buf.position())?;
 
-    // Write the initial prefix
-    buf.write_prefix(writer)?;
+    let initial = options
+        .held_line
+        .take()
+        .or_else(|| options.initial_value.clone());
+    if let Some(value) = initial {
+        buf.set_value(writer, &value)?;
+    }
+
+    #[cfg(feature = "history")]
+    get_next(writer, &mut buf, options, &mut navigator)?;
 
     'prompt: loop {
-        let (width, height) = size()?;
-        let (column, row) = cursor::position()?;
+        let (_, height) = buf.size();
+        undo.record(buf.buffer(), buf.cursor(), typing);
+
+        // Input that is already waiting is handled before drawing
+        // until the frame interval passes
+        let waiting = pending.is_some()
+            || !unmatched.is_empty()
+            || recorder.is_replaying()
+            || poll(Duration::ZERO)?;
+        let render = frame.is_due(Instant::now(), waiting);
+
+        // Moving the cursor while the mark is active changes the region
+        // and may change the spans of the highlighter
+        if render
+            && (buf.region() != drawn_region
+                || (buf.follows_cursor() && buf.cursor() != drawn_cursor)
+                || buf.shown() != drawn_shown)
+        {
+            drawn_region = buf.region();
+            drawn_cursor = buf.cursor();
+            drawn_shown = buf.shown();
+            buf.redraw(writer, buf.position())?;
+        }
+
+        if let Some(handler) = options.status.as_ref().filter(|_| render) {
+            let status = (handler)(&buf);
+            buf.set_status(writer, Some(status))?;
+        }
+
+        if let Some(styles) = &options.cursor_styles {
+            #[cfg(any(feature = "completion", feature = "search"))]
+            let mode_style = modes.cursor_style(styles);
+            #[cfg(not(any(feature = "completion", feature = "search")))]
+            let mode_style = None;
+            let style = match quoted {
+                true => styles.quoted,
+                false => mode_style,
+            }
+            .or(styles.edit)
+            .unwrap_or(CursorStyle::Default);
+            if cursor_style != Some(style) {
+                writer.queue(style)?;
+                cursor_style = Some(style);
+            }
+        }
+
+        if let Some(handler) = options.title.as_ref().filter(|_| render) {
+            title.update(writer, (handler)(buf.buffer()))?;
+        }
+
+        if let Some(printer) = &options.printer {
+            for text in printer.take() {
+                buf.print_above(writer, &text)?;
+            }
+        }
+
+        if let Some(reason) = expired(options, last_input, Instant::now()) {
+            abort(writer, &mut buf, options, reason)?;
+            return Err(Aborted(reason).into());
+        }
+
+        let mask_at = match mask_after {
+            Some(mask_after) if buf.is_revealed() => {
+                Some(last_input + mask_after)
+            }
+            _ => None,
+        };
+        let hide_at = show_typed
+            .filter(|_| buf.shown().is_some())
+            .map(|show_typed| last_input + show_typed);
+        let timeout_at = options.timeout.map(|timeout| last_input + timeout);
+        let cancel_at = options
+            .cancel
+            .as_ref()
+            .map(|_| Instant::now() + CANCEL_POLL_INTERVAL);
+        let print_at = options
+            .printer
+            .as_ref()
+            .map(|_| Instant::now() + PRINT_POLL_INTERVAL);
+        let sequence_at =
+            (!keys.is_empty()).then(|| last_input + sequence_timeout);
+        let escape_at = escape.deadline();
+        let deadline = [
+            mask_at,
+            hide_at,
+            timeout_at,
+            cancel_at,
+            print_at,
+            sequence_at,
+            escape_at,
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        // Write everything drawn for the last events at once
+        if render {
+            writer.flush()?;
+        }
 
-        buf.set_size((width, height));
-        buf.set_position((column, row));
+        let retried = pending.is_none() && !unmatched.is_empty();
+        let from_macro =
+            pending.is_none() && !retried && recorder.is_replaying();
+        let queued = pending
+            .take()
+            .or_else(|| unmatched.pop_front().map(Event::Key))
+            .or_else(|| recorder.next().map(Event::Key));
+        let from_terminal = queued.is_none();
+        let event = match queued
+            .map_or_else(|| next_event(deadline), |event| Ok(Some(event)))?
+        {
+            Some(event) => event,
+            None => {
+                let now = Instant::now();
+                if let Some(event) = escape.expire(now) {
+                    // Esc was typed on its own
+                    pending = Some(event);
+                    continue;
+                }
+                if sequence_at.is_some_and(|at| now >= at) {
+                    // Sequence was not completed in time
+                    for key in keys.drain(..).rev() {
+                        unmatched.push_front(key);
+                    }
+                    alone = true;
+                    continue;
+                }
+                if timeout_at.is_some_and(|at| now >= at) {
+                    // Aborted before the next key is read
+                    continue;
+                }
+                if mask_at.is_some_and(|at| now >= at) {
+                    // Inactivity timeout, mask the password again
+                    buf.set_revealed(false);
+                    buf.redraw(writer, buf.position())?;
+                }
+                if hide_at.is_some_and(|at| now >= at) {
+                    buf.set_shown(None);
+                }
+                continue;
+            }
+        };
+        last_input = Instant::now();
+
+        let event =
+            match escape.read(event, last_input, from_terminal && !quoted) {
+                Some((event, after)) => {
+                    pending = after;
+                    event
+                }
+                None => continue,
+            };
 
-        match read()? {
+        match event {
             Event::Key(event) => {
-                if let Some(actions) = options.bindings.first(&event) {
-                    for action in actions {
+                typing = false;
+                // Only the last character typed is shown
+                buf.set_shown(None);
+                if options.invalid_shown.take() {
+                    message::clear(writer, &buf)?;
+                }
+
+                // The key answers the confirmation, any key
+                // other than y declines
+                #[cfg(feature = "history")]
+                if let Some(hold) = confirming.take() {
+                    message::clear(writer, &buf)?;
+                    let confirmed =
+                        matches!(event.code, KeyCode::Char('y' | 'Y'));
+                    if confirmed
+                        && submit(writer, &mut buf, options, height, true)?
+                            == Submitted::Accepted
+                    {
+                        if hold {
+                            hold_line(options, &buf);
+                        }
+                        break 'prompt;
+                    }
+                    continue;
+                }
+
+                if !retried {
+                    recorder.record(event);
+                }
+
+                // Only a repeated key press cycles through tokens
+                #[cfg(feature = "history")]
+                let previous_token = inserted_token.take();
+
+                if quoted {
+                    quoted = false;
+                    if let Some(c) = literal(&event) {
+                        buf.write_char(writer, c)?;
+                    }
+                    continue;
+                }
+
+                #[cfg(any(feature = "completion", feature = "search"))]
+                if keys.is_empty()
+                    && modes.handle(
+                        &mut mode::ModeContext {
+                            writer,
+                            buf: &mut buf,
+                            options,
+                        },
+                        &event,
+                    )?
+                {
+                    continue;
+                }
+
+                keys.push(event);
+                let found = options.lookup(&keys, retried && alone);
+                if retried {
+                    alone = false;
+                }
+                let typed = keys.len();
+                match found {
+                    KeyMatch::Pending => continue,
+                    KeyMatch::Unbound if keys.len() > 1 => {
+                        // Handle the keys of the sequence on their own
+                        for key in keys.drain(..).rev() {
+                            unmatched.push_front(key);
+                        }
+                        alone = true;
+                        continue;
+                    }
+                    _ => keys.clear(),
+                }
+
+                if let KeyMatch::Actions(actions) = found {
+                    // Digits typed after a numeric argument extend it
+                    let actions = match actions[..] {
+                        [KeyAction::WriteChar(c @ '0'..='9')]
+                            if argument.is_active() =>
+                        {
+                            vec![KeyAction::DigitArgument(c as u8 - b'0')]
+                        }
+                        _ => actions,
+                    };
+                    let typing_argument = actions
+                        .iter()
+                        .any(|a| a.category() == CommandCategory::Argument);
+                    let count =
+                        if typing_argument { 1 } else { argument.take() };
+                    let actions = actions
+                        .into_iter()
+                        .flat_map(|action| {
+                            let count =
+                                if action.is_repeated() { count } else { 1 };
+                            std::iter::repeat_n(action, count)
+                        })
+                        .collect::<Vec<_>>();
+
+                    let single = actions.len() == 1;
+                    typing = !actions.is_empty()
+                        && actions
+                            .iter()
+                            .all(|a| matches!(a, KeyAction::WriteChar(_)));
+                    let mut actions = VecDeque::from(actions);
+                    while let Some(action) = actions.pop_front() {
+                        if !options.is_enabled(&action) {
+                            continue;
+                        }
+                        if let Some(hook) = &options.before_action {
+                            (hook)(action, &buf);
+                        }
+                        let mut outcome = CommandOutcome::Continue;
+                        // Whether the submitted line is held for the
+                        // next prompt
+                        let mut hold = false;
                         match action {
                             KeyAction::WriteChar(c) => {
-                                buf.write_char(writer, c)?;
+                                let mut text = String::from(c);
+                                // Copies made by a repeat count are
+                                // inserted with a single redraw
+                                take_chars(options, &mut actions, &mut text);
+                                if single
+                                    && unmatched.is_empty()
+                                    && !recorder.is_replaying()
+                                {
+                                    // Coalesce a flood of typed characters
+                                    // so they are drawn once
+                                    let mut typed = String::new();
+                                    pending = read_typed(options, &mut typed)?;
+                                    recorder.record_chars(&typed);
+                                    text.push_str(&typed);
+                                }
+                                buf.write_str(writer, &text)?;
+                                if show_typed.is_some() && buf.is_masked() {
+                                    buf.set_shown(buf.cursor().checked_sub(1));
+                                }
                             }
-                            KeyAction::SubmitLine => {
-                                if let Some(multiline) = &options.multiline {
-                                    buf.push(writer, '\n')?;
-                                    writer
-                                        .execute(cursor::MoveTo(0, row + 1))?;
-                                    if multiline.repeat_prompt {
-                                        buf.write_prefix(writer)?;
-                                    } else {
-                                        writer.execute(Clear(
-                                            ClearType::CurrentLine,
-                                        ))?;
-                                    }
+                            KeyAction::AcceptAndHold => {
+                                outcome = CommandOutcome::Submit;
+                                hold = true;
+                            }
+                            KeyAction::QuotedInsert => {
+                                quoted = true;
+                            }
+                            KeyAction::Undo | KeyAction::Redo => {
+                                let snapshot = if action == KeyAction::Undo {
+                                    undo.undo()
                                 } else {
-                                    #[cfg(feature = "history")]
-                                    if let Some(history) = &options.history {
-                                        let mut writer =
-                                            history.lock().unwrap();
-                                        writer.push(buf.buffer().to_string());
-                                    }
+                                    undo.redo()
+                                };
+                                if let Some(snapshot) = snapshot {
+                                    buf.replace(
+                                        writer,
+                                        snapshot.line,
+                                        snapshot.cursor,
+                                    )?;
+                                }
+                            }
+                            KeyAction::DigitArgument(digit) => {
+                                argument.digit(digit);
+                            }
+                            KeyAction::UniversalArgument => {
+                                argument.universal();
+                            }
+                            KeyAction::StartMacro => recorder.start(),
+                            KeyAction::EndMacro => {
+                                recorder.end(&options.last_macro, typed);
+                            }
+                            KeyAction::CallLastMacro => {
+                                // A macro may not call itself
+                                if !from_macro {
+                                    recorder.call(&options.last_macro, typed);
+                                }
+                            }
+                            // Handled by the active mode, there is
+                            // nothing to select without one
+                            KeyAction::SelectNext
+                            | KeyAction::SelectPrevious
+                            | KeyAction::AcceptSelection
+                            | KeyAction::CancelMode => {}
+                            KeyAction::EditAndExecuteCommand => {
+                                // Never write a password to a file
+                                if options.password.is_none() {
+                                    buf.move_to_end(writer)?;
+                                    writer.write_all(b"\r\n")?;
+                                    writer.flush()?;
 
-                                    if row == height - 1 {
-                                        write!(writer, "{}", '\n')?;
-                                        writer
-                                            .execute(cursor::MoveTo(0, row))?;
-                                    } else {
-                                        writer.execute(
-                                            cursor::MoveToNextLine(1),
-                                        )?;
+                                    let edited = external::edit(buf.buffer());
+                                    buf.set_size(size()?);
+                                    let row = match reports_position {
+                                        true => position(writer)?,
+                                        false => None,
+                                    }
+                                    .map_or(height.saturating_sub(1), |p| p.1);
+                                    buf.move_to_row(row);
+                                    let line = match &edited {
+                                        Ok(Some(line)) => line.clone(),
+                                        _ => buf.buffer().to_string(),
+                                    };
+                                    buf.set_value(writer, &line)?;
+                                    match edited {
+                                        Ok(Some(_)) => {
+                                            outcome = CommandOutcome::Submit;
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message::show(writer, &mut buf, e)?;
+                                        }
                                     }
-
-                                    break 'prompt;
                                 }
                             }
-                            KeyAction::MoveCursorLeft => {
-                                if column as usize > buf.prefix_columns() {
-                                    writer.execute(cursor::MoveTo(
-                                        column - 1,
-                                        row,
-                                    ))?;
+                            KeyAction::TogglePasswordReveal => {
+                                if options.password.is_some() {
+                                    buf.set_revealed(!buf.is_revealed());
+                                    buf.redraw(writer, buf.position())?;
                                 }
                             }
-                            KeyAction::MoveCursorRight => {
-                                let position = buf.end_pos(buf.buffer());
-
-                                if column < position.0 {
-                                    writer.execute(cursor::MoveTo(
-                                        column + 1,
-                                        row,
-                                    ))?;
+                            #[cfg(feature = "completion")]
+                            KeyAction::Complete => {
+                                if let Some(completion) = &options.completion {
+                                    if let Some(menu) = completion::complete(
+                                        writer, &mut buf, completion,
+                                    )? {
+                                        modes.push(
+                                            &mut mode::ModeContext {
+                                                writer,
+                                                buf: &mut buf,
+                                                options,
+                                            },
+                                            Box::new(menu),
+                                        )?;
+                                    }
                                 }
                             }
-                            KeyAction::EraseCharacter => {
-                                buf.erase_before(writer, 1)?;
+                            #[cfg(feature = "debug")]
+                            KeyAction::ToggleDebug => {
+                                debug_overlay = !debug_overlay;
+                                if !debug_overlay {
+                                    message::clear(writer, &buf)?;
+                                }
                             }
-                            KeyAction::AbortPrompt => {
-                                writer.execute(cursor::MoveToNextLine(1))?;
-                                break 'prompt;
+                            #[cfg(feature = "history")]
+                            KeyAction::HistoryPrevious => {
+                                navigate(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    &mut navigator,
+                                    history::Match::Any,
+                                    true,
+                                )?;
                             }
-                            KeyAction::ClearScreen => {
-                                writer.queue(Clear(ClearType::All))?;
-                                writer.queue(cursor::MoveTo(0, 0))?;
-                                buf.write_prefix(writer)?;
+                            #[cfg(feature = "history")]
+                            KeyAction::HistoryNext => {
+                                navigate(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    &mut navigator,
+                                    history::Match::Any,
+                                    false,
+                                )?;
                             }
-                            KeyAction::MoveToLineBegin => {
-                                writer.execute(cursor::MoveTo(
-                                    buf.prefix_columns().try_into()?,
-                                    row,
-                                ))?;
+                            #[cfg(feature = "history")]
+                            KeyAction::HistoryFirst
+                            | KeyAction::HistoryLast => {
+                                jump(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    &mut navigator,
+                                    action == KeyAction::HistoryFirst,
+                                )?;
                             }
-                            KeyAction::MoveToLineEnd => {
-                                let position = buf.end_pos(buf.buffer());
-                                writer
-                                    .execute(cursor::MoveTo(position.0, row))?;
+                            #[cfg(feature = "history")]
+                            KeyAction::HistorySearchBackward => {
+                                navigate(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    &mut navigator,
+                                    history::Match::Prefix,
+                                    true,
+                                )?;
                             }
-                            KeyAction::EraseToLineBegin => {
-                                if (column as usize) > buf.prefix_columns() {
-                                    let amount =
-                                        column as usize - buf.prefix_columns();
-                                    buf.erase_before(writer, amount as usize)?;
-                                }
+                            #[cfg(feature = "history")]
+                            KeyAction::HistorySearchForward => {
+                                navigate(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    &mut navigator,
+                                    history::Match::Prefix,
+                                    false,
+                                )?;
+                            }
+                            #[cfg(feature = "history")]
+                            KeyAction::HistorySubstringSearchBackward => {
+                                navigate(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    &mut navigator,
+                                    history::Match::Substring,
+                                    true,
+                                )?;
+                            }
+                            #[cfg(feature = "history")]
+                            KeyAction::HistorySubstringSearchForward => {
+                                navigate(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    &mut navigator,
+                                    history::Match::Substring,
+                                    false,
+                                )?;
+                            }
+                            #[cfg(feature = "history")]
+                            KeyAction::InsertHistoryToken(index) => {
+                                inserted_token = insert_history_token(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    Some(index),
+                                    previous_token.as_ref(),
+                                )?;
                             }
-                            KeyAction::EraseToLineEnd => {
-                                if (column as usize) < buf.columns() {
-                                    let amount =
-                                        buf.columns() - (column as usize);
-                                    buf.erase_after(writer, amount as usize)?;
+                            #[cfg(feature = "history")]
+                            KeyAction::OperateAndGetNext => {
+                                if let Some(history) = &options.history {
+                                    let next =
+                                        following(&mut *history.acquire());
+                                    *options.next_line.borrow_mut() = next;
                                 }
+                                outcome = CommandOutcome::Submit;
                             }
-                            KeyAction::ErasePreviousWord => {
-                                buf.erase_word_before(writer)?;
+                            #[cfg(feature = "history")]
+                            KeyAction::YankLastArg => {
+                                inserted_token = insert_history_token(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    None,
+                                    previous_token.as_ref(),
+                                )?;
+                            }
+                            #[cfg(feature = "search")]
+                            KeyAction::ReverseSearch
+                            | KeyAction::ForwardSearch => {
+                                if options.history.is_some() {
+                                    modes.push(
+                                        &mut mode::ModeContext {
+                                            writer,
+                                            buf: &mut buf,
+                                            options,
+                                        },
+                                        Box::new(search::Search::new(
+                                            matches!(
+                                                action,
+                                                KeyAction::ReverseSearch
+                                            ),
+                                        )),
+                                    )?;
+                                }
                             }
                             #[cfg(feature = "history")]
-                            KeyAction::HistoryPrevious => {
+                            KeyAction::ToggleHistoryScope => {
                                 if let Some(history) = &options.history {
-                                    let mut history = history.lock().unwrap();
-
-                                    if history.is_last() {
-                                        history_buffer =
-                                            buf.buffer().to_string();
-                                    }
+                                    let mut history = history.acquire();
+                                    let widened = history.is_widened();
+                                    history.set_widened(!widened);
+                                }
+                            }
+                            action => {
+                                outcome =
+                                    execute(writer, &mut buf, options, action)?;
+                            }
+                        }
 
-                                    if let Some(history_line) =
-                                        history.previous()
-                                    {
-                                        let position =
-                                            buf.end_pos(&history_line);
+                        if let Some(hook) = &options.after_action {
+                            (hook)(action, &buf);
+                        }
 
-                                        buf.refresh(
-                                            writer,
-                                            history_line,
-                                            position,
-                                        )?;
+                        match outcome {
+                            CommandOutcome::Continue => {}
+                            CommandOutcome::Beep => {
+                                writer.write_all(b"\x07")?;
+                            }
+                            CommandOutcome::Submit => {
+                                if let Some(multiline) = &options.multiline {
+                                    buf.move_to_end(writer)?;
+                                    buf.write_str(writer, "\n")?;
+                                    if multiline.repeat_prompt {
+                                        buf.write_prefix(writer)?;
+                                    }
+                                } else {
+                                    match submit(
+                                        writer, &mut buf, options, height,
+                                        false,
+                                    )? {
+                                        Submitted::Accepted => {
+                                            if hold {
+                                                hold_line(options, &buf);
+                                            }
+                                            break 'prompt;
+                                        }
+                                        Submitted::Rejected => {}
+                                        #[cfg(feature = "history")]
+                                        Submitted::Confirm => {
+                                            confirming = Some(hold);
+                                        }
                                     }
+                                    continue 'prompt;
                                 }
                             }
-                            #[cfg(feature = "history")]
-                            KeyAction::HistoryNext => {
+                            CommandOutcome::Abort(AbortReason::EndOfFile)
+                                if options.multiline.is_some() =>
+                            {
+                                // Multiline input is finished by Ctrl+d,
+                                // store all of its lines as one entry
+                                #[cfg(feature = "history")]
                                 if let Some(history) = &options.history {
-                                    let mut history = history.lock().unwrap();
-                                    if let Some(history_line) = history.next() {
-                                        let position =
-                                            buf.end_pos(&history_line);
-                                        buf.refresh(
-                                            writer,
-                                            history_line,
-                                            position,
-                                        )?;
-                                    } else {
-                                        let position =
-                                            buf.end_pos(&history_buffer);
-
-                                        buf.refresh(
-                                            writer,
-                                            &history_buffer,
-                                            position,
-                                        )?;
+                                    let value = buf.buffer().trim_end();
+                                    if !value.is_empty() {
+                                        history
+                                            .acquire()
+                                            .push(value.to_string());
                                     }
                                 }
+                                abort(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    AbortReason::EndOfFile,
+                                )?;
+                                break 'prompt;
+                            }
+                            CommandOutcome::Abort(reason) => {
+                                abort(writer, &mut buf, options, reason)?;
+                                return Err(Aborted(reason).into());
+                            }
+                            CommandOutcome::Eof => {
+                                abort(
+                                    writer,
+                                    &mut buf,
+                                    options,
+                                    AbortReason::EndOfFile,
+                                )?;
+                                return Err(EndOfInput.into());
                             }
                         }
                     }
                 }
             }
             Event::Mouse(_event) => {}
-            Event::Resize(_width, _height) => {}
+            Event::Resize(width, height) => {
+                // The terminal may have moved the cursor as it reflowed
+                let reported = match reports_position {
+                    true => position(writer)?,
+                    false => None,
+                };
+                let (column, row) = buf.position();
+                let position = reported.unwrap_or((
+                    column.min(width.saturating_sub(1)),
+                    row.min(height.saturating_sub(1)),
+                ));
+                buf.resize(writer, (width, height), position)?;
+            }
+        }
+
+        #[cfg(feature = "debug")]
+        if debug_overlay {
+            let overlay = debug::overlay(options, &buf);
+            message::show(writer, &mut buf, overlay)?;
         }
     }
 
     Ok(buf.into())
 }
+
+/// Run an action that edits the line or ends the prompt,
+/// returning what the prompt does next.
+///
+/// Actions that depend on the state of the prompt, such as undo,
+/// numeric arguments or history navigation, are run by [run].
+fn execute<W>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer<'_>,
+    options: &PromptOptions,
+    action: KeyAction,
+) -> Result<CommandOutcome>
+where
+    W: Write,
+{
+    let mut outcome = CommandOutcome::Continue;
+    match action {
+        KeyAction::SubmitLine => {
+            outcome = CommandOutcome::Submit;
+        }
+        KeyAction::MoveCursorLeft => {
+            let cursor = buf.cursor();
+            if cursor > 0 {
+                buf.move_to(writer, cursor - 1)?;
+            }
+        }
+        KeyAction::MoveCursorRight => {
+            let cursor = buf.cursor();
+            if cursor < buf.len() {
+                buf.move_to(writer, cursor + 1)?;
+            }
+        }
+        KeyAction::ForwardWord => {
+            let index = buf.next_word_end();
+            buf.move_to(writer, index)?;
+        }
+        KeyAction::BackwardWord => {
+            let index = buf.previous_word_start();
+            buf.move_to(writer, index)?;
+        }
+        KeyAction::MoveToColumn(index) => {
+            buf.move_to(writer, index)?;
+        }
+        KeyAction::EraseCharacter => {
+            buf.erase_before(writer, 1)?;
+        }
+        KeyAction::EraseNextCharacter => {
+            buf.erase_after(writer, 1)?;
+        }
+        KeyAction::EraseNextCharacterOrEndOfFile => {
+            if buf.is_empty() {
+                outcome = CommandOutcome::Eof;
+            } else if options.multiline.is_some() {
+                outcome = CommandOutcome::Abort(AbortReason::EndOfFile);
+            } else {
+                buf.erase_after(writer, 1)?;
+            }
+        }
+        KeyAction::AbortPrompt(reason) => {
+            outcome = if reason == AbortReason::EndOfFile && buf.is_empty() {
+                CommandOutcome::Eof
+            } else {
+                CommandOutcome::Abort(reason)
+            };
+        }
+        KeyAction::ClearScreen => {
+            writer.queue(Clear(ClearType::All))?;
+            buf.move_to_row(0);
+            buf.redraw(writer, buf.position())?;
+        }
+        KeyAction::MoveToLineBegin => {
+            let index = buf.line_start();
+            buf.move_to(writer, index)?;
+        }
+        KeyAction::MoveToLineEnd => {
+            let index = buf.line_end();
+            buf.move_to(writer, index)?;
+        }
+        KeyAction::EraseToLineBegin => {
+            buf.kill_before(writer)?;
+        }
+        KeyAction::EraseToLineEnd => {
+            buf.kill_after(writer)?;
+        }
+        KeyAction::EraseLine => {
+            buf.kill_line(writer)?;
+        }
+        KeyAction::ErasePreviousWord => {
+            buf.erase_word_before(writer)?;
+        }
+        KeyAction::EraseNextWord => {
+            buf.erase_word_after(writer)?;
+        }
+        KeyAction::UpcaseWord => {
+            buf.change_word_case(writer, WordCase::Upper)?;
+        }
+        KeyAction::DowncaseWord => {
+            buf.change_word_case(writer, WordCase::Lower)?;
+        }
+        KeyAction::CapitalizeWord => {
+            buf.change_word_case(writer, WordCase::Capital)?;
+        }
+        KeyAction::Yank => {
+            buf.yank(writer)?;
+        }
+        KeyAction::SetMark => buf.set_mark(),
+        KeyAction::ExchangePointAndMark => {
+            buf.exchange_point_and_mark(writer)?;
+        }
+        KeyAction::KillRegion => {
+            buf.kill_region(writer)?;
+        }
+        KeyAction::CopyRegion => {
+            buf.copy_region(writer)?;
+        }
+        KeyAction::CopyToClipboard => {
+            // Passwords are never copied
+            if options.password.is_none() {
+                let text = match buf.region() {
+                    Some((start, end)) => buf.text(start, end),
+                    None => buf.buffer().to_string(),
+                };
+                match &options.clipboard {
+                    Some(handler) => (handler)(&text),
+                    None => clipboard::copy(writer, &text)?,
+                }
+            }
+        }
+        KeyAction::Command(_) | KeyAction::Help => {
+            let mut editor = Editor::new(buf.buffer(), buf.cursor(), options);
+            outcome = options.run_command(action, &mut editor);
+            let (line, cursor) = editor.into_parts();
+            if line != buf.buffer() || cursor != buf.cursor() {
+                buf.replace(writer, line, cursor)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(outcome)
+}
+
+/// Result of submitting the line.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Submitted {
+    /// The line was accepted.
+    Accepted,
+    /// The line was not accepted and the prompt stays active.
+    Rejected,
+    /// The line repeats a dangerous command and is accepted
+    /// when the next key is `y` or `Y`.
+    #[cfg(feature = "history")]
+    Confirm,
+}
+
+/// Check and record the line when it is submitted, moving
+/// past the prompt.
+///
+/// When the line is not accepted the prompt stays active,
+/// `confirmed` is set once a dangerous command was confirmed.
+#[cfg_attr(not(feature = "history"), allow(unused_variables))]
+fn submit<W>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer<'_>,
+    options: &PromptOptions,
+    height: u16,
+    confirmed: bool,
+) -> Result<Submitted>
+where
+    W: Write,
+{
+    if let Some(window) = options.debounce_submit {
+        let last_submit = options.last_submit.get();
+        if last_submit.is_some_and(|at| at.elapsed() < window) {
+            return Ok(Submitted::Rejected);
+        }
+    }
+
+    #[cfg(feature = "history")]
+    if let (Some(history), Some(expansion)) =
+        (&options.history, &options.history_expansion)
+    {
+        let expanded = history::expand(buf.buffer(), &*history.acquire());
+        match expanded {
+            Ok(expanded) => {
+                message::clear(writer, buf)?;
+                if let Some(line) = expanded {
+                    buf.set_value(writer, &line)?;
+                    if expansion.confirm {
+                        return Ok(Submitted::Rejected);
+                    }
+                }
+            }
+            Err(e) => {
+                message::show(writer, buf, e)?;
+                return Ok(Submitted::Rejected);
+            }
+        }
+    }
+
+    if let Some(validation) = &options.validation {
+        if let Err(message) = (validation.validate)(buf.buffer()) {
+            if message.is_empty() {
+                writer.write_all(b"\x07")?;
+            } else {
+                message::show(writer, buf, message)?;
+                options.invalid_shown.set(true);
+            }
+            return Ok(Submitted::Rejected);
+        }
+    }
+
+    if let Some(on_accept) = &options.on_accept {
+        match (on_accept)(buf.buffer()) {
+            AcceptOutcome::Accept => {
+                message::clear(writer, buf)?;
+            }
+            AcceptOutcome::Replace(value) => {
+                message::clear(writer, buf)?;
+                buf.set_value(writer, &value)?;
+            }
+            AcceptOutcome::Preview(value) => {
+                buf.set_value(writer, &value)?;
+                return Ok(Submitted::Rejected);
+            }
+            AcceptOutcome::Reject(reason) => {
+                message::show(writer, buf, reason)?;
+                return Ok(Submitted::Rejected);
+            }
+        }
+    }
+
+    #[cfg(feature = "history")]
+    if let (Some(history), Some(warning)) =
+        (&options.history, &options.history_warning)
+    {
+        let line = buf.buffer();
+        let repeated = history.acquire().iter().any(|item| item.value == line);
+        if repeated && !confirmed && (warning.dangerous)(line) {
+            message::show(writer, buf, &warning.message)?;
+            return Ok(Submitted::Confirm);
+        }
+    }
+
+    #[cfg(feature = "history")]
+    if let Some(history) = &options.history {
+        if options.is_recorded(buf.buffer()) {
+            let mut writer = history.acquire();
+            writer.push(buf.buffer().to_string());
+        }
+    }
+
+    options.last_submit.set(Some(Instant::now()));
+
+    // Move below the last row of the value
+    let (_, row) = match &options.transient_prompt {
+        Some(transient) => buf.collapse(writer, &transient.prefix)?,
+        None => {
+            buf.move_to_end(writer)?;
+            buf.position()
+        }
+    };
+    next_line(writer, row, height)?;
+
+    Ok(Submitted::Accepted)
+}
+
+/// Hold the accepted line for the next prompt.
+fn hold_line(options: &PromptOptions, buf: &TerminalBuffer<'_>) {
+    *options.held_line.borrow_mut() = Some(buf.buffer().to_string());
+}
+
+/// Move to the start of the row below the prompt, scrolling the
+/// terminal when the prompt ends on its last row.
+fn next_line<W>(writer: &mut W, row: u16, height: u16) -> Result<()>
+where
+    W: Write,
+{
+    if row >= height.saturating_sub(1) {
+        writeln!(writer)?;
+        writer.execute(cursor::MoveTo(0, row))?;
+    } else {
+        writer.execute(cursor::MoveToNextLine(1))?;
+    }
+    Ok(())
+}
+
+/// Move past the prompt and notify the abort handler.
+fn abort<W>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer<'_>,
+    options: &PromptOptions,
+    reason: AbortReason,
+) -> Result<()>
+where
+    W: Write,
+{
+    buf.move_to_end(writer)?;
+    next_line(writer, buf.position().1, buf.size().1)?;
+    if let Some(on_abort) = &options.on_abort {
+        (on_abort)(reason);
+    }
+    Ok(())
+}
+
+/// Reason to abort the prompt without a key press, when the
+/// token is cancelled or there was no input before the timeout.
+fn expired(
+    options: &PromptOptions,
+    last_input: Instant,
+    now: Instant,
+) -> Option<AbortReason> {
+    if options.cancel.as_ref().is_some_and(|t| t.is_cancelled()) {
+        Some(AbortReason::Cancelled)
+    } else if options.timeout.is_some_and(|t| now >= last_input + t) {
+        Some(AbortReason::Timeout)
+    } else {
+        None
+    }
+}
+
+/// Move through the history showing the entry in the prompt.
+#[cfg(feature = "history")]
+fn navigate<W>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer<'_>,
+    options: &PromptOptions,
+    navigator: &mut history::HistoryNavigator,
+    kind: history::Match,
+    backward: bool,
+) -> Result<()>
+where
+    W: Write,
+{
+    if let Some(history) = &options.history {
+        let mut history = history.acquire();
+        let (line, cursor) = (buf.buffer(), buf.cursor());
+        let draft = if backward {
+            navigator.previous(&mut *history, kind, line, cursor)
+        } else {
+            navigator.next(&mut *history, kind, line, cursor)
+        };
+        if let Some(draft) = draft {
+            buf.replace(writer, draft.line, draft.cursor)?;
+        }
+    }
+    Ok(())
+}
+
+/// Move to the oldest history entry or back to the line
+/// being edited.
+#[cfg(feature = "history")]
+fn jump<W>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer<'_>,
+    options: &PromptOptions,
+    navigator: &mut history::HistoryNavigator,
+    oldest: bool,
+) -> Result<()>
+where
+    W: Write,
+{
+    if let Some(history) = &options.history {
+        let mut history = history.acquire();
+        let (line, cursor) = (buf.buffer(), buf.cursor());
+        let draft = if oldest {
+            navigator.first(&mut *history, line, cursor)
+        } else {
+            navigator.last(&mut *history, line, cursor)
+        };
+        if let Some(draft) = draft {
+            buf.replace(writer, draft.line, draft.cursor)?;
+        }
+    }
+    Ok(())
+}
+
+/// History entry after the one being shown and how many
+/// entries it is before the newest.
+#[cfg(feature = "history")]
+fn following(history: &mut dyn history::History) -> Option<(String, usize)> {
+    if history.is_last() {
+        return None;
+    }
+    let next = history.next()?.into_owned();
+    let mut offset = 1;
+    while history.next().is_some() {
+        offset += 1;
+    }
+    Some((next, offset))
+}
+
+/// Show the history entry stored by [KeyAction::OperateAndGetNext].
+///
+/// The submitted line may have been added to the history, so the
+/// entry is one further back when it is not at its offset.
+#[cfg(feature = "history")]
+fn get_next<W>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer<'_>,
+    options: &PromptOptions,
+    navigator: &mut history::HistoryNavigator,
+) -> Result<()>
+where
+    W: Write,
+{
+    let (Some(history), Some((line, offset))) =
+        (&options.history, options.next_line.take())
+    else {
+        return Ok(());
+    };
+    let mut history = history.acquire();
+    let mut previous = || {
+        navigator.previous(
+            &mut *history,
+            history::Match::Any,
+            buf.buffer(),
+            buf.cursor(),
+        )
+    };
+    let mut draft = None;
+    for _ in 0..offset {
+        draft = previous().or(draft);
+    }
+    if draft.as_ref().is_some_and(|d| d.line != line) {
+        draft = previous().or(draft);
+    }
+    if let Some(draft) = draft {
+        buf.replace(writer, draft.line, draft.cursor)?;
+    }
+    Ok(())
+}
+
+/// Insert a token extracted from the history at the cursor
+/// or replace the token inserted by the previous key press
+/// with one from an older entry.
+///
+/// Without an extractor the last argument of the entry is used.
+#[cfg(feature = "history")]
+fn insert_history_token<W>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer<'_>,
+    options: &PromptOptions,
+    extractor: Option<usize>,
+    previous: Option<&history::InsertedToken>,
+) -> Result<Option<history::InsertedToken>>
+where
+    W: Write,
+{
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let extract = match extractor {
+        Some(index) => match options.history_tokens.get(index) {
+            Some((_, extract)) => extract.as_ref(),
+            None => return Ok(None),
+        },
+        None => &history::last_argument,
+    };
+    let Some(history) = &options.history else {
+        return Ok(None);
+    };
+    let history = history.acquire();
+
+    let (offset, start, len) = match previous {
+        Some(token) if token.extractor == extractor => {
+            (token.offset + 1, token.start, token.len)
+        }
+        _ => (0, buf.cursor(), 0),
+    };
+
+    let found = history
+        .iter()
+        .rev()
+        .enumerate()
+        .skip(offset)
+        .find_map(|(offset, entry)| (extract)(&entry).map(|t| (offset, t)));
+    let Some((offset, token)) = found else {
+        // Nothing older, keep the current token
+        return Ok(previous.copied());
+    };
+
+    let graphemes: Vec<&str> = buf.buffer().graphemes(true).collect();
+    let token_len = token.graphemes(true).count();
+    let mut line = graphemes[..start].concat();
+    line.push_str(&token);
+    line.push_str(&graphemes[start + len..].concat());
+    buf.replace(writer, line, start + token_len)?;
+
+    Ok(Some(history::InsertedToken {
+        extractor,
+        offset,
+        start,
+        len: token_len,
+    }))
+}
+
+/// Character typed by a key inserted literally.
+fn literal(event: &KeyEvent) -> Option<char> {
+    let c = match event.code {
+        KeyCode::Char(c) if event.modifiers.contains(KeyModifiers::CONTROL) => {
+            match c {
+                '@' | ' ' => '\0',
+                'a'..='z' => (c as u8 - b'a' + 1) as char,
+                '[' | '3' => '\x1b',
+                '\\' | '4' => '\x1c',
+                ']' | '5' => '\x1d',
+                '^' | '6' => '\x1e',
+                '_' | '7' => '\x1f',
+                '?' | '8' => '\x7f',
+                _ => return None,
+            }
+        }
+        KeyCode::Char(c) => c,
+        KeyCode::Tab => '\t',
+        KeyCode::Enter => '\r',
+        KeyCode::Esc => '\x1b',
+        KeyCode::Backspace => '\x7f',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Take the characters written by the actions at the front
+/// of the queue, appending them to `text`.
+fn take_chars(
+    options: &PromptOptions,
+    actions: &mut VecDeque<KeyAction>,
+    text: &mut String,
+) {
+    while let Some(&action @ KeyAction::WriteChar(c)) = actions.front() {
+        if !options.is_enabled(&action) {
+            break;
+        }
+        text.push(c);
+        actions.pop_front();
+    }
+}
+
+/// Read the key events that are already available while they
+/// write a character, appending the characters to `text`.
+///
+/// Returns the first available event that does not write
+/// a character.
+fn read_typed(
+    options: &PromptOptions,
+    text: &mut String,
+) -> Result<Option<Event>> {
+    while poll(Duration::ZERO)? {
+        let event = read()?;
+        if let Event::Key(key) = &event {
+            if let KeyMatch::Actions(actions) = options.lookup(&[*key], false) {
+                if let [action @ KeyAction::WriteChar(c)] = actions[..] {
+                    if options.is_enabled(&action) {
+                        text.push(c);
+                        continue;
+                    }
+                }
+            }
+        }
+        return Ok(Some(event));
+    }
+    Ok(None)
+}
+
+/// Cursor position reported by the terminal once the queued
+/// output is written, `None` when the terminal does not
+/// answer the query.
+fn position<W: Write>(writer: &mut W) -> Result<Option<(u16, u16)>> {
+    writer.flush()?;
+    Ok(cursor::position().ok())
+}
+
+/// Wait for the next event.
+///
+/// When a deadline is given and it passes before an event
+/// is available `None` is returned.
+fn next_event(deadline: Option<Instant>) -> Result<Option<Event>> {
+    if let Some(deadline) = deadline {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if !poll(timeout)? {
+            return Ok(None);
+        }
+    }
+    Ok(Some(read()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    #[test]
+    fn simple_prompt_options() -> Result<()> {
+        let options = PromptOptions::new()
+            .required(Required {
+                trim: true,
+                max_attempts: 0,
+            })
+            .validation(Validation {
+                validate: Box::new(|value| match value {
+                    "no" => Err("try again".to_string()),
+                    _ => Ok(()),
+                }),
+            })
+            .transformer(Transformer {
+                transform: Box::new(|value| Cow::Owned(value.to_uppercase())),
+            });
+        let mut writer = Vec::new();
+        let mut reader = Cursor::new("  \r\nno\nyes\n");
+        let value = simple_prompt("> ", &mut writer, &mut reader, &options)?;
+        assert_eq!("YES", value);
+        assert_eq!("> > try again\n> ", String::from_utf8(writer)?);
+        Ok(())
+    }
+
+    /// Buffer drawn at the top of an 80 by 24 terminal.
+    fn buffer(value: &str) -> TerminalBuffer<'static> {
+        let mut buf = TerminalBuffer::new("> ", None);
+        buf.set_size((80, 24));
+        buf.move_to_row(0);
+        buf.set_value(&mut Vec::new(), value).unwrap();
+        buf
+    }
+
+    #[test]
+    fn execute_outcome() -> Result<()> {
+        let options = PromptOptions::new()
+            .command(KeyEvent::from(KeyCode::F(2)), |editor| {
+                editor.set_line("done");
+                CommandOutcome::Submit
+            })
+            .help(|_| CommandOutcome::Beep);
+        let mut out = Vec::new();
+        let mut run = |buf: &mut TerminalBuffer<'_>, action| {
+            execute(&mut out, buf, &options, action)
+        };
+
+        let mut buf = buffer("ab");
+        assert_eq!(
+            CommandOutcome::Submit,
+            run(&mut buf, KeyAction::SubmitLine)?
+        );
+        assert_eq!(
+            CommandOutcome::Abort(AbortReason::Interrupt),
+            run(&mut buf, KeyAction::AbortPrompt(AbortReason::Interrupt))?
+        );
+        assert_eq!(
+            CommandOutcome::Abort(AbortReason::EndOfFile),
+            run(&mut buf, KeyAction::AbortPrompt(AbortReason::EndOfFile))?
+        );
+
+        // Ctrl+d erases until the line is empty and then ends the input
+        buf.move_to(&mut Vec::new(), 0)?;
+        let eof = KeyAction::EraseNextCharacterOrEndOfFile;
+        assert_eq!(CommandOutcome::Continue, run(&mut buf, eof)?);
+        assert_eq!("b", buf.buffer());
+        assert_eq!(CommandOutcome::Continue, run(&mut buf, eof)?);
+        assert_eq!(CommandOutcome::Eof, run(&mut buf, eof)?);
+
+        // Commands change the line before their outcome is handled
+        assert_eq!(
+            CommandOutcome::Submit,
+            run(&mut buf, KeyAction::Command(0))?
+        );
+        assert_eq!(("done", 4), (buf.buffer(), buf.cursor()));
+        assert_eq!(CommandOutcome::Beep, run(&mut buf, KeyAction::Help)?);
+
+        // Actions run by the prompt are left unchanged
+        assert_eq!(CommandOutcome::Continue, run(&mut buf, KeyAction::Undo)?);
+        assert_eq!(("done", 4), (buf.buffer(), buf.cursor()));
+        Ok(())
+    }
+
+    #[test]
+    fn execute_move_to_column() -> Result<()> {
+        let options = PromptOptions::new();
+        let mut out = Vec::new();
+        let mut buf = buffer("a日e\u{301}z");
+        execute(&mut out, &mut buf, &options, KeyAction::MoveToColumn(2))?;
+        assert_eq!(2, buf.cursor());
+        assert_eq!((5, 0), buf.position());
+
+        // An index past the end moves to the end of the line
+        execute(&mut out, &mut buf, &options, KeyAction::MoveToColumn(9))?;
+        assert_eq!(4, buf.cursor());
+        assert_eq!((7, 0), buf.position());
+
+        execute(&mut out, &mut buf, &options, KeyAction::MoveToColumn(0))?;
+        assert_eq!((0, (2, 0)), (buf.cursor(), buf.position()));
+        assert_eq!("a日e\u{301}z", buf.buffer());
+        Ok(())
+    }
+
+    #[cfg(feature = "shell")]
+    #[test]
+    fn carried_to_new_options() {
+        let options = PromptOptions::new();
+        *options.held_line.borrow_mut() = Some("held".to_string());
+        *options.next_line.borrow_mut() = Some(("make".to_string(), 2));
+        let mut carried = Carried::default();
+        carried.take(&options);
+        assert!(options.next_line.borrow().is_none());
+
+        // Options created for the next line
+        let options = PromptOptions::new();
+        carried.restore(&options);
+        assert_eq!(Some("held".to_string()), options.held_line.take());
+        assert_eq!(Some(("make".to_string(), 2)), options.next_line.take());
+
+        carried.take(&options);
+        carried.restore(&options);
+        assert_eq!(None, options.next_line.take());
+    }
+
+    #[test]
+    fn validated_once() -> Result<()> {
+        let validated = Rc::new(Cell::new(0));
+        let count = Rc::clone(&validated);
+        let options = PromptOptions::new().validation(Validation {
+            validate: Box::new(move |value| {
+                count.set(count.get() + 1);
+                match value {
+                    "no" => Err(String::new()),
+                    _ => Ok(()),
+                }
+            }),
+        });
+        let mut writer = Vec::new();
+        let mut reader = Cursor::new("no\nyes\n");
+        let value = simple_prompt("> ", &mut writer, &mut reader, &options)?;
+        assert_eq!("yes", value);
+        assert_eq!(2, validated.get());
+
+        // A line accepted by the prompt is not validated again
+        validated.set(0);
+        let value = required("> ", &mut writer, &options, &mut |_, _, _| {
+            Ok("no".to_string())
+        })?;
+        assert_eq!("no", value);
+        assert_eq!(0, validated.get());
+        Ok(())
+    }
+
+    #[test]
+    fn debounced_submit() -> Result<()> {
+        let options =
+            PromptOptions::new().debounce_submit(Duration::from_secs(60));
+        let mut writer = Vec::new();
+
+        // The first submit of a prompt is accepted right away
+        let mut buf = buffer("ls");
+        let submitted = submit(&mut writer, &mut buf, &options, 24, false)?;
+        assert_eq!(Submitted::Accepted, submitted);
+
+        let mut buf = buffer("ls");
+        let submitted = submit(&mut writer, &mut buf, &options, 24, false)?;
+        assert_eq!(Submitted::Rejected, submitted);
+
+        options
+            .last_submit
+            .set(Instant::now().checked_sub(Duration::from_secs(61)));
+        let submitted = submit(&mut writer, &mut buf, &options, 24, false)?;
+        assert_eq!(Submitted::Accepted, submitted);
+        Ok(())
+    }
+
+    #[test]
+    fn required_stops_when_aborted() {
+        let options = PromptOptions::new().required(Required {
+            trim: true,
+            max_attempts: 0,
+        });
+        let mut writer = Vec::new();
+        let mut reads = 0;
+        let err = required("> ", &mut writer, &options, &mut |_, _, _| {
+            reads += 1;
+            Err(Aborted(AbortReason::Cancelled).into())
+        })
+        .unwrap_err();
+        assert_eq!(
+            Some(&Aborted(AbortReason::Cancelled)),
+            err.downcast_ref::<Aborted>()
+        );
+        assert_eq!(1, reads);
+    }
+
+    #[test]
+    fn expired_cancel_token() {
+        let token = CancelToken::new();
+        let options = PromptOptions::new().cancel(token.clone());
+        let now = Instant::now();
+        assert_eq!(None, expired(&options, now, now));
+        token.cancel();
+        assert_eq!(Some(AbortReason::Cancelled), expired(&options, now, now));
+        token.reset();
+        assert_eq!(None, expired(&options, now, now));
+    }
+
+    #[test]
+    fn expired_timeout() {
+        let options = PromptOptions::new().timeout(Duration::from_secs(5));
+        let last_input = Instant::now();
+        let later = |secs| last_input + Duration::from_secs(secs);
+        assert_eq!(None, expired(&options, last_input, later(4)));
+        assert_eq!(
+            Some(AbortReason::Timeout),
+            expired(&options, last_input, later(5))
+        );
+        assert_eq!(None, expired(&PromptOptions::new(), last_input, later(60)));
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn following_entry() {
+        use history::{History, MemoryHistory};
+
+        let mut history = MemoryHistory::new(Default::default());
+        for item in ["cd src", "make", "ls"] {
+            history.push(item.to_string());
+        }
+        assert_eq!(None, following(&mut history));
+        history.previous();
+        history.previous();
+        history.previous();
+        assert_eq!(Some(("make".to_string(), 2)), following(&mut history));
+        assert!(history.is_last());
+    }
+
+    #[test]
+    fn literal_keys() {
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        assert_eq!(Some('\x01'), literal(&ctrl('a')));
+        assert_eq!(Some('\x1f'), literal(&ctrl('7')));
+        assert_eq!(Some('\t'), literal(&KeyEvent::from(KeyCode::Tab)));
+        assert_eq!(Some('é'), literal(&KeyEvent::from(KeyCode::Char('é'))));
+        assert_eq!(None, literal(&KeyEvent::from(KeyCode::Left)));
+    }
+
+    #[test]
+    fn take_repeated_chars() {
+        let options =
+            PromptOptions::new().disable_action(KeyAction::WriteChar('!'));
+        let mut actions = VecDeque::from(vec![
+            KeyAction::WriteChar('a'),
+            KeyAction::WriteChar('a'),
+            KeyAction::WriteChar('!'),
+            KeyAction::WriteChar('a'),
+        ]);
+        let mut text = String::from("a");
+        take_chars(&options, &mut actions, &mut text);
+        assert_eq!("aaa", text);
+        assert_eq!(2, actions.len());
+    }
+
+    #[test]
+    fn simple_parse_end_of_input() {
+        let mut writer = Vec::new();
+        let mut reader = Cursor::new("42\n");
+        let options = PromptOptions::new();
+        let value: u16 =
+            simple_parse("Port: ", &mut writer, &mut reader, &options).unwrap();
+        assert_eq!(42, value);
+        let err = simple_prompt("Port: ", &mut writer, &mut reader, &options)
+            .unwrap_err();
+        assert!(err.is::<EndOfInput>());
+    }
+}
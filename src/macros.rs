@@ -0,0 +1,110 @@
+//! Recording and replaying keyboard macros.
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+/// Records key events and queues them for replay.
+#[derive(Default)]
+pub(crate) struct Recorder {
+    recording: Option<Vec<KeyEvent>>,
+    replay: VecDeque<KeyEvent>,
+}
+
+impl Recorder {
+    /// Next key event of a macro being replayed.
+    pub fn next(&mut self) -> Option<KeyEvent> {
+        self.replay.pop_front()
+    }
+
+    /// Determine if a macro is being replayed.
+    pub fn is_replaying(&self) -> bool {
+        !self.replay.is_empty()
+    }
+
+    /// Record a key event when a macro is being recorded.
+    pub fn record(&mut self, event: KeyEvent) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(event);
+        }
+    }
+
+    /// Record characters that were typed without being
+    /// read as separate events.
+    pub fn record_chars(&mut self, text: &str) {
+        for c in text.chars() {
+            self.record(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+    }
+
+    /// Start recording, discarding an unfinished recording.
+    pub fn start(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Finish recording and store the macro.
+    ///
+    /// The number of keys typed to finish the recording are
+    /// not part of the macro.
+    pub fn end(&mut self, last_macro: &RefCell<Vec<KeyEvent>>, typed: usize) {
+        if let Some(mut recording) = self.recording.take() {
+            recording.truncate(recording.len().saturating_sub(typed));
+            *last_macro.borrow_mut() = recording;
+        }
+    }
+
+    /// Replay a macro.
+    ///
+    /// The number of keys typed to call the macro are not recorded,
+    /// the replayed keys are recorded instead.
+    pub fn call(&mut self, last_macro: &RefCell<Vec<KeyEvent>>, typed: usize) {
+        if let Some(recording) = &mut self.recording {
+            recording.truncate(recording.len().saturating_sub(typed));
+        }
+        self.replay.extend(last_macro.borrow().iter().copied());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let last_macro = RefCell::new(Vec::new());
+        let mut recorder = Recorder::default();
+        recorder.record(key('a'));
+        recorder.start();
+        recorder.record(key('b'));
+        recorder.record_chars("cd");
+        recorder.record(key('x'));
+        recorder.record(key(')'));
+        recorder.end(&last_macro, 2);
+        assert_eq!(vec![key('b'), key('c'), key('d')], *last_macro.borrow());
+
+        recorder.record(key('e'));
+        recorder.call(&last_macro, 1);
+        assert!(recorder.is_replaying());
+        assert_eq!(Some(key('b')), recorder.next());
+        recorder.next();
+        recorder.next();
+        assert!(!recorder.is_replaying());
+
+        recorder.start();
+        recorder.record(key('x'));
+        recorder.record(key('e'));
+        recorder.call(&last_macro, 1);
+        while let Some(event) = recorder.next() {
+            recorder.record(event);
+        }
+        recorder.record(key(')'));
+        recorder.end(&last_macro, 1);
+        assert_eq!(
+            vec![key('x'), key('b'), key('c'), key('d')],
+            *last_macro.borrow()
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Messages displayed on the line below the prompt.
+//!
+//! The cursor position is saved and restored around each message
+//! so the prompt is left untouched; the message is drawn below
+//! every row of the value and the status line.
+use anyhow::Result;
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::{
+    cursor,
+    terminal::{Clear, ClearType, ScrollUp},
+    QueueableCommand,
+};
+use std::fmt::Display;
+use std::io::Write;
+
+use crate::terminal_buffer::TerminalBuffer;
+
+/// Scroll the terminal when the prompt ends on the last line so
+/// there is a line available below the prompt.
+pub(crate) fn make_room<W>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer<'_>,
+) -> Result<()>
+where
+    W: Write,
+{
+    let (_, height) = buf.size();
+    let line = buf.position().1.saturating_add(buf.rows_below());
+    if height > 0 && line >= height {
+        let rows = line - height + 1;
+        writer.queue(ScrollUp(rows))?;
+        writer.queue(cursor::MoveUp(rows))?;
+        buf.scrolled(rows);
+    }
+    Ok(())
+}
+
+/// Show a styled message on the line below the prompt.
+pub(crate) fn show<W, M>(
+    writer: &mut W,
+    buf: &mut TerminalBuffer<'_>,
+    message: M,
+) -> Result<()>
+where
+    W: Write,
+    M: Display,
+{
+    make_room(writer, buf)?;
+    writer.queue(cursor::SavePosition)?;
+    writer.queue(cursor::MoveToNextLine(buf.rows_below()))?;
+    writer.queue(Clear(ClearType::CurrentLine))?;
+    crate::style::queue_style(writer, &buf.theme().message)?;
+    writer.queue(Print(message))?;
+    writer.queue(SetAttribute(Attribute::Reset))?;
+    writer.queue(cursor::RestorePosition)?;
+    Ok(())
+}
+
+/// Clear the line below the prompt.
+pub(crate) fn clear<W>(writer: &mut W, buf: &TerminalBuffer<'_>) -> Result<()>
+where
+    W: Write,
+{
+    writer.queue(cursor::SavePosition)?;
+    writer.queue(cursor::MoveToNextLine(buf.rows_below()))?;
+    writer.queue(Clear(ClearType::CurrentLine))?;
+    writer.queue(cursor::RestorePosition)?;
+    Ok(())
+}
@@ -0,0 +1,221 @@
+//! Sub-modes that temporarily take over key handling.
+//!
+//! Modes such as the completion menu and incremental search are
+//! kept on a stack above the prompt bindings. Each key event is
+//! resolved against the keymap of the top mode first; when a mode
+//! does not handle a key it is exited and the key is passed down
+//! to the next mode and finally to the prompt bindings.
+use anyhow::Result;
+use crossterm::event::KeyEvent;
+use std::io::Write;
+
+use crate::key_binding::{KeyAction, KeyBindings};
+use crate::options::{CursorStyles, PromptOptions};
+use crate::style::CursorStyle;
+use crate::terminal_buffer::TerminalBuffer;
+
+/// State a mode may access while handling a key.
+pub(crate) struct ModeContext<'a, 'p, W> {
+    pub writer: &'a mut W,
+    pub buf: &'a mut TerminalBuffer<'p>,
+    pub options: &'a PromptOptions,
+}
+
+/// Outcome of a key sent to a mode.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum ModeEvent {
+    /// The key was handled and the mode is still active.
+    Consumed,
+    /// The key was handled and the mode should be exited.
+    Exit,
+    /// The key was not handled; exit the mode and pass the key on.
+    Forward,
+}
+
+/// An input handler that can be pushed on to the mode stack.
+pub(crate) trait Mode<W: Write> {
+    /// Key bindings used to resolve events for this mode.
+    fn keymap(&self) -> &KeyBindings;
+
+    /// Shape of the cursor while the mode is active.
+    fn cursor_style(&self, _styles: &CursorStyles) -> Option<CursorStyle> {
+        None
+    }
+
+    /// Called when the mode is pushed on to the stack.
+    fn enter(&mut self, _ctx: &mut ModeContext<'_, '_, W>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the mode is removed from the stack.
+    fn exit(&mut self, _ctx: &mut ModeContext<'_, '_, W>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Handle the action bound to a key, `None` when the key
+    /// is not bound in the keymap of this mode.
+    fn handle(
+        &mut self,
+        ctx: &mut ModeContext<'_, '_, W>,
+        action: Option<KeyAction>,
+    ) -> Result<ModeEvent>;
+}
+
+/// Stack of active modes.
+pub(crate) struct ModeStack<W> {
+    modes: Vec<Box<dyn Mode<W>>>,
+}
+
+impl<W: Write> ModeStack<W> {
+    /// Create an empty stack.
+    pub fn new() -> Self {
+        Self { modes: Vec::new() }
+    }
+
+    /// Enter a mode.
+    pub fn push(
+        &mut self,
+        ctx: &mut ModeContext<'_, '_, W>,
+        mut mode: Box<dyn Mode<W>>,
+    ) -> Result<()> {
+        mode.enter(ctx)?;
+        self.modes.push(mode);
+        Ok(())
+    }
+
+    /// Shape of the cursor for the active mode.
+    pub fn cursor_style(&self, styles: &CursorStyles) -> Option<CursorStyle> {
+        self.modes.last()?.cursor_style(styles)
+    }
+
+    /// Exit the active mode.
+    pub fn pop(&mut self, ctx: &mut ModeContext<'_, '_, W>) -> Result<()> {
+        if let Some(mut mode) = self.modes.pop() {
+            mode.exit(ctx)?;
+        }
+        Ok(())
+    }
+
+    /// Send a key event to the active modes.
+    ///
+    /// Returns `true` when a mode handled the key, otherwise the
+    /// key should be handled by the prompt bindings.
+    pub fn handle(
+        &mut self,
+        ctx: &mut ModeContext<'_, '_, W>,
+        event: &KeyEvent,
+    ) -> Result<bool> {
+        while let Some(mode) = self.modes.last_mut() {
+            let action = mode
+                .keymap()
+                .first(event)
+                .and_then(|actions| actions.into_iter().next())
+                .filter(|action| ctx.options.is_enabled(action));
+            match mode.handle(ctx, action)? {
+                ModeEvent::Consumed => return Ok(true),
+                ModeEvent::Exit => {
+                    self.pop(ctx)?;
+                    return Ok(true);
+                }
+                ModeEvent::Forward => self.pop(ctx)?,
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Recorder {
+        name: &'static str,
+        forward: bool,
+        keymap: KeyBindings,
+        log: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Mode<Vec<u8>> for Recorder {
+        fn keymap(&self) -> &KeyBindings {
+            &self.keymap
+        }
+
+        fn enter(
+            &mut self,
+            _ctx: &mut ModeContext<'_, '_, Vec<u8>>,
+        ) -> Result<()> {
+            self.log.borrow_mut().push(format!("enter {}", self.name));
+            Ok(())
+        }
+
+        fn exit(
+            &mut self,
+            _ctx: &mut ModeContext<'_, '_, Vec<u8>>,
+        ) -> Result<()> {
+            self.log.borrow_mut().push(format!("exit {}", self.name));
+            Ok(())
+        }
+
+        fn handle(
+            &mut self,
+            _ctx: &mut ModeContext<'_, '_, Vec<u8>>,
+            action: Option<KeyAction>,
+        ) -> Result<ModeEvent> {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {:?}", self.name, action));
+            Ok(if self.forward {
+                ModeEvent::Forward
+            } else {
+                ModeEvent::Consumed
+            })
+        }
+    }
+
+    #[test]
+    fn forward_through_stack() -> Result<()> {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut writer = Vec::new();
+        let mut buf = TerminalBuffer::new("> ", None);
+        let options = PromptOptions::default();
+        let mut ctx = ModeContext {
+            writer: &mut writer,
+            buf: &mut buf,
+            options: &options,
+        };
+
+        let mut modes = ModeStack::new();
+        for (name, forward) in [("bottom", false), ("top", true)] {
+            let mode = Recorder {
+                name,
+                forward,
+                keymap: KeyBindings::default(),
+                log: Rc::clone(&log),
+            };
+            modes.push(&mut ctx, Box::new(mode))?;
+        }
+
+        let event = KeyEvent {
+            code: KeyCode::Char('x'),
+            modifiers: KeyModifiers::NONE,
+        };
+        assert!(modes.handle(&mut ctx, &event)?);
+        assert_eq!(
+            vec![
+                "enter bottom",
+                "enter top",
+                "top Some(WriteChar('x'))",
+                "exit top",
+                "bottom Some(WriteChar('x'))",
+            ],
+            *log.borrow()
+        );
+
+        modes.pop(&mut ctx)?;
+        assert!(!modes.handle(&mut ctx, &event)?);
+        Ok(())
+    }
+}
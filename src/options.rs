@@ -1,18 +1,37 @@
 //! Options for creating prompts.
-use crate::key_binding::KeyBindings;
+use crate::editor::{CommandHandler, CommandOutcome, Editor};
+use crate::key_binding::{
+    AbortReason, CommandCategory, KeyAction, KeyBindings, KeyMatch,
+};
+use crate::style::{CursorStyle, TabStyle, Theme};
+use crate::terminal_buffer::TerminalBuffer;
 use std::borrow::Cow;
+use std::cell::{Cell, RefCell, RefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-#[cfg(feature = "history")]
-use crate::history::History;
-
-#[cfg(feature = "history")]
-use std::sync::Mutex;
+#[cfg(any(feature = "history", doc))]
+use crate::history::{HistoryEntry, HistoryGuard, HistoryHandle};
+#[cfg(any(feature = "styling", doc))]
+use crate::style::Highlighter;
+use crossterm::event::KeyEvent;
 
 /// The options to use when creating a prompt.
 #[derive(Default)]
 pub struct PromptOptions {
     /// Key bindings to use for the prompt.
-    pub(crate) bindings: KeyBindings,
+    pub(crate) bindings: RefCell<KeyBindings>,
+
+    /// Key bindings stacked over the key bindings, the last
+    /// overlay takes precedence.
+    pub(crate) overlays: Vec<KeyBindings>,
+
+    /// Categories of actions that are ignored.
+    pub(crate) disabled: Vec<CommandCategory>,
+
+    /// Actions that are ignored.
+    pub(crate) disabled_actions: Vec<KeyAction>,
 
     /// Options for requiring a value.
     pub(crate) required: Option<Required>,
@@ -22,7 +41,7 @@ pub struct PromptOptions {
 
     /// Options for multiline input.
     ///
-    /// Use Ctrl+c or Ctrl+d to exit the prompt.
+    /// Use Ctrl+d to finish the input, Ctrl+c aborts the prompt.
     pub(crate) multiline: Option<MultiLine>,
 
     /// Options for validating the input.
@@ -31,10 +50,135 @@ pub struct PromptOptions {
     /// Options for transforming the value.
     pub(crate) transformer: Option<Transformer>,
 
+    /// Options for collapsing the prompt once a line is accepted.
+    pub(crate) transient_prompt: Option<TransientPrompt>,
+
+    /// Abort the prompt after this period without input.
+    pub(crate) timeout: Option<Duration>,
+
+    /// Commands defined by the application and their keys.
+    pub(crate) commands: Vec<(KeyEvent, CommandHandler)>,
+
+    /// Called for keys bound to [KeyAction::Help].
+    pub(crate) help: Option<CommandHandler>,
+
+    /// Wait this long for the next key of a key sequence.
+    pub(crate) sequence_timeout: Option<Duration>,
+
+    /// Wait this long after Esc for a key sent as Esc and the key.
+    pub(crate) escape_timeout: Option<Duration>,
+
+    /// Token to abort the prompt from another thread.
+    pub(crate) cancel: Option<CancelToken>,
+
+    /// Text printed above the prompt by other threads.
+    pub(crate) printer: Option<ExternalPrinter>,
+
+    /// Called with the reason when the prompt is aborted.
+    pub(crate) on_abort: Option<AbortHandler>,
+
+    /// Called with the line before it is accepted.
+    pub(crate) on_accept: Option<AcceptHandler>,
+
+    /// Produces the terminal title from the current line.
+    pub(crate) title: Option<TitleHandler>,
+
+    /// Produces the status line drawn below the value.
+    pub(crate) status: Option<StatusHandler>,
+
+    /// Describes state of the application in the debug overlay.
+    #[cfg(any(feature = "debug", doc))]
+    pub(crate) debug_info: Option<StatusHandler>,
+
+    /// Copies text to the system clipboard.
+    pub(crate) clipboard: Option<ClipboardHandler>,
+
+    /// Called before each action is executed.
+    pub(crate) before_action: Option<ActionHook>,
+
+    /// Called after each action is executed.
+    pub(crate) after_action: Option<ActionHook>,
+
+    /// Ignore a submit within this period of the last submit.
+    pub(crate) debounce_submit: Option<Duration>,
+
+    /// When a line was last submitted using these options.
+    pub(crate) last_submit: Cell<Option<Instant>>,
+
+    /// Whether a validation message is drawn below the prompt.
+    pub(crate) invalid_shown: Cell<bool>,
+
+    /// Key events of the most recently recorded keyboard macro.
+    pub(crate) last_macro: RefCell<Vec<KeyEvent>>,
+
+    /// Value the prompt starts with.
+    pub(crate) initial_value: Option<String>,
+
+    /// Hint drawn while the value is empty.
+    pub(crate) placeholder: Option<String>,
+
+    /// Styles of the parts of the prompt.
+    pub(crate) theme: Theme,
+
+    /// How tabs in the value are drawn.
+    pub(crate) tab_style: TabStyle,
+
+    /// Shapes of the cursor for each mode.
+    pub(crate) cursor_styles: Option<CursorStyles>,
+
+    /// Highlighting applied to the value when it is drawn.
+    #[cfg(any(feature = "styling", doc))]
+    #[doc(cfg(feature = "styling"))]
+    pub(crate) highlighter: Option<Box<dyn Highlighter>>,
+
+    /// Line submitted by [KeyAction::AcceptAndHold] to show
+    /// in the next prompt using these options.
+    pub(crate) held_line: RefCell<Option<String>>,
+
+    /// History entry to show in the next prompt using these
+    /// options and how many entries it is before the newest.
+    #[cfg(any(feature = "history", doc))]
+    pub(crate) next_line: RefCell<Option<(String, usize)>>,
+
+    /// Whether the prompt is shown by the shell loop.
+    #[cfg(any(feature = "history", doc))]
+    pub(crate) in_shell: Cell<bool>,
+
+    /// Options for completion.
+    #[cfg(any(feature = "completion", doc))]
+    #[doc(cfg(feature = "completion"))]
+    pub(crate) completion: Option<Completion>,
+
     /// History implementation.
     #[cfg(any(feature = "history", doc))]
     #[doc(cfg(feature = "history"))]
-    pub(crate) history: Option<Box<Mutex<dyn History>>>,
+    pub(crate) history: Option<Box<dyn HistoryHandle>>,
+
+    /// Namespace for history items.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    pub(crate) history_namespace: Option<String>,
+
+    /// Options for warning before repeating a dangerous command.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    pub(crate) history_warning: Option<HistoryWarning>,
+
+    /// Options for expanding history designators.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    pub(crate) history_expansion: Option<HistoryExpansion>,
+
+    /// Whether empty and whitespace only lines are added
+    /// to the history.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    pub(crate) history_blank: Option<bool>,
+
+    /// Keys that insert tokens extracted from the history.
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    pub(crate) history_tokens: Vec<(KeyEvent, TokenHandler)>,
 }
 
 impl PromptOptions {
@@ -45,10 +189,105 @@ impl PromptOptions {
 
     /// Configure key bindings.
     pub fn bindings(mut self, bindings: KeyBindings) -> Self {
-        self.bindings = bindings;
+        self.bindings = RefCell::new(bindings);
+        self
+    }
+
+    /// Change the key bindings of these options.
+    ///
+    /// Changes apply to the next key typed, so keys can be bound
+    /// by a [command](PromptOptions::command) while a prompt is
+    /// active or between the lines of a shell, for example by
+    /// a `bind` builtin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key bindings are already borrowed.
+    pub fn key_bindings(&self) -> RefMut<'_, KeyBindings> {
+        self.bindings.borrow_mut()
+    }
+
+    /// Stack key bindings over the key bindings of the prompt.
+    ///
+    /// Keys bound in the overlay take precedence and other keys
+    /// fall through to the layers below, so a prompt can change a
+    /// few keys without changing shared key bindings:
+    ///
+    /// ```
+    /// use crossterm_prompt::{KeyAction, KeyBindings, PromptOptions};
+    /// use crossterm_prompt::prelude::{KeyCode, KeyEvent};
+    ///
+    /// let confirm = KeyBindings::builder()
+    ///     .bind(KeyEvent::from(KeyCode::Char('y')), KeyAction::SubmitLine)
+    ///     .build();
+    /// let options = PromptOptions::new().overlay(confirm);
+    /// ```
+    ///
+    /// Overlays added later take precedence over earlier overlays.
+    pub fn overlay(mut self, bindings: KeyBindings) -> Self {
+        self.overlays.push(bindings);
         self
     }
 
+    /// Disable a category of actions.
+    ///
+    /// Keys bound to actions in the category are ignored, for
+    /// example disable [CommandCategory::History] for a password
+    /// prompt or [CommandCategory::ClearScreen] when the prompt is
+    /// embedded in part of the screen.
+    pub fn disable(mut self, category: CommandCategory) -> Self {
+        if !self.disabled.contains(&category) {
+            self.disabled.push(category);
+        }
+        self
+    }
+
+    /// Disable a single action.
+    ///
+    /// Keys bound to the action are ignored while the rest of the
+    /// key bindings stay in place, for example do not abort on
+    /// Ctrl+c with `KeyAction::AbortPrompt(AbortReason::Interrupt)`
+    /// or do not clear the screen with [KeyAction::ClearScreen].
+    pub fn disable_action(mut self, action: KeyAction) -> Self {
+        if !self.disabled_actions.contains(&action) {
+            self.disabled_actions.push(action);
+        }
+        self
+    }
+
+    /// Determine if an action may be performed.
+    pub(crate) fn is_enabled(&self, action: &KeyAction) -> bool {
+        !self.disabled.contains(&action.category())
+            && !self.disabled_actions.contains(action)
+    }
+
+    /// Look up the keys typed so far, a single key looked up
+    /// `alone` does not start a key sequence.
+    pub(crate) fn lookup(&self, keys: &[KeyEvent], alone: bool) -> KeyMatch {
+        if let [event] = keys {
+            if let Some(index) =
+                self.commands.iter().position(|(key, _)| key == event)
+            {
+                return KeyMatch::Actions(vec![KeyAction::Command(index)]);
+            }
+            #[cfg(feature = "history")]
+            if let Some(index) =
+                self.history_tokens.iter().position(|(key, _)| key == event)
+            {
+                return KeyMatch::Actions(vec![KeyAction::InsertHistoryToken(
+                    index,
+                )]);
+            }
+        }
+        for bindings in self.overlays.iter().rev() {
+            match lookup(bindings, keys, alone) {
+                KeyMatch::Unbound => {}
+                found => return found,
+            }
+        }
+        lookup(&self.bindings.borrow(), keys, alone)
+    }
+
     /// Configure password for these options.
     pub fn password(mut self, password: PassWord) -> Self {
         self.password = Some(password);
@@ -79,13 +318,515 @@ impl PromptOptions {
         self
     }
 
+    /// Configure to collapse the prompt once a line is accepted.
+    pub fn transient_prompt(mut self, transient: TransientPrompt) -> Self {
+        self.transient_prompt = Some(transient);
+        self
+    }
+
+    /// Set the value the prompt starts with, the cursor is
+    /// placed at the end of the value.
+    pub fn initial_value<S: Into<String>>(mut self, value: S) -> Self {
+        self.initial_value = Some(value.into());
+        self
+    }
+
+    /// Set a hint drawn dimmed while the value is empty, it is
+    /// never part of the value.
+    pub fn placeholder<S: Into<String>>(mut self, placeholder: S) -> Self {
+        self.placeholder = Some(placeholder.into());
+        self
+    }
+
+    /// Abort the prompt after a period without input, the prompt
+    /// returns an [Aborted](crate::Aborted) error.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Bind a key to a command that may change the line.
+    ///
+    /// The command returns [CommandOutcome::Continue] to keep the
+    /// prompt active or another outcome to end it, for example to
+    /// submit the line in upper case:
+    ///
+    /// ```
+    /// use crossterm_prompt::prelude::*;
+    ///
+    /// let options = PromptOptions::new().command(
+    ///     KeyEvent::new(KeyCode::Char('u'), KeyModifiers::ALT),
+    ///     |editor| {
+    ///         editor.set_line(editor.line().to_uppercase());
+    ///         CommandOutcome::Submit
+    ///     },
+    /// );
+    /// ```
+    ///
+    /// Keys bound here take precedence over the key bindings, the
+    /// command can also be bound in the key bindings using
+    /// [KeyAction::Command] with the index of the command in the
+    /// order commands are added.
+    pub fn command<F>(mut self, event: KeyEvent, command: F) -> Self
+    where
+        F: Fn(&mut Editor<'_>) -> CommandOutcome + 'static,
+    {
+        self.commands.push((event, Box::new(command)));
+        self
+    }
+
+    /// Set the handler for keys bound to [KeyAction::Help],
+    /// by default F1.
+    ///
+    /// The handler receives the line like a
+    /// [command](PromptOptions::command), for example to show
+    /// help for the text typed so far.
+    pub fn help<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&mut Editor<'_>) -> CommandOutcome + 'static,
+    {
+        self.help = Some(Box::new(handler));
+        self
+    }
+
+    /// Run the handler for a command or help action.
+    pub(crate) fn run_command(
+        &self,
+        action: KeyAction,
+        editor: &mut Editor<'_>,
+    ) -> CommandOutcome {
+        let handler = match action {
+            KeyAction::Command(index) => {
+                self.commands.get(index).map(|(_, command)| command)
+            }
+            KeyAction::Help => self.help.as_ref(),
+            _ => None,
+        };
+        match handler {
+            Some(handler) => handler(editor),
+            None => CommandOutcome::Continue,
+        }
+    }
+
+    /// Time to wait for the next key of a key sequence before the
+    /// typed keys are handled on their own, defaults to 500ms.
+    pub fn sequence_timeout(mut self, timeout: Duration) -> Self {
+        self.sequence_timeout = Some(timeout);
+        self
+    }
+
+    /// Wait after Esc for the next key, disabled by default.
+    ///
+    /// Terminals send a key with Alt as Esc followed by the key
+    /// which may arrive separately, for example over a slow
+    /// connection. A key typed within the timeout is combined with
+    /// the Esc into the key with Alt, otherwise Esc is handled on
+    /// its own so that a binding for Esc is reliable.
+    pub fn escape_timeout(mut self, timeout: Duration) -> Self {
+        self.escape_timeout = Some(timeout);
+        self
+    }
+
+    /// Configure a token that aborts the prompt when cancelled.
+    ///
+    /// A cancelled token aborts every prompt using it until
+    /// it is [reset](CancelToken::reset).
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Configure a printer other threads use to print text
+    /// above the prompt.
+    pub fn external_printer(mut self, printer: ExternalPrinter) -> Self {
+        self.printer = Some(printer);
+        self
+    }
+
+    /// Configure a callback invoked before an aborted prompt
+    /// returns an [Aborted](crate::Aborted) error.
+    ///
+    /// The callback receives the trigger that aborted the prompt
+    /// so cleanup can differ between, for example, the user pressing
+    /// Ctrl+c and a shutdown cancelling the prompt.
+    pub fn on_abort<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(AbortReason) + 'static,
+    {
+        self.on_abort = Some(Box::new(handler));
+        self
+    }
+
+    /// Configure a callback invoked when a line is submitted.
+    ///
+    /// The callback decides whether the line is accepted, see
+    /// [AcceptOutcome]. It is called again each time the line is
+    /// submitted so a previewed value is checked before it is
+    /// accepted.
+    pub fn on_accept<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&str) -> AcceptOutcome + 'static,
+    {
+        self.on_accept = Some(Box::new(handler));
+        self
+    }
+
+    /// Ignore submitting a line within a period of the previous
+    /// submit.
+    ///
+    /// Prevents a bouncing Enter key from running a command twice,
+    /// the options must be reused for each prompt so the time of
+    /// the previous submit is known.
+    pub fn debounce_submit(mut self, window: Duration) -> Self {
+        self.debounce_submit = Some(window);
+        self
+    }
+
+    /// Set the terminal window title while the prompt is active.
+    ///
+    /// The callback is invoked with the current line before each
+    /// key is read, the original title is restored when the
+    /// prompt returns.
+    pub fn title<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&str) -> String + 'static,
+    {
+        self.title = Some(Box::new(handler));
+        self
+    }
+
+    /// Draw a status line below the value, for example the number
+    /// of characters or whether the value is valid.
+    ///
+    /// The callback is invoked with the buffer after each key
+    /// is handled and the line is drawn again when the status
+    /// changes; it is removed when the prompt returns.
+    pub fn status<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&TerminalBuffer<'_>) -> String + 'static,
+    {
+        self.status = Some(Box::new(handler));
+        self
+    }
+
+    /// Add text from the application to the debug overlay
+    /// toggled by [KeyAction::ToggleDebug].
+    ///
+    /// The callback is invoked with the buffer each time the
+    /// overlay is drawn and the text is shown after the state
+    /// of the prompt, for example the state of a completer.
+    #[cfg(any(feature = "debug", doc))]
+    #[doc(cfg(feature = "debug"))]
+    pub fn debug_info<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&TerminalBuffer<'_>) -> String + 'static,
+    {
+        self.debug_info = Some(Box::new(handler));
+        self
+    }
+
+    /// Set the handler that copies text to the system clipboard
+    /// for [KeyAction::CopyToClipboard].
+    ///
+    /// By default the text is sent to the terminal using the
+    /// OSC 52 escape sequence, a handler can use a clipboard
+    /// library instead when the terminal does not support it.
+    pub fn clipboard<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&str) + 'static,
+    {
+        self.clipboard = Some(Box::new(handler));
+        self
+    }
+
+    #[cfg(any(feature = "styling", doc))]
+    #[doc(cfg(feature = "styling"))]
+    /// Set the styles of the parts of the prompt, for example
+    /// [Theme::light](crate::style::Theme::light) for terminals
+    /// with a light background.
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Set how a tab inserted in the value, for example with
+    /// [KeyAction::QuotedInsert], is drawn; the default is
+    /// four spaces.
+    pub fn tab_style(mut self, tab_style: TabStyle) -> Self {
+        self.tab_style = tab_style;
+        self
+    }
+
+    /// Set the shape of the cursor for each mode, the shape
+    /// configured by the user of the terminal is restored when
+    /// the prompt returns.
+    pub fn cursor_styles(mut self, styles: CursorStyles) -> Self {
+        self.cursor_styles = Some(styles);
+        self
+    }
+
+    #[cfg(any(feature = "styling", doc))]
+    #[doc(cfg(feature = "styling"))]
+    /// Set the highlighter that styles the value each time
+    /// it is drawn.
+    pub fn highlighter<H>(mut self, highlighter: H) -> Self
+    where
+        H: Highlighter + 'static,
+    {
+        self.highlighter = Some(Box::new(highlighter));
+        self
+    }
+
+    /// Set a callback invoked before each enabled action is executed
+    /// with the action and the line it is executed on.
+    pub fn before_action<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(KeyAction, &TerminalBuffer<'_>) + 'static,
+    {
+        self.before_action = Some(Box::new(hook));
+        self
+    }
+
+    /// Set a callback invoked after each enabled action is executed
+    /// with the action and the line it changed.
+    ///
+    /// For example to count the actions used:
+    ///
+    /// ```
+    /// use crossterm_prompt::prelude::*;
+    /// use std::cell::RefCell;
+    /// use std::collections::HashMap;
+    /// use std::rc::Rc;
+    ///
+    /// let counts = Rc::new(RefCell::new(HashMap::new()));
+    /// let recorded = Rc::clone(&counts);
+    /// let options = PromptOptions::new().after_action(move |action, _| {
+    ///     *recorded.borrow_mut().entry(action.category()).or_insert(0) += 1;
+    /// });
+    /// ```
+    pub fn after_action<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(KeyAction, &TerminalBuffer<'_>) + 'static,
+    {
+        self.after_action = Some(Box::new(hook));
+        self
+    }
+
+    #[cfg(any(feature = "completion", doc))]
+    #[doc(cfg(feature = "completion"))]
+    /// Configure for completion.
+    pub fn completion(mut self, completion: Completion) -> Self {
+        self.completion = Some(completion);
+        self
+    }
+
     #[cfg(any(feature = "history", doc))]
     #[doc(cfg(feature = "history"))]
     /// Configure with a history.
-    pub fn history(mut self, history: Box<Mutex<dyn History>>) -> Self {
-        self.history = Some(history);
+    ///
+    /// The history may be shared with other prompts using
+    /// a [SharedHistory](crate::history::SharedHistory) or an
+    /// `Rc<RefCell<_>>`, or owned by the options using a `RefCell`;
+    /// use a [HistoryRegistry](crate::history::HistoryRegistry) to
+    /// select a shared history by name.
+    pub fn history<H: HistoryHandle + 'static>(mut self, history: H) -> Self {
+        self.history = Some(Box::new(history));
         self
     }
+
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    /// Get exclusive access to the configured history.
+    pub fn acquire_history(&self) -> Option<HistoryGuard<'_>> {
+        self.history.as_ref().map(|history| history.acquire())
+    }
+
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    /// Configure a namespace for history items.
+    ///
+    /// Items submitted by the prompt are tagged with the namespace
+    /// and history navigation is restricted to the namespace
+    /// until the scope is widened.
+    pub fn history_namespace<S: Into<String>>(mut self, namespace: S) -> Self {
+        self.history_namespace = Some(namespace.into());
+        self
+    }
+
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    /// Configure a warning for repeating dangerous commands.
+    pub fn history_warning(mut self, warning: HistoryWarning) -> Self {
+        self.history_warning = Some(warning);
+        self
+    }
+
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    /// Configure expansion of history designators such as `!!`.
+    ///
+    /// See [expand](crate::history::expand) for the supported
+    /// designators.
+    pub fn history_expansion(mut self, expansion: HistoryExpansion) -> Self {
+        self.history_expansion = Some(expansion);
+        self
+    }
+
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    /// Configure whether empty and whitespace only lines are
+    /// added to the history.
+    ///
+    /// By default blank lines are added except when the prompt
+    /// is shown by [shell](crate::shell()).
+    pub fn history_blank(mut self, record: bool) -> Self {
+        self.history_blank = Some(record);
+        self
+    }
+
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    /// Determine if a submitted line is added to the history.
+    pub(crate) fn is_recorded(&self, line: &str) -> bool {
+        let blank = self.history_blank.unwrap_or(!self.in_shell.get());
+        blank || !line.trim().is_empty()
+    }
+
+    #[cfg(any(feature = "history", doc))]
+    #[doc(cfg(feature = "history"))]
+    /// Bind a key to insert a token extracted from the history.
+    ///
+    /// The extractor is called with history entries from newest to
+    /// oldest until it returns a token, which is inserted at the
+    /// cursor; pressing the key again replaces the token with one
+    /// from an older entry. For example, to insert the first
+    /// argument of the previous line like bash's `yank-nth-arg`:
+    ///
+    /// ```
+    /// use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    /// use crossterm_prompt::PromptOptions;
+    ///
+    /// let options = PromptOptions::new().history_token(
+    ///     KeyEvent::new(
+    ///         KeyCode::Char('y'),
+    ///         KeyModifiers::CONTROL | KeyModifiers::ALT,
+    ///     ),
+    ///     |entry| entry.value.split_whitespace().nth(1).map(String::from),
+    /// );
+    /// ```
+    ///
+    /// The last word of the previous line is inserted with
+    /// [KeyAction::YankLastArg], by default Alt+.
+    ///
+    /// Keys bound here take precedence over the key bindings.
+    pub fn history_token<F>(mut self, event: KeyEvent, extract: F) -> Self
+    where
+        F: Fn(&HistoryEntry) -> Option<String> + 'static,
+    {
+        self.history_tokens.push((event, Box::new(extract)));
+        self
+    }
+}
+
+#[cfg(any(feature = "history", doc))]
+pub(crate) type TokenHandler = Box<dyn Fn(&HistoryEntry) -> Option<String>>;
+
+type AbortHandler = Box<dyn Fn(AbortReason)>;
+
+type AcceptHandler = Box<dyn Fn(&str) -> AcceptOutcome>;
+
+type TitleHandler = Box<dyn Fn(&str) -> String>;
+
+type StatusHandler = Box<dyn Fn(&TerminalBuffer<'_>) -> String>;
+
+type ClipboardHandler = Box<dyn Fn(&str)>;
+
+type ActionHook = Box<dyn Fn(KeyAction, &TerminalBuffer<'_>)>;
+
+/// Decision made when a line is submitted.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AcceptOutcome {
+    /// Accept the line.
+    Accept,
+    /// Accept the line with a different value.
+    Replace(String),
+    /// Show a different value and return to editing.
+    Preview(String),
+    /// Return to editing showing a message below the prompt.
+    Reject(String),
+}
+
+/// Look up keys in a layer of key bindings.
+fn lookup(bindings: &KeyBindings, keys: &[KeyEvent], alone: bool) -> KeyMatch {
+    match keys {
+        [event] if alone => bindings
+            .first(event)
+            .map_or(KeyMatch::Unbound, KeyMatch::Actions),
+        _ => bindings.lookup(keys),
+    }
+}
+
+/// Token to cancel a prompt from another thread.
+///
+/// Clones share the same state so a clone can be given to the
+/// prompt options while another is kept to cancel the prompt.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Create a new token.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Cancel prompts using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Determine if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Clear the cancellation so the token can be used
+    /// for the next prompt.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Printer for text above a prompt from other threads.
+///
+/// Clones share the same queue so a clone can be given to the
+/// prompt options while others print from other threads. The
+/// prompt is erased, the text printed and the prompt drawn
+/// again below it; text printed while no prompt is active is
+/// printed when the next prompt is shown.
+#[derive(Debug, Clone, Default)]
+pub struct ExternalPrinter(Arc<Mutex<Vec<String>>>);
+
+impl ExternalPrinter {
+    /// Create a new printer.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Print text on the lines above the prompt.
+    pub fn print<S: Into<String>>(&self, text: S) {
+        self.lock().push(text.into());
+    }
+
+    /// Remove the text waiting to be printed.
+    pub(crate) fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Lock the queue, a thread that panicked while printing
+    /// leaves the queue usable.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<String>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
 }
 
 /// The options for a required value.
@@ -109,11 +850,27 @@ pub struct PassWord {
     ///
     /// Default is to print the asterisk ('*').
     pub echo: Option<char>,
+
+    /// Mask a revealed password again after this period
+    /// without any input.
+    ///
+    /// Has no effect when `echo` is `None`.
+    pub mask_after: Option<Duration>,
+
+    /// Show the last character typed for this period before
+    /// it is masked, to help catch typos.
+    ///
+    /// Has no effect when `echo` is `None`.
+    pub show_typed: Option<Duration>,
 }
 
 impl Default for PassWord {
     fn default() -> Self {
-        Self { echo: Some('*') }
+        Self {
+            echo: Some('*'),
+            mask_after: None,
+            show_typed: None,
+        }
     }
 }
 
@@ -124,27 +881,34 @@ pub struct MultiLine {
     pub repeat_prompt: bool,
 }
 
+type ValidationHandler = Box<dyn Fn(&str) -> Result<(), String>>;
+
 /// The options for validation.
 pub struct Validation {
     /// Closure to validate the value.
     ///
-    /// When a value is invalid (`false`)
-    /// a prompt is automatically displayed again.
-    pub validate: Box<dyn Fn(&str) -> bool>,
+    /// When a value is invalid the error message is drawn on
+    /// the line below the prompt until the next key is typed
+    /// and the prompt stays active; an empty message is not
+    /// drawn. A [simple_prompt](crate::simple_prompt) writes
+    /// the message and shows the prompt again.
+    pub validate: ValidationHandler,
 }
 
 impl Default for Validation {
     fn default() -> Self {
         Self {
-            validate: Box::new(|_| true),
+            validate: Box::new(|_| Ok(())),
         }
     }
 }
 
+type TransformHandler = Box<dyn Fn(&str) -> Cow<'_, str>>;
+
 /// The options for transforming the value.
 pub struct Transformer {
     /// Closure to transform the value.
-    pub transform: Box<dyn Fn(&str) -> Cow<'_, str>>,
+    pub transform: TransformHandler,
 }
 
 impl Default for Transformer {
@@ -154,3 +918,195 @@ impl Default for Transformer {
         }
     }
 }
+
+/// The options for collapsing the prompt once a line is accepted.
+///
+/// The prompt is drawn again with a compact prefix before the
+/// accepted line, for example to keep only `❯ value` in the
+/// scrollback of a shell.
+pub struct TransientPrompt {
+    /// Prefix drawn before the accepted line.
+    pub prefix: String,
+}
+
+impl Default for TransientPrompt {
+    fn default() -> Self {
+        Self {
+            prefix: "❯ ".to_string(),
+        }
+    }
+}
+
+#[cfg(any(feature = "completion", doc))]
+type CompletionHandler = Box<dyn Fn(&str, &[String]) -> Vec<String>>;
+
+/// The options for the shape of the cursor in each mode.
+///
+/// A mode without a shape uses the shape for editing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CursorStyles {
+    /// Shape while editing the line, the shape configured by
+    /// the user of the terminal when not set.
+    pub edit: Option<CursorStyle>,
+    /// Shape while the next key is inserted as a character.
+    pub quoted: Option<CursorStyle>,
+    /// Shape while searching the history.
+    pub search: Option<CursorStyle>,
+    /// Shape while the completion menu is open.
+    pub menu: Option<CursorStyle>,
+}
+
+/// The options for completion.
+#[cfg(any(feature = "completion", doc))]
+#[doc(cfg(feature = "completion"))]
+pub struct Completion {
+    /// Closure to find candidates for the word before the cursor.
+    ///
+    /// Called with the unescaped word and the words before it;
+    /// candidates that do not start with the word are ignored.
+    pub complete: CompletionHandler,
+}
+
+#[cfg(any(feature = "completion", doc))]
+impl Default for Completion {
+    fn default() -> Self {
+        Self {
+            complete: Box::new(|_, _| vec![]),
+        }
+    }
+}
+
+/// The options for warning before repeating a dangerous command.
+///
+/// When a submitted line exactly matches an entry in the history
+/// and the entry is flagged as dangerous the message is shown below
+/// the prompt and the line is only accepted when `y` is pressed.
+#[cfg(any(feature = "history", doc))]
+#[doc(cfg(feature = "history"))]
+pub struct HistoryWarning {
+    /// Closure to determine if a history entry is dangerous.
+    pub dangerous: Box<dyn Fn(&str) -> bool>,
+
+    /// Message asking for confirmation.
+    pub message: String,
+}
+
+#[cfg(any(feature = "history", doc))]
+impl Default for HistoryWarning {
+    fn default() -> Self {
+        Self {
+            dangerous: Box::new(|_| false),
+            message: "Repeat this dangerous command? [y/N]".to_string(),
+        }
+    }
+}
+
+/// The options for expanding history designators.
+///
+/// Designators are expanded when the line is submitted and the
+/// expanded line is shown in place of the submitted line; when a
+/// designator does not match an entry an error is shown below
+/// the prompt and the line can be edited.
+#[cfg(any(feature = "history", doc))]
+#[doc(cfg(feature = "history"))]
+#[derive(Default)]
+pub struct HistoryExpansion {
+    /// Return to editing the expanded line so it must be
+    /// submitted again to be accepted.
+    pub confirm: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    #[test]
+    fn disabled_actions() {
+        let interrupt = KeyAction::AbortPrompt(AbortReason::Interrupt);
+        let options = PromptOptions::new()
+            .disable_action(interrupt)
+            .disable_action(KeyAction::ClearScreen);
+        assert!(!options.is_enabled(&interrupt));
+        assert!(!options.is_enabled(&KeyAction::ClearScreen));
+        assert!(
+            options.is_enabled(&KeyAction::AbortPrompt(AbortReason::EndOfFile))
+        );
+
+        let options = options.disable(CommandCategory::Motion);
+        assert!(!options.is_enabled(&KeyAction::MoveToLineEnd));
+        assert!(options.is_enabled(&KeyAction::Yank));
+    }
+
+    #[test]
+    fn external_printer() {
+        let printer = ExternalPrinter::new();
+        let options = PromptOptions::new().external_printer(printer.clone());
+        let thread = std::thread::spawn(move || printer.print("done"));
+        thread.join().unwrap();
+        let printer = options.printer.as_ref().unwrap();
+        assert_eq!(vec!["done".to_string()], printer.take());
+        assert!(printer.take().is_empty());
+    }
+
+    #[test]
+    fn command_outcome() {
+        let options = PromptOptions::new()
+            .command(KeyEvent::from(KeyCode::F(2)), |editor| {
+                editor.set_line("done");
+                CommandOutcome::Submit
+            })
+            .help(|_| CommandOutcome::Beep);
+        let mut editor = Editor::new("", 0, &options);
+        assert_eq!(
+            CommandOutcome::Submit,
+            options.run_command(KeyAction::Command(0), &mut editor)
+        );
+        assert_eq!("done", editor.line());
+        assert_eq!(
+            CommandOutcome::Beep,
+            options.run_command(KeyAction::Help, &mut editor)
+        );
+        assert_eq!(
+            CommandOutcome::Continue,
+            options.run_command(KeyAction::Command(1), &mut editor)
+        );
+    }
+
+    #[test]
+    fn overlay_precedence() {
+        let enter = KeyEvent::from(KeyCode::Enter);
+        let y = KeyEvent::from(KeyCode::Char('y'));
+        let ctrl_x = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        let options = PromptOptions::new()
+            .overlay(
+                KeyBindings::builder()
+                    .bind(y, KeyAction::SubmitLine)
+                    .bind(ctrl_x, KeyAction::Yank)
+                    .build(),
+            )
+            .overlay(
+                KeyBindings::builder()
+                    .bind(y, KeyAction::MoveToLineEnd)
+                    .unbind(enter)
+                    .build(),
+            );
+
+        let actions =
+            |keys: &[KeyEvent], alone| match options.lookup(keys, alone) {
+                KeyMatch::Actions(actions) => actions,
+                found => panic!("{:?}", found),
+            };
+        assert_eq!(vec![KeyAction::MoveToLineEnd], actions(&[y], false));
+        assert!(actions(&[enter], false).is_empty());
+        assert_eq!(vec![KeyAction::Yank], actions(&[ctrl_x], true));
+        assert_eq!(
+            vec![KeyAction::StartMacro],
+            actions(&[ctrl_x, KeyEvent::from(KeyCode::Char('('))], false)
+        );
+        assert_eq!(
+            vec![KeyAction::WriteChar('n')],
+            actions(&[KeyEvent::from(KeyCode::Char('n'))], false)
+        );
+    }
+}
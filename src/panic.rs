@@ -9,11 +9,11 @@
 //! when a panic happens.
 //!
 use backtrace::Backtrace;
-use std::panic::PanicInfo;
+use std::panic::PanicHookInfo;
 
 use crossterm::{cursor, execute, terminal::disable_raw_mode};
 
-fn handle_panic_hook(info: &PanicInfo) {
+fn handle_panic_hook(info: &PanicHookInfo) {
     let _ = disable_raw_mode();
     let thread = std::thread::current();
     let thread_name = if let Some(name) = thread.name() {
@@ -22,7 +22,7 @@ fn handle_panic_hook(info: &PanicInfo) {
         thread.id().as_u64().to_string()
     };
     eprintln!("thread '{}' {}", thread_name, info);
-    if let Ok(_) = std::env::var("RUST_BACKTRACE") {
+    if std::env::var("RUST_BACKTRACE").is_ok() {
         let backtrace = Backtrace::new();
         eprintln!("{:?}", backtrace);
     } else {
@@ -0,0 +1,46 @@
+//! Commonly used types and functions.
+//!
+//! Key events are re-exported from the version of crossterm
+//! used by this crate so bindings can be constructed without
+//! depending on crossterm directly:
+//!
+//! ```
+//! use crossterm_prompt::prelude::*;
+//!
+//! let alt_dot = KeyEvent::new(KeyCode::Char('.'), KeyModifiers::ALT);
+//! let options = PromptOptions::new().password(PassWord::default());
+//! ```
+pub use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+pub use crate::step::PromptStep;
+pub use crate::style::{CursorStyle, TabStyle, Theme};
+
+#[cfg(any(feature = "styling", doc))]
+#[doc(cfg(feature = "styling"))]
+pub use crate::style::{Highlighter, StyledText};
+pub use crate::{
+    parse, prompt, simple_parse, simple_prompt, AbortReason, Aborted,
+    AcceptOutcome, CancelToken, CommandCategory, CommandOutcome, CursorStyles,
+    Editor, EndOfInput, ExternalPrinter, KeyAction, KeyBindings,
+    KeyBindingsBuilder, MultiLine, PassWord, PromptOptions, Required,
+    Transformer, TransientPrompt, Validation,
+};
+
+#[cfg(any(feature = "completion", doc))]
+#[doc(cfg(feature = "completion"))]
+pub use crate::Completion;
+
+#[cfg(any(feature = "history", doc))]
+#[doc(cfg(feature = "history"))]
+pub use crate::history::{
+    FileHistory, History, HistoryEntry, HistoryHandle, HistoryOptions,
+    MemoryHistory, SharedHistory,
+};
+
+#[cfg(any(feature = "history", doc))]
+#[doc(cfg(feature = "history"))]
+pub use crate::{HistoryExpansion, HistoryWarning};
+
+#[cfg(any(feature = "shell", doc))]
+#[doc(cfg(feature = "shell"))]
+pub use crate::{shell, shell::ShellContext};
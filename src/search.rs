@@ -0,0 +1,208 @@
+//! Incremental search through the history.
+//!
+//! Searches run backwards (Ctrl+r) or forwards (Ctrl+s) and the
+//! direction can be switched while searching. While searching the
+//! buffer shows the current match and the query is shown below the
+//! prompt. Keys that are not used by the search accept the match
+//! and are then handled as normal; Tab and Ctrl+e move to the end
+//! of the match first so that, for example, Tab opens completion
+//! at the end of the match.
+use anyhow::Result;
+use std::io::Write;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::history::History;
+use crate::key_binding::{KeyAction, KeyBindings};
+use crate::message;
+use crate::mode::{Mode, ModeContext, ModeEvent};
+use crate::options::CursorStyles;
+use crate::style::CursorStyle;
+use crate::terminal_buffer::TerminalBuffer;
+
+/// State for an incremental search.
+pub(crate) struct Search {
+    query: String,
+    backward: bool,
+    index: Option<usize>,
+    original: String,
+    failed: bool,
+    keymap: KeyBindings,
+}
+
+impl<W: Write> Mode<W> for Search {
+    fn keymap(&self) -> &KeyBindings {
+        &self.keymap
+    }
+
+    fn cursor_style(&self, styles: &CursorStyles) -> Option<CursorStyle> {
+        styles.search
+    }
+
+    fn enter(&mut self, ctx: &mut ModeContext<'_, '_, W>) -> Result<()> {
+        self.original = ctx.buf.buffer().to_string();
+        self.draw(ctx.writer, ctx.buf)
+    }
+
+    fn exit(&mut self, ctx: &mut ModeContext<'_, '_, W>) -> Result<()> {
+        message::clear(ctx.writer, ctx.buf)
+    }
+
+    fn handle(
+        &mut self,
+        ctx: &mut ModeContext<'_, '_, W>,
+        action: Option<KeyAction>,
+    ) -> Result<ModeEvent> {
+        let history = match &ctx.options.history {
+            Some(history) => history.acquire(),
+            None => return Ok(ModeEvent::Forward),
+        };
+        let (writer, buf) = (&mut *ctx.writer, &mut *ctx.buf);
+        match action {
+            Some(KeyAction::ReverseSearch) => {
+                self.backward = true;
+                self.find(writer, buf, &*history, true)?;
+            }
+            Some(KeyAction::ForwardSearch) => {
+                self.backward = false;
+                self.find(writer, buf, &*history, true)?;
+            }
+            Some(KeyAction::CancelMode) => {
+                buf.set_value(writer, &self.original)?;
+                return Ok(ModeEvent::Exit);
+            }
+            Some(KeyAction::WriteChar(c)) => {
+                self.query.push(c);
+                self.find(writer, buf, &*history, false)?;
+            }
+            Some(KeyAction::EraseCharacter) => {
+                self.query.pop();
+                self.index = None;
+                if self.query.is_empty() {
+                    self.failed = false;
+                    buf.set_value(writer, &self.original)?;
+                } else {
+                    self.find(writer, buf, &*history, false)?;
+                }
+            }
+            Some(KeyAction::MoveToLineEnd) => {
+                // Accept the match with the cursor at the end
+                let value = buf.buffer().to_string();
+                buf.set_value(writer, &value)?;
+                return Ok(ModeEvent::Forward);
+            }
+            _ => return Ok(ModeEvent::Forward),
+        }
+        self.draw(writer, buf)?;
+        Ok(ModeEvent::Consumed)
+    }
+}
+
+impl Search {
+    /// Create a search, the buffer is saved when the mode is
+    /// entered so it can be restored on cancel.
+    ///
+    /// Without a match a backward search starts from the newest
+    /// entry and a forward search from the oldest entry.
+    pub fn new(backward: bool) -> Self {
+        Self {
+            query: String::new(),
+            backward,
+            index: None,
+            original: String::new(),
+            failed: false,
+            keymap: KeyBindings::search(),
+        }
+    }
+
+    /// Find the query starting from the current match and show
+    /// the next match in the search direction.
+    fn find<W>(
+        &mut self,
+        writer: &mut W,
+        buf: &mut TerminalBuffer,
+        history: &dyn History,
+        skip_current: bool,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let len = history.len();
+        let skip = usize::from(skip_current);
+        let indices: Box<dyn Iterator<Item = usize>> = if self.backward {
+            let end = self.index.map_or(len, |i| i + 1 - skip);
+            Box::new((0..end.min(len)).rev())
+        } else {
+            let start = self.index.map_or(0, |i| i + skip);
+            Box::new(start..len)
+        };
+
+        let namespace = history.namespace();
+        let found = indices.into_iter().find_map(|index| {
+            let entry = history.entry(index)?;
+            let visible =
+                history.is_widened() || entry.namespace.as_deref() == namespace;
+            if visible {
+                let offset = entry.value.find(&self.query)?;
+                Some((index, entry.into_owned().value, offset))
+            } else {
+                None
+            }
+        });
+
+        match found {
+            Some((index, value, offset)) => {
+                let cursor = value[..offset].graphemes(true).count();
+                self.index = Some(index);
+                self.failed = false;
+                buf.replace(writer, value, cursor)?;
+            }
+            None => self.failed = true,
+        }
+        Ok(())
+    }
+
+    /// Show the query below the prompt.
+    fn draw<W>(&self, writer: &mut W, buf: &mut TerminalBuffer) -> Result<()>
+    where
+        W: Write,
+    {
+        let failed = if self.failed { "failed " } else { "" };
+        let direction = if self.backward { "reverse-" } else { "" };
+        message::show(
+            writer,
+            buf,
+            format!("({}{}i-search)`{}'", failed, direction, self.query),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::MemoryHistory;
+
+    #[test]
+    fn search_directions() -> Result<()> {
+        let mut history = MemoryHistory::new(Default::default());
+        for item in ["git add", "ls", "git commit", "git push"] {
+            history.push(item.to_string());
+        }
+        let mut writer = Vec::new();
+        let mut buf = TerminalBuffer::new("> ", None);
+
+        let mut search = Search::new(false);
+        search.query.push_str("git");
+        search.find(&mut writer, &mut buf, &history, false)?;
+        assert_eq!("git add", buf.buffer());
+        search.find(&mut writer, &mut buf, &history, true)?;
+        assert_eq!("git commit", buf.buffer());
+
+        search.backward = true;
+        search.find(&mut writer, &mut buf, &history, true)?;
+        assert_eq!("git add", buf.buffer());
+        search.find(&mut writer, &mut buf, &history, true)?;
+        assert!(search.failed);
+        assert_eq!("git add", buf.buffer());
+        Ok(())
+    }
+}
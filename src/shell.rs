@@ -0,0 +1,195 @@
+//! Shell-style tokenizing of input.
+//!
+//! Splits a line into words using the familiar POSIX rules for
+//! whitespace, single quotes, double quotes and backslash escapes
+//! so that shell handlers and the completion engine agree on
+//! where words begin and end.
+
+/// State of a shell passed when requesting the prefix
+/// and options for the next line.
+#[cfg(any(feature = "shell", doc))]
+#[doc(cfg(feature = "shell"))]
+#[derive(Debug, Clone, Default)]
+pub struct ShellContext {
+    /// Number of lines handled so far.
+    pub lines: usize,
+    /// The last line passed to the handler.
+    pub last_line: Option<String>,
+}
+
+/// A word in a line of input.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Token {
+    /// The unescaped value of the word.
+    pub value: String,
+    /// Byte offset of the start of the raw word in the line.
+    pub start: usize,
+    /// Byte offset of the end of the raw word in the line.
+    pub end: usize,
+    /// Quote character that is still open at the end of the word.
+    ///
+    /// Only the last token of a line can have an unterminated quote.
+    pub quote: Option<char>,
+}
+
+/// Split a line into tokens.
+///
+/// Unterminated quotes and a trailing backslash are tolerated so
+/// that partially typed input can be tokenized.
+pub fn tokenize(line: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        if quote.is_none() && c.is_whitespace() {
+            if let Some(mut token) = current.take() {
+                token.end = index;
+                tokens.push(token);
+            }
+            continue;
+        }
+
+        let token = current.get_or_insert_with(|| Token {
+            value: String::new(),
+            start: index,
+            end: index,
+            quote: None,
+        });
+
+        match (quote, c) {
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '\\') => {
+                if let Some((_, next)) = chars.next() {
+                    token.value.push(next);
+                }
+            }
+            (Some('"'), '\\') => match chars.peek() {
+                Some((_, next)) if is_double_quote_escape(*next) => {
+                    token.value.push(*next);
+                    chars.next();
+                }
+                _ => token.value.push(c),
+            },
+            _ => token.value.push(c),
+        }
+    }
+
+    if let Some(mut token) = current.take() {
+        token.end = line.len();
+        token.quote = quote;
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// Escape a word so that it tokenizes back to the same value.
+///
+/// When `quote` is given the word is assumed to be written inside
+/// that quote and only the characters special within the quote are
+/// escaped; the surrounding quote characters are not included.
+pub fn escape(word: &str, quote: Option<char>) -> String {
+    let mut escaped = String::with_capacity(word.len());
+    for c in word.chars() {
+        match quote {
+            Some('\'') => {
+                if c == '\'' {
+                    // Close the quote, escape it and re-open.
+                    escaped.push_str("'\\''");
+                } else {
+                    escaped.push(c);
+                }
+            }
+            Some(_) => {
+                if is_double_quote_escape(c) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            None => {
+                if c.is_whitespace() || is_special(c) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+        }
+    }
+    escaped
+}
+
+/// Characters that may be escaped inside double quotes.
+fn is_double_quote_escape(c: char) -> bool {
+    matches!(c, '"' | '\\' | '$' | '`')
+}
+
+/// Characters that must be escaped outside of quotes.
+fn is_special(c: char) -> bool {
+    matches!(
+        c,
+        '\\' | '\''
+            | '"'
+            | '$'
+            | '`'
+            | '&'
+            | '|'
+            | ';'
+            | '<'
+            | '>'
+            | '('
+            | ')'
+            | '*'
+            | '?'
+            | '['
+            | '#'
+            | '~'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(line: &str) -> Vec<String> {
+        tokenize(line).into_iter().map(|t| t.value).collect()
+    }
+
+    #[test]
+    fn tokenize_words() {
+        assert_eq!(vec!["ls", "-la", "foo"], values("  ls -la\tfoo "));
+        assert!(tokenize("   ").is_empty());
+    }
+
+    #[test]
+    fn tokenize_quotes_and_escapes() {
+        assert_eq!(vec!["a b", "c'd", "e f"], values(r#"'a b' "c'd" e\ f"#));
+        assert_eq!(vec![r#"x"y\z$"#], values(r#""x\"y\z\$""#));
+        assert_eq!(vec!["ab"], values(r#"a"b""#));
+    }
+
+    #[test]
+    fn tokenize_unterminated() {
+        let tokens = tokenize(r#"cat "my fi"#);
+        assert_eq!(2, tokens.len());
+        assert_eq!("my fi", tokens[1].value);
+        assert_eq!(4, tokens[1].start);
+        assert_eq!(Some('"'), tokens[1].quote);
+        assert_eq!(vec!["a"], values("a\\"));
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        let word = r#"it's a "file" $HOME\x"#;
+        for quote in [None, Some('"')] {
+            let line = match quote {
+                Some(q) => format!("{}{}{}", q, escape(word, quote), q),
+                None => escape(word, quote),
+            };
+            assert_eq!(vec![word.to_string()], values(&line));
+        }
+        let line = format!("'{}'", escape(word, Some('\'')));
+        assert_eq!(vec![word.to_string()], values(&line));
+    }
+}
@@ -0,0 +1,312 @@
+//! Combinators for chaining prompts.
+//!
+//! Small interactive flows can be composed from steps, for example
+//! asking for a port and retrying until a number is entered:
+//!
+//! ```no_run
+//! use crossterm_prompt::{step::{Ask, PromptStep}, PromptOptions};
+//!
+//! let mut stdout = std::io::stdout();
+//! let mut flow = Ask::new("Host: ", PromptOptions::new()).then(|host| {
+//!     Ask::new(format!("Port for {}: ", host), PromptOptions::new())
+//!         .parse::<u16>()
+//!         .with_retry(2)
+//!         .or_default(8080)
+//!         .map(move |port| format!("{}:{}", host, port))
+//! });
+//! let address = flow.run(&mut stdout)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+use anyhow::Result;
+use std::error::Error;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use crate::PromptOptions;
+
+/// A step in an interactive flow that produces a value.
+pub trait PromptStep {
+    /// Value produced by the step.
+    type Output;
+
+    /// Run the step.
+    fn run<W: Write>(&mut self, writer: &mut W) -> Result<Self::Output>;
+
+    /// Run another step created from the value of this step.
+    fn then<F, S>(self, next: F) -> Then<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(Self::Output) -> S,
+        S: PromptStep,
+    {
+        Then { step: self, next }
+    }
+
+    /// Transform the value of this step.
+    fn map<F, T>(self, map: F) -> Map<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(Self::Output) -> T,
+    {
+        Map { step: self, map }
+    }
+
+    /// Run this step again when it fails, up to `retries` times.
+    ///
+    /// A prompt that is [Aborted](crate::Aborted), reaches the
+    /// [EndOfInput](crate::EndOfInput) or fails to read or write
+    /// the terminal is not retried.
+    fn with_retry(self, retries: usize) -> Retry<Self>
+    where
+        Self: Sized,
+    {
+        Retry {
+            step: self,
+            retries,
+        }
+    }
+
+    /// Use a default value when this step fails.
+    ///
+    /// Like [PromptStep::with_retry] the errors of an aborted
+    /// prompt, the end of input and terminal errors are returned
+    /// instead of the default value.
+    fn or_default(self, value: Self::Output) -> OrDefault<Self>
+    where
+        Self: Sized,
+        Self::Output: Clone,
+    {
+        OrDefault { step: self, value }
+    }
+}
+
+/// Step that shows a prompt.
+pub struct Ask {
+    prefix: String,
+    options: PromptOptions,
+}
+
+impl Ask {
+    /// Create a step showing a prompt with options.
+    pub fn new<S: Into<String>>(prefix: S, options: PromptOptions) -> Self {
+        Self {
+            prefix: prefix.into(),
+            options,
+        }
+    }
+
+    /// Parse the value of the prompt to another type.
+    pub fn parse<T>(self) -> Parse<T> {
+        Parse {
+            ask: self,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl PromptStep for Ask {
+    type Output = String;
+
+    fn run<W: Write>(&mut self, writer: &mut W) -> Result<Self::Output> {
+        crate::prompt(&self.prefix, writer, &self.options)
+    }
+}
+
+/// Step that shows a prompt and parses the value.
+pub struct Parse<T> {
+    ask: Ask,
+    marker: PhantomData<T>,
+}
+
+impl<T> PromptStep for Parse<T>
+where
+    T: FromStr,
+    <T as FromStr>::Err: Error + Sync + Send + 'static,
+{
+    type Output = T;
+
+    fn run<W: Write>(&mut self, writer: &mut W) -> Result<Self::Output> {
+        crate::parse(&self.ask.prefix, writer, &self.ask.options)
+    }
+}
+
+/// Step created by [PromptStep::then].
+pub struct Then<P, F> {
+    step: P,
+    next: F,
+}
+
+impl<P, F, S> PromptStep for Then<P, F>
+where
+    P: PromptStep,
+    F: FnMut(P::Output) -> S,
+    S: PromptStep,
+{
+    type Output = S::Output;
+
+    fn run<W: Write>(&mut self, writer: &mut W) -> Result<Self::Output> {
+        let value = self.step.run(writer)?;
+        (self.next)(value).run(writer)
+    }
+}
+
+/// Step created by [PromptStep::map].
+pub struct Map<P, F> {
+    step: P,
+    map: F,
+}
+
+impl<P, F, T> PromptStep for Map<P, F>
+where
+    P: PromptStep,
+    F: FnMut(P::Output) -> T,
+{
+    type Output = T;
+
+    fn run<W: Write>(&mut self, writer: &mut W) -> Result<Self::Output> {
+        self.step.run(writer).map(&mut self.map)
+    }
+}
+
+/// Step created by [PromptStep::with_retry].
+pub struct Retry<P> {
+    step: P,
+    retries: usize,
+}
+
+impl<P: PromptStep> PromptStep for Retry<P> {
+    type Output = P::Output;
+
+    fn run<W: Write>(&mut self, writer: &mut W) -> Result<Self::Output> {
+        let mut attempts = 0;
+        loop {
+            match self.step.run(writer) {
+                Err(e) if attempts < self.retries && !is_final(&e) => {
+                    attempts += 1
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Step created by [PromptStep::or_default].
+pub struct OrDefault<P: PromptStep> {
+    step: P,
+    value: P::Output,
+}
+
+impl<P> PromptStep for OrDefault<P>
+where
+    P: PromptStep,
+    P::Output: Clone,
+{
+    type Output = P::Output;
+
+    fn run<W: Write>(&mut self, writer: &mut W) -> Result<Self::Output> {
+        match self.step.run(writer) {
+            Err(e) if !is_final(&e) => Ok(self.value.clone()),
+            result => result,
+        }
+    }
+}
+
+/// Determine if an error ends the flow, which is when the prompt
+/// was aborted, the input ended or the terminal failed.
+fn is_final(e: &anyhow::Error) -> bool {
+    e.is::<crate::Aborted>()
+        || e.is::<crate::EndOfInput>()
+        || e.is::<std::io::Error>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::bail;
+    use std::collections::VecDeque;
+
+    /// Step answering with queued values.
+    struct Answers(VecDeque<&'static str>);
+
+    impl PromptStep for Answers {
+        type Output = u16;
+
+        fn run<W: Write>(&mut self, _writer: &mut W) -> Result<u16> {
+            match self.0.pop_front() {
+                Some(value) => Ok(value.parse()?),
+                None => bail!("no more answers"),
+            }
+        }
+    }
+
+    fn answers(values: &[&'static str]) -> Answers {
+        Answers(values.iter().copied().collect())
+    }
+
+    #[test]
+    fn step_retry_and_default() -> Result<()> {
+        let mut writer = Vec::new();
+        let mut step = answers(&["x", "y", "3"]).with_retry(2);
+        assert_eq!(3, step.run(&mut writer)?);
+
+        let mut step = answers(&["x", "3"]).with_retry(0).or_default(7);
+        assert_eq!(7, step.run(&mut writer)?);
+        Ok(())
+    }
+
+    /// Step that is aborted each time it runs.
+    struct Cancelled(usize);
+
+    impl PromptStep for Cancelled {
+        type Output = u16;
+
+        fn run<W: Write>(&mut self, _writer: &mut W) -> Result<u16> {
+            self.0 += 1;
+            Err(crate::Aborted(crate::AbortReason::Cancelled).into())
+        }
+    }
+
+    #[test]
+    fn step_retry_aborted() {
+        let mut writer = Vec::new();
+        let mut step = Cancelled(0).with_retry(3);
+        assert!(step.run(&mut writer).unwrap_err().is::<crate::Aborted>());
+        assert_eq!(1, step.step.0);
+    }
+
+    /// Step failing with an error each time it runs.
+    struct Failing(fn() -> anyhow::Error);
+
+    impl PromptStep for Failing {
+        type Output = u16;
+
+        fn run<W: Write>(&mut self, _writer: &mut W) -> Result<u16> {
+            Err((self.0)())
+        }
+    }
+
+    #[test]
+    fn step_default_aborted() {
+        let mut writer = Vec::new();
+        let mut step = Cancelled(0).or_default(7);
+        assert!(step.run(&mut writer).unwrap_err().is::<crate::Aborted>());
+
+        let mut step = Failing(|| crate::EndOfInput.into()).or_default(7);
+        assert!(step.run(&mut writer).unwrap_err().is::<crate::EndOfInput>());
+
+        let mut step = Failing(|| std::io::Error::other("closed").into())
+            .with_retry(2)
+            .or_default(7);
+        assert!(step.run(&mut writer).unwrap_err().is::<std::io::Error>());
+    }
+
+    #[test]
+    fn step_then_and_map() -> Result<()> {
+        let mut writer = Vec::new();
+        let mut step = answers(&["2"])
+            .then(|count| answers(&["5"]).map(move |value| value * count));
+        assert_eq!(10, step.run(&mut writer)?);
+        Ok(())
+    }
+}
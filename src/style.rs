@@ -0,0 +1,352 @@
+//! Styled text for prompt prefixes and highlighting of the value.
+//!
+//! A [Theme] sets the styles of each part of the prompt, a
+//! [CursorStyle] sets the shape of the cursor and a [TabStyle]
+//! sets how tabs in the value are drawn.
+//!
+//! With the `styling` feature a `StyledText` is built from crossterm
+//! styled content so a prefix can be colored or bold, a `Highlighter`
+//! styles parts of the value each time it is drawn and the theme of
+//! a prompt can be changed. The escape sequences that style a prefix
+//! are not counted in its width with or without the feature.
+use anyhow::Result;
+use crossterm::style::{
+    Attribute, Color, Colors, ContentStyle, SetAttribute, SetAttributes,
+    SetColors,
+};
+use crossterm::{Command, QueueableCommand};
+use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+
+#[cfg(any(feature = "styling", doc))]
+use crossterm::style::StyledContent;
+
+/// Text made of spans that may be styled.
+///
+/// ```
+/// use crossterm::style::Stylize;
+/// use crossterm_prompt::style::StyledText;
+///
+/// let prefix = StyledText::new().push("user".green().bold()).push("> ");
+/// ```
+#[cfg(any(feature = "styling", doc))]
+#[doc(cfg(feature = "styling"))]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct StyledText {
+    text: String,
+}
+
+#[cfg(any(feature = "styling", doc))]
+impl StyledText {
+    /// Create empty text.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Append a span, either styled content or plain text.
+    pub fn push<D: fmt::Display>(mut self, span: D) -> Self {
+        self.text.push_str(&span.to_string());
+        self
+    }
+}
+
+#[cfg(any(feature = "styling", doc))]
+impl<D: fmt::Display> From<StyledContent<D>> for StyledText {
+    fn from(content: StyledContent<D>) -> Self {
+        Self::new().push(content)
+    }
+}
+
+#[cfg(any(feature = "styling", doc))]
+impl AsRef<str> for StyledText {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+#[cfg(any(feature = "styling", doc))]
+impl fmt::Display for StyledText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Style for a range of bytes in the value.
+pub type Span = (Range<usize>, ContentStyle);
+
+/// Highlighting applied to the value each time it is drawn.
+///
+/// Closures that take the value and the grapheme index of the
+/// cursor are highlighters:
+///
+/// ```
+/// use crossterm::style::{ContentStyle, Stylize};
+/// use crossterm_prompt::prelude::*;
+///
+/// let options = PromptOptions::new().highlighter(|line: &str, _| {
+///     let command = line.find(' ').unwrap_or(line.len());
+///     vec![(0..command, ContentStyle::new().green())]
+/// });
+/// ```
+#[cfg(any(feature = "styling", doc))]
+#[doc(cfg(feature = "styling"))]
+pub trait Highlighter {
+    /// Styles for ranges of bytes in the line, where ranges
+    /// overlap the last span is used.
+    ///
+    /// Bytes that are not in a span are drawn without a style;
+    /// the value is never highlighted while it is masked.
+    fn highlight(&self, line: &str, cursor: usize) -> Vec<Span>;
+
+    /// Whether the spans depend on the cursor, for example to
+    /// highlight a matching bracket, so the value is drawn
+    /// again when the cursor moves.
+    fn follows_cursor(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(any(feature = "styling", doc))]
+impl<F> Highlighter for F
+where
+    F: Fn(&str, usize) -> Vec<Span>,
+{
+    fn highlight(&self, line: &str, cursor: usize) -> Vec<Span> {
+        (self)(line, cursor)
+    }
+}
+
+/// Styles of the parts of a prompt.
+///
+/// Prompts are drawn with the dark theme unless the theme is
+/// changed with the `styling` feature:
+///
+/// ```
+/// use crossterm::style::{Color, Stylize};
+/// use crossterm_prompt::style::Theme;
+///
+/// let mut theme = Theme::light();
+/// theme.prefix = theme.prefix.with(Color::Blue).bold();
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Theme {
+    /// Style of the prefix.
+    pub prefix: ContentStyle,
+    /// Style of the value, spans of a highlighter are
+    /// drawn over it.
+    pub input: ContentStyle,
+    /// Style of the placeholder.
+    pub placeholder: ContentStyle,
+    /// Style of the region between the mark and the cursor.
+    pub selection: ContentStyle,
+    /// Style of the candidates in the completion menu.
+    pub menu: ContentStyle,
+    /// Style of the selected candidate in the completion menu.
+    pub menu_selected: ContentStyle,
+    /// Style of messages below the prompt such as
+    /// validation errors.
+    pub message: ContentStyle,
+    /// Style of the status line below the value.
+    pub status: ContentStyle,
+}
+
+impl Theme {
+    /// Theme for terminals with a dark background.
+    pub fn dark() -> Self {
+        Self {
+            prefix: ContentStyle::new(),
+            input: ContentStyle::new(),
+            placeholder: attribute(Attribute::Dim),
+            selection: attribute(Attribute::Reverse),
+            menu: ContentStyle::new(),
+            menu_selected: attribute(Attribute::Reverse),
+            message: foreground(Color::Yellow),
+            status: attribute(Attribute::Dim),
+        }
+    }
+
+    /// Theme for terminals with a light background.
+    pub fn light() -> Self {
+        Self {
+            placeholder: foreground(Color::DarkGrey),
+            message: foreground(Color::DarkYellow),
+            status: foreground(Color::DarkGrey),
+            ..Self::dark()
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Shape of the terminal cursor.
+///
+/// Terminals that do not support changing the shape of the
+/// cursor ignore it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CursorStyle {
+    /// Shape configured by the user of the terminal.
+    Default,
+    /// Blinking block.
+    BlinkingBlock,
+    /// Steady block.
+    SteadyBlock,
+    /// Blinking underline.
+    BlinkingUnderline,
+    /// Steady underline.
+    SteadyUnderline,
+    /// Blinking vertical bar.
+    BlinkingBar,
+    /// Steady vertical bar.
+    SteadyBar,
+}
+
+impl Command for CursorStyle {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        let code = match self {
+            Self::Default => 0,
+            Self::BlinkingBlock => 1,
+            Self::SteadyBlock => 2,
+            Self::BlinkingUnderline => 3,
+            Self::SteadyUnderline => 4,
+            Self::BlinkingBar => 5,
+            Self::SteadyBar => 6,
+        };
+        write!(f, "\x1B[{} q", code)
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> crossterm::Result<()> {
+        Ok(())
+    }
+}
+
+/// How a tab in the value is drawn.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TabStyle {
+    /// A number of spaces.
+    Spaces(usize),
+    /// Caret notation, `^I`.
+    Caret,
+}
+
+impl TabStyle {
+    /// Text drawn for a tab.
+    pub(crate) fn text(&self) -> String {
+        match self {
+            Self::Spaces(count) => " ".repeat(*count),
+            Self::Caret => "^I".to_string(),
+        }
+    }
+}
+
+impl Default for TabStyle {
+    fn default() -> Self {
+        Self::Spaces(4)
+    }
+}
+
+/// Style with a single attribute.
+fn attribute(attribute: Attribute) -> ContentStyle {
+    let mut style = ContentStyle::new();
+    style.attributes.set(attribute);
+    style
+}
+
+/// Style with a foreground color.
+fn foreground(color: Color) -> ContentStyle {
+    ContentStyle {
+        foreground_color: Some(color),
+        ..ContentStyle::new()
+    }
+}
+
+/// Determine if a style leaves text unchanged.
+pub(crate) fn is_plain(style: &ContentStyle) -> bool {
+    *style == ContentStyle::new()
+}
+
+/// Style drawn over another, the colors that are set replace
+/// the colors below and the attributes are combined.
+pub(crate) fn merge(below: ContentStyle, over: ContentStyle) -> ContentStyle {
+    ContentStyle {
+        foreground_color: over.foreground_color.or(below.foreground_color),
+        background_color: over.background_color.or(below.background_color),
+        attributes: below.attributes | over.attributes,
+    }
+}
+
+/// Write the sequences that apply a style.
+pub(crate) fn queue_style<W: Write>(
+    writer: &mut W,
+    style: &ContentStyle,
+) -> Result<()> {
+    writer.queue(SetColors(Colors {
+        foreground: style.foreground_color,
+        background: style.background_color,
+    }))?;
+    writer.queue(SetAttributes(style.attributes))?;
+    Ok(())
+}
+
+/// Write text in a style, resetting the style afterwards.
+pub(crate) fn write_styled<W: Write>(
+    writer: &mut W,
+    style: &ContentStyle,
+    text: &str,
+) -> Result<()> {
+    if is_plain(style) {
+        writer.write_all(text.as_bytes())?;
+    } else {
+        queue_style(writer, style)?;
+        writer.write_all(text.as_bytes())?;
+        writer.queue(SetAttribute(Attribute::Reset))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TerminalBuffer;
+    use crossterm::style::Stylize;
+
+    #[test]
+    fn theme_styles() {
+        let highlight = ContentStyle::new().green();
+        let merged = merge(Theme::dark().selection, highlight);
+        assert_eq!(Some(Color::Green), merged.foreground_color);
+        assert!(merged.attributes.has(Attribute::Reverse));
+        assert!(is_plain(&Theme::light().input));
+
+        let mut out = Vec::new();
+        let mut buf = TerminalBuffer::new("> ", None);
+        let mut theme = Theme::light();
+        theme.prefix = theme.prefix.bold();
+        buf.set_theme(theme);
+        buf.write_prefix(&mut out).unwrap();
+        assert_eq!("\x1B[1m> \x1B[0m", String::from_utf8_lossy(&out));
+    }
+
+    #[test]
+    fn cursor_style() {
+        let mut out = Vec::new();
+        out.queue(CursorStyle::SteadyBar).unwrap();
+        out.queue(CursorStyle::BlinkingBlock).unwrap();
+        out.queue(CursorStyle::Default).unwrap();
+        assert_eq!("\x1B[6 q\x1B[1 q\x1B[0 q", String::from_utf8_lossy(&out));
+    }
+
+    #[cfg(feature = "styling")]
+    #[test]
+    fn styled_prefix_width() {
+        let prefix = StyledText::from("日本".red().bold()).push("> ");
+        assert!(prefix.as_ref().starts_with('\x1B'));
+        let buf = TerminalBuffer::new(prefix.as_ref(), None);
+        assert_eq!(6, buf.prefix_columns());
+    }
+}
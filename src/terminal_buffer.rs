@@ -7,7 +7,8 @@
 use anyhow::Result;
 use crossterm::{
     cursor,
-    terminal::{Clear, ClearType},
+    style::{Attribute, ContentStyle, SetAttribute},
+    terminal::{Clear, ClearType, ScrollUp},
     QueueableCommand,
 };
 use std::borrow::Cow;
@@ -15,28 +16,77 @@ use std::io::Write;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
-/// Internal buffer for a string that operates on columns
+use crate::style::{self, TabStyle, Theme};
+
+#[cfg(feature = "styling")]
+use crate::style::Highlighter;
+
+/// Change to the case of a word.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum WordCase {
+    /// Convert the word to upper case.
+    Upper,
+    /// Convert the word to lower case.
+    Lower,
+    /// Convert the first letter to upper case and the rest
+    /// of the word to lower case.
+    Capital,
+}
+
+/// Buffer for a string that operates on columns
 /// and rows and may include a prefix to the buffer value.
+///
+/// The cursor is exposed as a grapheme index into the buffer
+/// with helpers to convert to and from terminal positions.
+///
+/// Values wider than the terminal wrap onto the following rows,
+/// each line of a value with multiple lines starts a new row.
 pub struct TerminalBuffer<'a> {
     prefix: &'a str,
     buffer: String,
     prefix_cols: usize,
     buffer_cols: usize,
     echo: Option<char>,
+    revealed: bool,
+    shown: Option<usize>,
+    kill_ring: Vec<String>,
+    mark: Option<usize>,
+    #[cfg(feature = "styling")]
+    highlighter: Option<&'a dyn Highlighter>,
+    placeholder: Option<&'a str>,
+    status: Option<String>,
+    theme: Theme,
+    tab_style: TabStyle,
+    drawn: Option<Vec<Vec<Cell>>>,
+    cursor: usize,
+    first_row: u16,
     size: (u16, u16),
     position: (u16, u16),
 }
 
 impl<'a> TerminalBuffer<'a> {
     /// Create a new buffer using the given prefix and mask character.
-    pub fn new(prefix: &'a str, echo: Option<char>) -> Self {
-        let prefix_cols: usize = UnicodeWidthStr::width(prefix);
+    pub(crate) fn new(prefix: &'a str, echo: Option<char>) -> Self {
+        let prefix_cols = visible_width(prefix);
         Self {
             prefix,
             prefix_cols,
             buffer: String::new(),
             buffer_cols: 0,
             echo,
+            revealed: false,
+            shown: None,
+            kill_ring: Vec::new(),
+            mark: None,
+            #[cfg(feature = "styling")]
+            highlighter: None,
+            placeholder: None,
+            status: None,
+            theme: Theme::default(),
+            tab_style: TabStyle::default(),
+            drawn: None,
+            cursor: 0,
+            first_row: 0,
             size: (0, 0),
             position: (0, 0),
         }
@@ -52,46 +102,347 @@ impl<'a> TerminalBuffer<'a> {
         self.prefix_cols
     }
 
-    /*
     /// Get the number of columns for the buffer.
     pub fn buffer_columns(&self) -> usize {
         self.buffer_cols
     }
-    */
 
     /// Get the total column width for the prefix and buffer.
     pub fn columns(&self) -> usize {
         self.prefix_cols + self.buffer_cols
     }
 
+    /// Determine if a masked buffer is currently revealed.
+    pub fn is_revealed(&self) -> bool {
+        self.revealed
+    }
+
+    /// Set whether a masked buffer is revealed.
+    pub(crate) fn set_revealed(&mut self, revealed: bool) {
+        self.revealed = revealed;
+        self.buffer_cols = self.display_width(&self.buffer);
+        self.position = self.position_of(self.cursor);
+    }
+
+    /// Grapheme index of a masked grapheme drawn as it was typed.
+    pub(crate) fn shown(&self) -> Option<usize> {
+        self.shown
+    }
+
+    /// Draw a masked grapheme as it was typed, or mask it again,
+    /// the next time the value is drawn.
+    pub(crate) fn set_shown(&mut self, index: Option<usize>) {
+        self.shown = index;
+        self.buffer_cols = self.display_width(&self.buffer);
+        self.position = self.position_of(self.cursor);
+    }
+
+    /// Determine if the buffer is displayed using the echo character.
+    pub fn is_masked(&self) -> bool {
+        self.echo.is_some() && !self.revealed
+    }
+
+    /// Get the number of columns used to display the buffer or
+    /// a new value of the buffer.
+    fn display_width(&self, value: &str) -> usize {
+        UnicodeSegmentation::graphemes(value, true)
+            .enumerate()
+            .map(|(index, grapheme)| self.cell_width(index, grapheme))
+            .sum()
+    }
+
+    /// Get the number of columns used to display a grapheme
+    /// at an index into the buffer.
+    ///
+    /// When the buffer is masked each grapheme is displayed
+    /// as a single echo character except a grapheme shown
+    /// as it was typed.
+    fn cell_width(&self, index: usize, grapheme: &str) -> usize {
+        match self.echo {
+            Some(echo) if self.is_masked() && self.shown != Some(index) => {
+                UnicodeWidthStr::width(echo.to_string().as_str())
+            }
+            _ if grapheme == "\t" => self.tab_style.text().len(),
+            _ => grapheme_width(grapheme),
+        }
+    }
+
+    /// Set how tabs in the value are drawn.
+    pub(crate) fn set_tab_style(&mut self, tab_style: TabStyle) {
+        self.tab_style = tab_style;
+        self.buffer_cols = self.display_width(&self.buffer);
+    }
+
+    /// Set the highlighter that styles the value when it is drawn.
+    #[cfg(feature = "styling")]
+    pub(crate) fn set_highlighter(
+        &mut self,
+        highlighter: Option<&'a dyn Highlighter>,
+    ) {
+        self.highlighter = highlighter;
+    }
+
+    /// Set the hint drawn while the value is empty.
+    pub(crate) fn set_placeholder(&mut self, placeholder: Option<&'a str>) {
+        self.placeholder = placeholder;
+    }
+
+    /// Set the status line drawn below the value and draw it
+    /// again when it changed.
+    pub(crate) fn set_status<W>(
+        &mut self,
+        writer: &mut W,
+        status: Option<String>,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        if status != self.status {
+            self.status = status;
+            self.position = self.position_of(self.cursor);
+            self.redraw(writer, self.position)?;
+        }
+        Ok(())
+    }
+
+    /// Styles the prompt is drawn with.
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Set the styles the prompt is drawn with.
+    pub(crate) fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Determine if the value is drawn again when the cursor moves.
+    pub(crate) fn follows_cursor(&self) -> bool {
+        #[cfg(feature = "styling")]
+        return self.highlighter.is_some_and(|h| h.follows_cursor());
+        #[cfg(not(feature = "styling"))]
+        false
+    }
+
+    /// Get the terminal size.
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    /// Get the cursor position.
+    pub fn position(&self) -> (u16, u16) {
+        self.position
+    }
+
     /// Set the terminal size.
-    pub fn set_size(&mut self, size: (u16, u16)) {
+    pub(crate) fn set_size(&mut self, size: (u16, u16)) {
         self.size = size;
     }
 
-    /// Set the cursor position.
-    pub fn set_position(&mut self, position: (u16, u16)) {
-        self.position = position;
+    /// Reflow the value for a new terminal size, clearing the
+    /// rows it was drawn on and drawing it again.
+    ///
+    /// The position is where the terminal reports the cursor
+    /// after it was resized.
+    pub(crate) fn resize<W>(
+        &mut self,
+        writer: &mut W,
+        size: (u16, u16),
+        position: (u16, u16),
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let old_row = self.cursor_row();
+        self.size = size;
+        let row = self.cursor_row();
+
+        // Clear from the highest row the value may start on
+        let top = position.1.saturating_sub(old_row.max(row) as u16);
+        writer.queue(cursor::MoveTo(0, top))?;
+        writer.queue(Clear(ClearType::FromCursorDown))?;
+        self.drawn = None;
+
+        self.first_row = position.1.saturating_sub(row as u16);
+        self.position = self.position_of(self.cursor);
+        self.redraw(writer, self.position)
+    }
+
+    /// Erase the prompt and print text in its place, drawing the
+    /// prompt again on the row below the text.
+    pub(crate) fn print_above<W>(
+        &mut self,
+        writer: &mut W,
+        text: &str,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        writer.queue(cursor::MoveTo(0, self.first_row))?;
+        writer.queue(Clear(ClearType::FromCursorDown))?;
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        let mut rows = 0;
+        for line in text.split('\n') {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(b"\r\n")?;
+            rows += match self.size.0 as usize {
+                0 => 1,
+                width => visible_width(line).max(1).div_ceil(width),
+            };
+        }
+        // The terminal scrolls when the text reaches the last row
+        let row = (self.first_row as usize + rows)
+            .min(self.size.1.saturating_sub(1) as usize);
+        self.move_to_row(row as u16);
+        self.redraw(writer, self.position)
+    }
+
+    /// Draw the value from the start of a row on the next redraw,
+    /// for example after another program wrote to the terminal.
+    pub(crate) fn move_to_row(&mut self, row: u16) {
+        self.first_row = row;
+        self.drawn = None;
+        self.position = self.position_of(self.cursor);
+    }
+
+    /// Move the cursor to a grapheme index.
+    pub(crate) fn move_to<W>(
+        &mut self,
+        writer: &mut W,
+        index: usize,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        self.cursor = index.min(self.len());
+        self.position = self.position_of(self.cursor);
+        writer.queue(cursor::MoveTo(self.position.0, self.position.1))?;
+        Ok(())
+    }
+
+    /// Move the cursor to the end of the value before leaving the
+    /// prompt, removing the placeholder and the status line.
+    pub(crate) fn move_to_end<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        if self.buffer.is_empty() && self.placeholder.is_some() {
+            writer.queue(Clear(ClearType::UntilNewLine))?;
+        }
+        // Leave no status line or typed password character behind
+        if self.status.take().is_some() || self.shown.is_some() {
+            self.set_shown(None);
+            self.redraw(writer, self.position)?;
+        }
+        self.move_to(writer, self.len())
+    }
+
+    /// Draw the value again after another prefix in place of the
+    /// prompt, returning the position after the value.
+    pub(crate) fn collapse<W>(
+        &self,
+        writer: &mut W,
+        prefix: &str,
+    ) -> Result<(u16, u16)>
+    where
+        W: Write,
+    {
+        let mut collapsed = TerminalBuffer::new(prefix, self.echo);
+        collapsed.buffer = self.buffer.clone();
+        collapsed.buffer_cols = self.buffer_cols;
+        collapsed.tab_style = self.tab_style;
+        collapsed.size = self.size;
+        collapsed.first_row = self.first_row;
+        collapsed.cursor = collapsed.len();
+        let position = collapsed.position_of(collapsed.cursor);
+        writer.queue(cursor::MoveTo(0, self.first_row))?;
+        writer.queue(Clear(ClearType::FromCursorDown))?;
+        collapsed.redraw(writer, position)?;
+        Ok(position)
     }
 
     /// Update the buffer to a new value.
+    ///
+    /// Changing the value deactivates the mark.
     fn update(&mut self, value: String) {
-        self.buffer_cols = UnicodeWidthStr::width(&value[..]);
+        if value != self.buffer {
+            self.mark = None;
+        }
+        self.buffer_cols = self.display_width(&value);
         self.buffer = value;
     }
 
-    /// Push a character onto the buffer and write it but do not flush
-    /// the stream.
-    ///
-    /// This should only be used for control characters and newlines
-    /// as it does not respect the masking of visible characters when
-    /// echo has been set.
-    pub fn push<W>(&mut self, writer: &mut W, c: char) -> Result<()>
+    /// Grapheme index of the mark while it is active.
+    pub fn mark(&self) -> Option<usize> {
+        self.mark
+    }
+
+    /// Set the mark at the cursor.
+    pub(crate) fn set_mark(&mut self) {
+        self.mark = Some(self.cursor());
+    }
+
+    /// Start and end grapheme indices of the text between
+    /// the mark and the cursor.
+    pub fn region(&self) -> Option<(usize, usize)> {
+        let mark = self.mark?;
+        let cursor = self.cursor();
+        Some((mark.min(cursor), mark.max(cursor)))
+    }
+
+    /// Text between two grapheme indices.
+    pub fn text(&self, start: usize, end: usize) -> String {
+        let graphemes = self.graphemes();
+        let end = end.min(graphemes.len());
+        graphemes[start.min(end)..end].join("")
+    }
+
+    /// Move the cursor to the mark and set the mark where
+    /// the cursor was.
+    pub(crate) fn exchange_point_and_mark<W>(
+        &mut self,
+        writer: &mut W,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        if let Some(mark) = self.mark {
+            self.mark = Some(self.cursor);
+            self.cursor = mark;
+            self.position = self.position_of(mark);
+            self.redraw(writer, self.position)?;
+        }
+        Ok(())
+    }
+
+    /// Erase the region onto the kill ring.
+    pub(crate) fn kill_region<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        if let Some((start, end)) = self.region() {
+            let text = self.text(start, end);
+            let graphemes = self.graphemes();
+            let mut buffer = graphemes[..start].join("");
+            buffer.push_str(&graphemes[end..].join(""));
+            self.kill(&text);
+            self.mark = None;
+            self.replace(writer, buffer, start)?;
+        }
+        Ok(())
+    }
+
+    /// Copy the region onto the kill ring and deactivate the mark.
+    pub(crate) fn copy_region<W>(&mut self, writer: &mut W) -> Result<()>
     where
         W: Write,
     {
-        self.buffer.push(c);
-        writer.write(c.to_string().as_bytes())?;
+        if let Some((start, end)) = self.region() {
+            let text = self.text(start, end);
+            self.kill(&text);
+            self.mark = None;
+            self.redraw(writer, self.position)?;
+        }
         Ok(())
     }
 
@@ -101,31 +452,297 @@ impl<'a> TerminalBuffer<'a> {
             .collect::<Vec<&str>>()
     }
 
-    /// Erase the word before the cursor.
-    pub fn erase_word_before<W>(&mut self, writer: &mut W) -> Result<()>
+    /// Get the number of graphemes in the buffer.
+    pub fn len(&self) -> usize {
+        self.graphemes().len()
+    }
+
+    /// Determine if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Get the cursor as a grapheme index into the buffer.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Grapheme index of the start of the line the cursor is on.
+    pub fn line_start(&self) -> usize {
+        let (before, _) = self.split_at_cursor();
+        let start = before.rfind('\n').map_or(0, |index| index + 1);
+        UnicodeSegmentation::graphemes(&before[..start], true).count()
+    }
+
+    /// Grapheme index of the end of the line the cursor is on.
+    pub fn line_end(&self) -> usize {
+        let (before, after) = self.split_at_cursor();
+        let end = after.find('\n').unwrap_or(after.len());
+        UnicodeSegmentation::graphemes(&before[..], true).count()
+            + UnicodeSegmentation::graphemes(&after[..end], true).count()
+    }
+
+    /// Row, counted from the row of the prefix, and column where
+    /// each grapheme of a value is drawn followed by the position
+    /// after the value.
+    ///
+    /// A grapheme that does not fit on a row wraps to the next row;
+    /// when the terminal size is not known the value is not wrapped.
+    fn layout(&self, value: &str) -> Vec<(usize, usize)> {
+        let width = self.size.0 as usize;
+        let (mut row, mut column) = self.value_start();
+        let mut cells = Vec::with_capacity(value.len() + 1);
+        for (index, grapheme) in
+            UnicodeSegmentation::graphemes(value, true).enumerate()
+        {
+            if grapheme == "\n" && !self.is_masked() {
+                cells.push((row, column));
+                row += 1;
+                column = 0;
+                continue;
+            }
+            let grapheme_width = self.cell_width(index, grapheme);
+            if width > 0 && column + grapheme_width > width {
+                row += 1;
+                column = 0;
+            }
+            cells.push((row, column));
+            column += grapheme_width;
+        }
+        if width > 0 && column >= width {
+            row += 1;
+            column = 0;
+        }
+        cells.push((row, column));
+        cells
+    }
+
+    /// Row of the cursor counted from the row of the prefix.
+    fn cursor_row(&self) -> usize {
+        let cells = self.layout(&self.buffer);
+        cells[self.cursor.min(cells.len() - 1)].0
+    }
+
+    /// Scroll the terminal when the rows do not fit below the
+    /// prefix, returning the number of rows scrolled.
+    fn scroll_into_view<W>(&mut self, writer: &mut W) -> Result<u16>
+    where
+        W: Write,
+    {
+        let (_, height) = self.size;
+        let bottom = self.first_row as usize
+            + self.rows()
+            + self.status.is_some() as usize;
+        let mut scroll = 0;
+        if height > 0 && bottom > height as usize {
+            scroll =
+                (bottom - height as usize).min(self.first_row as usize) as u16;
+            if scroll > 0 {
+                writer.queue(ScrollUp(scroll))?;
+                self.scrolled(scroll);
+            }
+        }
+        Ok(scroll)
+    }
+
+    /// Number of rows the prefix and buffer are drawn on.
+    pub fn rows(&self) -> usize {
+        self.layout(&self.buffer)
+            .last()
+            .map_or(0, |(row, _)| row + 1)
+    }
+
+    /// Number of rows drawn below the row of the cursor,
+    /// including the status line.
+    pub(crate) fn rows_below(&self) -> u16 {
+        let rows = self.rows() + self.status.is_some() as usize;
+        (rows - 1 - self.cursor_row()).min(u16::MAX as usize) as u16
+    }
+
+    /// Move the rows of the value up after the terminal scrolled.
+    pub(crate) fn scrolled(&mut self, rows: u16) {
+        self.first_row = self.first_row.saturating_sub(rows);
+        self.position.1 = self.position.1.saturating_sub(rows);
+    }
+
+    /// Convert a terminal column to a grapheme index into the buffer.
+    ///
+    /// Columns inside the prefix map to the start of the buffer,
+    /// columns past the end map to the end of the buffer and
+    /// a column inside a wide grapheme maps to the next grapheme.
+    ///
+    /// For a value drawn on multiple rows the column is on the
+    /// last row.
+    pub fn index_at_column(&self, column: u16) -> usize {
+        let cells = self.layout(&self.buffer);
+        let (row, _) = cells[cells.len() - 1];
+        index_in_row(&cells, row, column)
+    }
+
+    /// Convert a grapheme index into the buffer to a terminal column.
+    ///
+    /// Indices past the end of the buffer map to the column
+    /// after the last grapheme.
+    pub fn column_of(&self, index: usize) -> u16 {
+        self.position_of(index).0
+    }
+
+    /// Convert a grapheme index into the buffer to a terminal
+    /// position.
+    pub fn position_of(&self, index: usize) -> (u16, u16) {
+        let cells = self.layout(&self.buffer);
+        let (row, column) = cells[index.min(cells.len() - 1)];
+        (
+            column.min(u16::MAX as usize) as u16,
+            self.first_row.saturating_add(row as u16),
+        )
+    }
+
+    /// Split the buffer into the text before and after the cursor.
+    pub fn split_at_cursor(&self) -> (String, String) {
+        let graphemes = self.graphemes();
+        let pos = self.cursor();
+        (graphemes[..pos].join(""), graphemes[pos..].join(""))
+    }
+
+    /// Grapheme index of the end of the word after the cursor.
+    pub fn next_word_end(&self) -> usize {
+        let (before, after) = self.split_at_cursor();
+        let mut end = 0;
+        for (index, word) in after.split_word_bound_indices() {
+            end = index + word.len();
+            if is_word(word) {
+                break;
+            }
+        }
+        UnicodeSegmentation::graphemes(&before[..], true).count()
+            + UnicodeSegmentation::graphemes(&after[..end], true).count()
+    }
+
+    /// Grapheme index of the start of the word before the cursor.
+    pub fn previous_word_start(&self) -> usize {
+        let (before, _) = self.split_at_cursor();
+        let mut start = 0;
+        for (index, word) in before.split_word_bound_indices().rev() {
+            start = index;
+            if is_word(word) {
+                break;
+            }
+        }
+        UnicodeSegmentation::graphemes(&before[..start], true).count()
+    }
+
+    /// Replace the text between a byte offset into the buffer
+    /// and the cursor, moving the cursor to the end of the replacement.
+    pub fn replace_before_cursor<W>(
+        &mut self,
+        writer: &mut W,
+        start: usize,
+        replacement: &str,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let (before, after) = self.split_at_cursor();
+        let mut buffer = before[..start].to_string();
+        buffer.push_str(replacement);
+        let index = UnicodeSegmentation::graphemes(&buffer[..], true).count();
+        buffer.push_str(&after);
+        self.replace(writer, buffer, index)
+    }
+
+    /// Replace the buffer and redraw moving the cursor
+    /// to a grapheme index in the new buffer.
+    pub(crate) fn replace<W>(
+        &mut self,
+        writer: &mut W,
+        value: String,
+        index: usize,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        self.update(value);
+        self.cursor = index.min(self.len());
+        let position = self.position_of(self.cursor);
+        self.position = position;
+        self.redraw(writer, position)
+    }
+
+    /// Erase the word before the cursor onto the kill ring.
+    pub(crate) fn erase_word_before<W>(&mut self, writer: &mut W) -> Result<()>
     where
         W: Write,
     {
         if !self.buffer.is_empty() {
-            let (column, row) = self.position;
-            let after_start = column as usize - self.prefix_cols;
-            let before = &self.buffer[0..after_start];
-            let after = &self.buffer[after_start..];
+            let (before, after) = self.split_at_cursor();
             let mut words = (before.trim_end()).split_word_bounds();
             words.next_back();
             let mut buffer = words.collect::<Vec<&str>>().join("");
-            let new_col: u16 = (self.prefix_cols
-                + UnicodeWidthStr::width(&buffer[..]))
-            .try_into()?;
-            buffer.push_str(after);
-            let position = (new_col, row);
-            self.refresh(writer, buffer, position)?;
+            self.kill(&before[buffer.len()..]);
+            let index =
+                UnicodeSegmentation::graphemes(&buffer[..], true).count();
+            buffer.push_str(&after);
+            self.replace(writer, buffer, index)?;
         }
         Ok(())
     }
 
-    /// Erase a number of columns before the cursor.
-    pub fn erase_before<W>(
+    /// Change the case from the cursor to the end of the word,
+    /// moving the cursor after the word.
+    pub(crate) fn change_word_case<W>(
+        &mut self,
+        writer: &mut W,
+        case: WordCase,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let amount = self.next_word_end() - self.cursor();
+        let (mut before, after) = self.split_at_cursor();
+        let end = after
+            .grapheme_indices(true)
+            .nth(amount)
+            .map_or(after.len(), |(index, _)| index);
+        let (word, rest) = after.split_at(end);
+        match case {
+            WordCase::Upper => before.push_str(&word.to_uppercase()),
+            WordCase::Lower => before.push_str(&word.to_lowercase()),
+            WordCase::Capital => {
+                let mut capital = true;
+                for c in word.chars() {
+                    if capital && c.is_alphanumeric() {
+                        before.extend(c.to_uppercase());
+                        capital = false;
+                    } else {
+                        before.extend(c.to_lowercase());
+                    }
+                }
+            }
+        }
+        let index = UnicodeSegmentation::graphemes(&before[..], true).count();
+        before.push_str(rest);
+        self.replace(writer, before, index)
+    }
+
+    /// Erase the word after the cursor.
+    pub(crate) fn erase_word_after<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        let amount = self.next_word_end() - self.cursor();
+        let (_, after) = self.split_at_cursor();
+        let end = after
+            .grapheme_indices(true)
+            .nth(amount)
+            .map(|(index, _)| index)
+            .unwrap_or(after.len());
+        self.kill(&after[..end]);
+        self.erase_after(writer, amount)
+    }
+
+    /// Erase a number of graphemes before the cursor.
+    pub(crate) fn erase_before<W>(
         &mut self,
         writer: &mut W,
         amount: usize,
@@ -136,8 +753,8 @@ impl<'a> TerminalBuffer<'a> {
         self.erase(writer, amount, true)
     }
 
-    /// Erase a number of columns after the cursor.
-    pub fn erase_after<W>(
+    /// Erase a number of graphemes after the cursor.
+    pub(crate) fn erase_after<W>(
         &mut self,
         writer: &mut W,
         amount: usize,
@@ -148,7 +765,63 @@ impl<'a> TerminalBuffer<'a> {
         self.erase(writer, amount, false)
     }
 
-    /// Erase a number of columns before or after the cursor.
+    /// Erase from the cursor to the beginning of the line
+    /// onto the kill ring.
+    pub(crate) fn kill_before<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        let (before, _) = self.split_at_cursor();
+        self.kill(&before);
+        self.erase_before(writer, self.cursor())
+    }
+
+    /// Erase from the cursor to the end of the line
+    /// onto the kill ring.
+    pub(crate) fn kill_after<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        let (_, after) = self.split_at_cursor();
+        self.kill(&after);
+        self.erase_after(writer, self.len() - self.cursor())
+    }
+
+    /// Erase the whole line onto the kill ring.
+    pub(crate) fn kill_line<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        let line = self.buffer.clone();
+        self.kill(&line);
+        self.replace(writer, String::new(), 0)
+    }
+
+    /// Insert the most recently killed text at the cursor.
+    pub(crate) fn yank<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        if let Some(text) = self.kill_ring.last().cloned() {
+            let (before, after) = self.split_at_cursor();
+            let mut buffer = before;
+            buffer.push_str(&text);
+            let index =
+                UnicodeSegmentation::graphemes(&buffer[..], true).count();
+            buffer.push_str(&after);
+            self.replace(writer, buffer, index)?;
+        }
+        Ok(())
+    }
+
+    /// Push text onto the kill ring.
+    fn kill(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.kill_ring.push(text.to_string());
+        }
+    }
+
+    /// Erase a number of graphemes before or after the cursor.
     fn erase<W>(
         &mut self,
         writer: &mut W,
@@ -159,36 +832,18 @@ impl<'a> TerminalBuffer<'a> {
         W: Write,
     {
         let graphemes = self.graphemes();
-        if graphemes.len() > 0 {
-            // Cursor position relative to start of the buffer
-            let (column, row) = self.position;
-            let (before_end, after_start, new_col) = if before {
-                let after_start = column as usize - self.prefix_columns();
-                let before_end = if after_start >= amount {
-                    after_start - amount
-                } else {
-                    amount
-                };
-                let new_col = self.prefix_cols + (after_start - amount);
-                (before_end, after_start, new_col)
+        if !graphemes.is_empty() {
+            let cursor = self.cursor();
+            let (start, end) = if before {
+                (cursor.saturating_sub(amount), cursor)
             } else {
-                let before_end = column as usize - self.prefix_columns();
-                let after_start = if before_end + amount <= graphemes.len() {
-                    before_end + amount
-                } else {
-                    graphemes.len()
-                };
-                (before_end, after_start, column as usize)
+                (cursor, (cursor + amount).min(graphemes.len()))
             };
 
-            let before_range = 0..before_end;
-            let after_range = after_start..self.buffer_cols;
-
-            let mut new_buf = String::new();
-            new_buf.push_str(&graphemes[before_range].join(""));
-            new_buf.push_str(&graphemes[after_range].join(""));
+            let mut new_buf = graphemes[..start].join("");
+            new_buf.push_str(&graphemes[end..].join(""));
 
-            self.refresh(writer, new_buf, (new_col.try_into()?, row))?;
+            self.replace(writer, new_buf, start)?;
         }
 
         Ok(())
@@ -196,113 +851,1077 @@ impl<'a> TerminalBuffer<'a> {
 
     /// Get a visible representation of the buffer.
     pub fn visible(&'a self) -> Cow<'a, str> {
-        if let Some(echo) = &self.echo {
-            let masked = echo.to_string().repeat(self.buffer_cols);
+        if let (Some(echo), false) = (&self.echo, self.revealed) {
+            let echo = echo.to_string();
+            let masked = self
+                .graphemes()
+                .into_iter()
+                .enumerate()
+                .map(|(index, grapheme)| match self.shown == Some(index) {
+                    true => grapheme,
+                    false => &echo,
+                })
+                .collect();
             Cow::Owned(masked)
         } else {
             Cow::Borrowed(&self.buffer)
         }
     }
 
-    /// Write bytes to the stream and flush.
-    fn write_bytes(&self, writer: &mut dyn Write, bytes: &[u8]) -> Result<()> {
-        writer.write(bytes)?;
-        writer.flush()?;
-        Ok(())
-    }
-
     /// Write the prefix and flush the stream.
-    pub fn write_prefix<W>(&mut self, writer: &mut W) -> Result<()>
+    pub(crate) fn write_prefix<W>(&mut self, writer: &mut W) -> Result<()>
     where
         W: Write,
     {
-        self.write_bytes(writer, self.prefix.as_bytes())
+        style::write_styled(writer, &self.theme.prefix, self.prefix)?;
+        self.write_placeholder(writer)?;
+        self.drawn = None;
+        Ok(())
     }
 
-    /// Redraw the prefix and buffer moving the cursor
-    /// to the given position.
-    pub fn redraw<W>(&self, writer: &mut W, position: (u16, u16)) -> Result<()>
+    /// Write the placeholder dimmed after the prefix when the
+    /// value is empty, leaving the cursor before it.
+    fn write_placeholder<W>(&self, writer: &mut W) -> Result<()>
     where
         W: Write,
     {
-        let (col, row) = position;
-        writer.queue(cursor::MoveTo(0, row))?;
-        writer.queue(Clear(ClearType::CurrentLine))?;
-        writer.write(self.prefix.as_bytes())?;
-        writer.write(self.visible().as_ref().as_bytes())?;
-        writer.queue(cursor::MoveTo(col, row))?;
-        writer.flush()?;
+        if let Some(text) = self.placeholder_text() {
+            style::write_styled(writer, &self.theme.placeholder, &text)?;
+            writer.queue(cursor::MoveLeft(text_width(&text) as u16))?;
+        }
         Ok(())
     }
 
+    /// Placeholder to draw when the value is empty, cut at the
+    /// end of the row so drawing the value on the row removes it.
+    fn placeholder_text(&self) -> Option<String> {
+        let placeholder =
+            self.placeholder.filter(|_| self.buffer.is_empty())?;
+        let available = match self.size.0 as usize {
+            0 => usize::MAX,
+            width => width - self.value_start().1,
+        };
+        let text = fit(placeholder, available);
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// Row the value starts on and its column on the row,
+    /// counted from the row of the prefix.
+    ///
+    /// A wide grapheme of the prefix that does not fit at the end
+    /// of a row is drawn at the start of the next row.
+    fn value_start(&self) -> (usize, usize) {
+        let width = self.size.0 as usize;
+        if width == 0 {
+            return (0, self.prefix_cols);
+        }
+        let (mut row, mut column) = (0, 0);
+        for grapheme in visible_text(self.prefix).graphemes(true) {
+            let grapheme_width = grapheme_width(grapheme);
+            if column + grapheme_width > width {
+                row += 1;
+                column = 0;
+            }
+            column += grapheme_width;
+        }
+        match column >= width {
+            true => (row + 1, 0),
+            false => (row, column),
+        }
+    }
+
+    /// Cells to draw on each row of the value, counted from
+    /// the row of the prefix.
+    fn frame(&self) -> Vec<Vec<Cell>> {
+        let cells = self.layout(&self.buffer);
+        let mut frame = vec![Vec::new(); cells[cells.len() - 1].0 + 1];
+        if let Some(text) = self.placeholder_text() {
+            let (row, column) = self.value_start();
+            frame[row].push(Cell {
+                column,
+                width: text_width(&text),
+                text,
+                pen: Pen::new(self.theme.placeholder),
+            });
+            self.push_status(&mut frame);
+            return frame;
+        }
+
+        let (start, end) =
+            self.region().filter(|(start, end)| start < end).unzip();
+        #[cfg(feature = "styling")]
+        let spans = match self.highlighter {
+            Some(highlighter) if !self.is_masked() => {
+                highlighter.highlight(&self.buffer, self.cursor)
+            }
+            _ => Vec::new(),
+        };
+        #[cfg(not(feature = "styling"))]
+        let spans: Vec<style::Span> = Vec::new();
+        let visible = self.visible();
+        for (index, (offset, grapheme)) in
+            visible.grapheme_indices(true).enumerate()
+        {
+            if grapheme == "\n" && !self.is_masked() {
+                continue;
+            }
+            let (row, column) = cells[index];
+            let mut pen = self.theme.input;
+            if let Some((_, span)) = spans
+                .iter()
+                .rev()
+                .find(|(range, _)| range.contains(&offset))
+            {
+                pen = style::merge(pen, *span);
+            }
+            if start.is_some_and(|start| index >= start)
+                && end.is_some_and(|end| index < end)
+            {
+                pen = style::merge(pen, self.theme.selection);
+            }
+            frame[row].push(Cell {
+                column,
+                width: self.cell_width(index, grapheme),
+                text: match grapheme {
+                    "\t" => self.tab_style.text(),
+                    _ => caret_notation(grapheme)
+                        .unwrap_or_else(|| grapheme.to_string()),
+                },
+                pen: Pen::new(pen),
+            });
+        }
+        self.push_status(&mut frame);
+        frame
+    }
+
+    /// Add the status line to a frame as a row below the value.
+    fn push_status(&self, frame: &mut Vec<Vec<Cell>>) {
+        if let Some(status) = &self.status {
+            let available = match self.size.0 as usize {
+                0 => usize::MAX,
+                width => width,
+            };
+            let line = status.lines().next().unwrap_or_default();
+            let text = fit(line, available);
+            frame.push(vec![Cell {
+                column: 0,
+                width: text_width(&text),
+                text,
+                pen: Pen::new(self.theme.status),
+            }]);
+        }
+    }
+
+    /// Column after the cells of a row of a frame.
+    fn row_end(&self, row: usize, cells: &[Cell]) -> usize {
+        match cells.last() {
+            Some(cell) => cell.column + cell.width,
+            None => match self.value_start() {
+                (start_row, column) if start_row == row => column,
+                _ => 0,
+            },
+        }
+    }
+
     /// Redraw the prefix and buffer moving the cursor
     /// to the given position.
-    pub fn refresh<W, S: AsRef<str>>(
+    ///
+    /// Only the cells that changed since the value was last
+    /// drawn are written; the region is drawn in reverse video
+    /// while the mark is active and the spans of the highlighter
+    /// are styled. When the rows no longer fit the terminal is
+    /// scrolled and the position moves up with the rows.
+    pub(crate) fn redraw<W>(
         &mut self,
         writer: &mut W,
-        buf: S,
         position: (u16, u16),
     ) -> Result<()>
     where
         W: Write,
     {
-        self.update(buf.as_ref().to_string());
-        self.redraw(writer, position)
+        // Rows added at the bottom of the terminal scroll it up
+        let scrolled = self.scroll_into_view(writer)?;
+        let position = (position.0, position.1.saturating_sub(scrolled));
+        let frame = self.frame();
+        let (start_row, start_column) = self.value_start();
+        let mut pen = Pen::default();
+        match self.drawn.take() {
+            Some(drawn) => {
+                for row in 0..frame.len().max(drawn.len()) {
+                    let y = self.first_row.saturating_add(row as u16);
+                    let (Some(old), Some(new)) =
+                        (drawn.get(row), frame.get(row))
+                    else {
+                        if let Some(new) = frame.get(row) {
+                            // A row the value did not cover before
+                            let column = self.row_end(row, &[]);
+                            writer.queue(cursor::MoveTo(column as u16, y))?;
+                            writer.queue(Clear(ClearType::UntilNewLine))?;
+                            pen.write(writer, new)?;
+                        } else {
+                            writer.queue(cursor::MoveTo(0, y))?;
+                            writer.queue(Clear(ClearType::CurrentLine))?;
+                        }
+                        continue;
+                    };
+                    if old == new {
+                        continue;
+                    }
+                    let changed = old
+                        .iter()
+                        .zip(new)
+                        .take_while(|(old, new)| old == new)
+                        .count();
+                    let column = match new.get(changed) {
+                        Some(cell) => cell.column,
+                        None => self.row_end(row, new),
+                    };
+                    writer.queue(cursor::MoveTo(column as u16, y))?;
+                    pen.write(writer, &new[changed..])?;
+                    if self.row_end(row, old) > self.row_end(row, new) {
+                        writer.queue(Clear(ClearType::UntilNewLine))?;
+                    }
+                }
+            }
+            None => {
+                writer.queue(cursor::MoveTo(0, self.first_row))?;
+                writer.queue(Clear(ClearType::CurrentLine))?;
+                style::write_styled(writer, &self.theme.prefix, self.prefix)?;
+                for (row, cells) in frame.iter().enumerate().skip(start_row) {
+                    if row > start_row {
+                        writer.write_all(b"\r\n")?;
+                        writer.queue(Clear(ClearType::CurrentLine))?;
+                    } else if row > 0 {
+                        // Leave the end of a prefix wider than a row
+                        writer.queue(cursor::MoveTo(
+                            start_column as u16,
+                            self.first_row.saturating_add(row as u16),
+                        ))?;
+                        writer.queue(Clear(ClearType::UntilNewLine))?;
+                    }
+                    pen.write(writer, cells)?;
+                }
+            }
+        }
+        self.drawn = Some(frame);
+        writer.queue(cursor::MoveTo(position.0, position.1))?;
+        Ok(())
     }
 
-    // Write a character to the line.
-    pub fn write_char<W>(&mut self, writer: &mut W, c: char) -> Result<()>
+    /// Set the buffer value and redraw moving the cursor
+    /// to the end of the value.
+    pub fn set_value<W>(&mut self, writer: &mut W, value: &str) -> Result<()>
     where
         W: Write,
     {
-        let graphemes = self.graphemes();
-
-        let (col, row) = self.position;
-        let pos = col as usize - self.prefix_cols;
-        let char_str = c.to_string();
+        let index = UnicodeSegmentation::graphemes(value, true).count();
+        self.replace(writer, value.to_string(), index)
+    }
 
-        // Appending to the end
-        let (before, after) = if pos as usize == self.buffer.len() {
-            (&graphemes[..], &graphemes[graphemes.len()..])
-        } else {
-            let before = &graphemes[0..pos as usize];
-            let after = &graphemes[pos as usize..];
-            (before, after)
-        };
+    /// Write a character to the line.
+    pub(crate) fn write_char<W>(
+        &mut self,
+        writer: &mut W,
+        c: char,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        self.write_str(writer, c.encode_utf8(&mut [0; 4]))
+    }
 
-        // Prepare new line buffer
-        let mut new_buf = String::new();
-        new_buf.push_str(&before.join(""));
-        new_buf.push_str(&char_str[..]);
-        new_buf.push_str(&after.join(""));
+    /// Write text to the line at the cursor with a single redraw.
+    pub(crate) fn write_str<W>(
+        &mut self,
+        writer: &mut W,
+        text: &str,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let (before, after) = self.split_at_cursor();
+        let mut new_buf = before;
+        new_buf.push_str(text);
+        let index = UnicodeSegmentation::graphemes(&new_buf[..], true).count();
+        new_buf.push_str(&after);
+        self.replace(writer, new_buf, index)
+    }
+}
 
-        // Store the updated buffer
-        self.update(new_buf);
+/// Style the value is being drawn with.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+struct Pen {
+    style: Option<ContentStyle>,
+}
 
-        let new_pos = ((self.prefix_cols + pos + 1) as u16, row);
-        self.redraw(writer, new_pos)?;
+impl Pen {
+    /// Pen for a style, no style for a plain style.
+    fn new(style: ContentStyle) -> Self {
+        Self {
+            style: (!style::is_plain(&style)).then_some(style),
+        }
+    }
 
+    /// Change to another style.
+    fn change<W: Write>(&mut self, writer: &mut W, next: Pen) -> Result<()> {
+        if next != *self {
+            if self.style.is_some() {
+                writer.queue(SetAttribute(Attribute::Reset))?;
+            }
+            if let Some(style) = &next.style {
+                style::queue_style(writer, style)?;
+            }
+            *self = next;
+        }
         Ok(())
     }
 
-    // Calculate the end position for a value.
-    pub fn end_pos(&self, value: &str) -> (u16, u16) {
-        let (_col, row) = self.position;
-        let (w, _h) = self.size;
-        let remainder = w as usize - self.prefix_cols;
-        // Fits without wrapping
-        if value.len() < remainder {
-            let len = UnicodeWidthStr::width(value);
-            let new_col = (self.prefix_cols + len) as u16;
-            (new_col, row)
-        } else {
-            todo!("calculate with long wrapped value");
+    /// Write cells of a row, leaving the text without a style.
+    fn write<W: Write>(
+        &mut self,
+        writer: &mut W,
+        cells: &[Cell],
+    ) -> Result<()> {
+        for cell in cells {
+            self.change(writer, cell.pen)?;
+            writer.write_all(cell.text.as_bytes())?;
         }
+        self.change(writer, Pen::default())
+    }
+}
+
+/// Graphemes at the start of text that are narrower than
+/// a number of columns.
+fn fit(text: &str, available: usize) -> String {
+    let mut columns = 0;
+    text.graphemes(true)
+        .take_while(|grapheme| {
+            columns += grapheme_width(grapheme);
+            columns < available
+        })
+        .collect()
+}
+
+/// Text drawn at a column of a row.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Cell {
+    column: usize,
+    width: usize,
+    text: String,
+    pen: Pen,
+}
+
+/// Grapheme index drawn at a column on a row of a layout,
+/// the last index on the row when the column is past its end.
+fn index_in_row(cells: &[(usize, usize)], row: usize, column: u16) -> usize {
+    let mut found = None;
+    for (index, &(cell_row, cell_column)) in cells.iter().enumerate() {
+        if cell_row == row {
+            if cell_column >= column as usize {
+                return index;
+            }
+            found = Some(index);
+        } else if cell_row > row {
+            break;
+        }
+    }
+    found.unwrap_or(cells.len() - 1)
+}
+
+/// Width of a string ignoring escape sequences, such as the
+/// sequences that style text or start and end a hyperlink.
+fn visible_width(value: &str) -> usize {
+    text_width(&visible_text(value))
+}
+
+/// Number of columns used to display text.
+pub(crate) fn text_width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+/// Number of columns used to display a grapheme.
+///
+/// Emoji sequences joined with a zero width joiner, emoji with
+/// a skin tone modifier or an emoji presentation selector and
+/// flags are drawn in two columns by terminals although the
+/// widths of their characters add up to more or less.
+fn grapheme_width(grapheme: &str) -> usize {
+    let mut chars = grapheme.chars();
+    // Letters such as Indic conjuncts may also use a joiner
+    let emoji = chars.next().is_some_and(|c| !c.is_alphanumeric())
+        && chars.any(|c| {
+            matches!(
+                c,
+                '\u{200D}'
+                    | '\u{FE0F}'
+                    | '\u{1F1E6}'..='\u{1F1FF}'
+                    | '\u{1F3FB}'..='\u{1F3FF}'
+            )
+        });
+    match emoji {
+        true => 2,
+        false => match caret_notation(grapheme) {
+            Some(text) => text.len(),
+            None => UnicodeWidthStr::width(grapheme),
+        },
+    }
+}
+
+/// Text drawn for a control character such as a quoted Esc,
+/// which would otherwise be interpreted by the terminal.
+///
+/// Control characters are drawn in caret notation like `^[`
+/// and those above DEL with a `M-` prefix like `M-^[`; tabs
+/// and line breaks are drawn by the buffer.
+fn caret_notation(grapheme: &str) -> Option<String> {
+    let mut chars = grapheme.chars();
+    let c = chars.next().filter(|_| chars.next().is_none())?;
+    let caret = |c: u8| format!("^{}", (c ^ 0x40) as char);
+    match c {
+        '\t' | '\n' => None,
+        '\0'..='\x1F' | '\x7F' => Some(caret(c as u8)),
+        '\u{80}'..='\u{9F}' => Some(format!("M-{}", caret(c as u8 - 0x80))),
+        _ => None,
+    }
+}
+
+/// Text of a string without escape sequences.
+fn visible_text(value: &str) -> String {
+    let mut text = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find(['\x1B', '\u{9B}']) {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        rest = &rest[escape_len(rest)..];
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Length in bytes of the escape sequence at the start of text,
+/// a sequence that is not terminated runs to the end of the text.
+fn escape_len(text: &str) -> usize {
+    // Control sequences end with a byte from `@` to `~`
+    let control = |start: usize| {
+        text[start..]
+            .find(|c| ('@'..='~').contains(&c))
+            .map_or(text.len(), |index| start + index + 1)
+    };
+    if text.starts_with('\u{9B}') {
+        return control('\u{9B}'.len_utf8());
+    }
+    match text[1..].chars().next() {
+        Some('[') => control(2),
+        // Commands and strings end with BEL or the string terminator
+        Some(']' | 'P' | 'X' | '^' | '_') => [("\x07", 1), ("\x1B\\", 2)]
+            .iter()
+            .filter_map(|(terminator, len)| {
+                text[2..].find(terminator).map(|index| 2 + index + len)
+            })
+            .min()
+            .unwrap_or(text.len()),
+        // Intermediate bytes then a final byte, such as `ESC ( B`
+        Some(' '..='/') => text[1..]
+            .find(|c| ('0'..='~').contains(&c))
+            .map_or(text.len(), |index| 1 + index + 1),
+        // A single final byte, such as `ESC 7`
+        Some('0'..='~') => 2,
+        _ => 1,
+    }
+}
+
+impl From<TerminalBuffer<'_>> for String {
+    fn from(buf: TerminalBuffer<'_>) -> Self {
+        buf.buffer
     }
 }
 
-impl Into<String> for TerminalBuffer<'_> {
-    fn into(self) -> String {
-        self.buffer
+/// Determine if a word boundary segment is a word.
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl TerminalBuffer<'_> {
+        /// Set the cursor position as if reported by the terminal.
+        ///
+        /// The cursor moves to the column on the row it is on and
+        /// the rows of the value follow the row.
+        fn set_position(&mut self, position: (u16, u16)) {
+            let cells = self.layout(&self.buffer);
+            let row = self.cursor_row();
+            self.cursor = index_in_row(&cells, row, position.0);
+            self.first_row = position.1.saturating_sub(row as u16);
+            self.position = position;
+        }
+    }
+
+    fn typed<'a>(prefix: &'a str, value: &str) -> TerminalBuffer<'a> {
+        let mut buf = TerminalBuffer::new(prefix, None);
+        buf.set_size((80, 24));
+        buf.set_position((buf.prefix_columns() as u16, 0));
+        let mut out = Vec::new();
+        for c in value.chars() {
+            buf.write_char(&mut out, c).unwrap();
+        }
+        buf
+    }
+
+    #[test]
+    fn hyperlink_prefix() {
+        let prefix = "\x1B]8;;https://example.com\x1B\\Port\x1B]8;;\x1B\\: ";
+        let buf = TerminalBuffer::new(prefix, None);
+        assert_eq!(6, buf.prefix_columns());
+    }
+
+    #[test]
+    fn escaped_prefix_width() {
+        assert_eq!(2, visible_width("\x1B]0;title\x07ab"));
+        assert_eq!(2, visible_width("\x1B[1m\x1B[38;5;2m>\x1B[0m "));
+        assert_eq!(2, visible_width("\x1B[32m>\x1B(B\x1B[m "));
+        assert_eq!(2, visible_width("\x1B7> \x1B8"));
+        assert_eq!(2, visible_width("\x1BPq#0\x1B\\> "));
+        assert_eq!(2, visible_width("\u{9B}1m> \u{9B}0m"));
+        assert_eq!(2, visible_width("> \x1B[38;5"));
+
+        // The cursor follows the visible columns of a colored prefix
+        let mut out = Vec::new();
+        let mut buf = typed("\x1B[1;32m>\x1B[0m ", "abc");
+        assert_eq!(2, buf.prefix_columns());
+        assert_eq!((5, 0), buf.position());
+        buf.move_to(&mut out, 0).unwrap();
+        assert_eq!((2, 0), buf.position());
+
+        buf.set_size((5, 24));
+        assert_eq!((4, 0), buf.position_of(2));
+        assert_eq!((0, 1), buf.position_of(3));
+    }
+
+    #[test]
+    fn wide_prefix_editing() {
+        let mut out = Vec::new();
+        let mut buf = typed("日本語> ", "a語b");
+        assert_eq!(8, buf.prefix_columns());
+        assert_eq!((12, 0), buf.position());
+        assert_eq!(3, buf.cursor());
+        assert_eq!((12, 0), buf.position_of(buf.len()));
+
+        buf.set_position(buf.position_of(1));
+        assert_eq!((9, 0), buf.position());
+        buf.write_char(&mut out, 'x').unwrap();
+        assert_eq!("ax語b", buf.buffer());
+        assert_eq!(2, buf.cursor());
+
+        buf.erase_after(&mut out, 1).unwrap();
+        assert_eq!("axb", buf.buffer());
+        buf.erase_before(&mut out, 5).unwrap();
+        assert_eq!("b", buf.buffer());
+        assert_eq!((8, 0), buf.position());
+        buf.erase_before(&mut out, 1).unwrap();
+        assert_eq!("b", buf.buffer());
+    }
+
+    #[test]
+    fn masked_typed_character() {
+        let mut out = Vec::new();
+        let mut buf = TerminalBuffer::new("> ", Some('*'));
+        buf.set_size((80, 24));
+        buf.set_position((2, 0));
+        buf.write_str(&mut out, "a日").unwrap();
+        buf.set_shown(Some(1));
+        assert_eq!("*日", buf.visible());
+        assert_eq!(3, buf.buffer_columns());
+        assert_eq!((5, 0), buf.position());
+
+        out.clear();
+        buf.redraw(&mut out, buf.position()).unwrap();
+        assert!(String::from_utf8_lossy(&out).starts_with("\x1B[1;4H日"));
+
+        // The character is masked before leaving the prompt
+        out.clear();
+        buf.move_to_end(&mut out).unwrap();
+        assert_eq!(None, buf.shown());
+        assert_eq!("**", buf.visible());
+        assert!(String::from_utf8_lossy(&out).contains('*'));
+    }
+
+    #[test]
+    fn tab_styles() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "");
+        buf.write_str(&mut out, "a\tb").unwrap();
+        assert_eq!(8, buf.columns());
+        assert_eq!((8, 0), buf.position());
+        assert!(String::from_utf8_lossy(&out).contains("a    b"));
+
+        out.clear();
+        buf.set_tab_style(TabStyle::Caret);
+        buf.move_to(&mut out, 2).unwrap();
+        assert_eq!((5, 0), buf.position());
+        buf.redraw(&mut out, buf.position()).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains("^Ib"));
+        assert_eq!(6, buf.columns());
+    }
+
+    #[test]
+    fn control_characters() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "a");
+        buf.write_str(&mut out, "\x1B\r\x07b").unwrap();
+        assert_eq!(10, buf.columns());
+        assert_eq!((10, 0), buf.position());
+        let drawn = String::from_utf8(out).unwrap();
+        assert!(drawn.contains("^[^M^Gb"));
+        assert!(!drawn.contains('\r'));
+        assert!(!drawn.contains('\x07'));
+
+        assert_eq!(Some("^?".to_string()), caret_notation("\x7F"));
+        assert_eq!(Some("M-^[".to_string()), caret_notation("\u{9B}"));
+        assert_eq!(None, caret_notation("\t"));
+        assert_eq!(None, caret_notation("é"));
+    }
+
+    #[test]
+    fn masked_kill_and_yank() {
+        let mut out = Vec::new();
+        let mut buf = TerminalBuffer::new("> ", Some('*'));
+        buf.set_size((80, 24));
+        buf.set_position((2, 0));
+        for c in "pa日ss".chars() {
+            buf.write_char(&mut out, c).unwrap();
+        }
+        assert_eq!("*****", buf.visible());
+        assert_eq!(5, buf.buffer_columns());
+        assert_eq!((7, 0), buf.position());
+
+        buf.set_position(buf.position_of(2));
+        assert_eq!((4, 0), buf.position());
+        buf.kill_after(&mut out).unwrap();
+        assert_eq!("pa", buf.buffer());
+        assert_eq!("**", buf.visible());
+        assert_eq!((4, 0), buf.position());
+
+        buf.set_position(buf.position_of(1));
+        buf.kill_before(&mut out).unwrap();
+        assert_eq!("a", buf.buffer());
+        assert_eq!((2, 0), buf.position());
+
+        buf.yank(&mut out).unwrap();
+        assert_eq!("pa", buf.buffer());
+        assert_eq!((3, 0), buf.position());
+
+        buf.set_revealed(true);
+        buf.set_position(buf.position_of(buf.len()));
+        buf.yank(&mut out).unwrap();
+        assert_eq!("pap", buf.buffer());
+        assert_eq!(3, buf.buffer_columns());
+        buf.set_revealed(false);
+        assert_eq!("***", buf.visible());
+    }
+
+    #[test]
+    fn kill_whole_line() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "echo 語");
+        buf.set_position(buf.position_of(2));
+        buf.kill_line(&mut out).unwrap();
+        assert_eq!("", buf.buffer());
+        assert_eq!((2, 0), buf.position());
+        buf.yank(&mut out).unwrap();
+        assert_eq!("echo 語", buf.buffer());
+        assert_eq!(6, buf.cursor());
+    }
+
+    #[test]
+    fn mark_and_region() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "git 語 commit");
+        buf.set_position(buf.position_of(4));
+        buf.set_mark();
+        buf.set_position(buf.position_of(6));
+        assert_eq!(Some((4, 6)), buf.region());
+        buf.exchange_point_and_mark(&mut out).unwrap();
+        assert_eq!(4, buf.cursor());
+        assert_eq!(Some(6), buf.mark());
+
+        buf.copy_region(&mut out).unwrap();
+        assert_eq!(None, buf.region());
+        buf.set_mark();
+        buf.set_position(buf.position_of(0));
+        buf.kill_region(&mut out).unwrap();
+        assert_eq!("語 commit", buf.buffer());
+        assert_eq!(None, buf.mark());
+        buf.yank(&mut out).unwrap();
+        assert_eq!("git 語 commit", buf.buffer());
+    }
+
+    #[test]
+    fn word_case() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "straße ÉCOLE mIxed");
+        buf.set_position(buf.position_of(0));
+        buf.change_word_case(&mut out, WordCase::Upper).unwrap();
+        assert_eq!("STRASSE ÉCOLE mIxed", buf.buffer());
+        assert_eq!(7, buf.cursor());
+        buf.change_word_case(&mut out, WordCase::Lower).unwrap();
+        assert_eq!("STRASSE école mIxed", buf.buffer());
+        buf.change_word_case(&mut out, WordCase::Capital).unwrap();
+        assert_eq!("STRASSE école Mixed", buf.buffer());
+        assert_eq!(19, buf.cursor());
+        buf.change_word_case(&mut out, WordCase::Upper).unwrap();
+        assert_eq!(19, buf.cursor());
+    }
+
+    #[test]
+    fn wide_prefix_erase_word() {
+        let mut out = Vec::new();
+        let mut buf = typed("語> ", "foo bär baz");
+        buf.set_position(buf.position_of(7));
+        buf.erase_word_before(&mut out).unwrap();
+        assert_eq!("foo  baz", buf.buffer());
+        assert_eq!(4, buf.cursor());
+        assert_eq!((8, 0), buf.position());
+    }
+
+    #[test]
+    fn word_motion_and_kill() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "foo-bär  baz");
+        buf.set_position(buf.position_of(0));
+        assert_eq!(3, buf.next_word_end());
+        buf.set_position(buf.position_of(3));
+        assert_eq!(7, buf.next_word_end());
+        assert_eq!(0, buf.previous_word_start());
+        buf.set_position(buf.position_of(9));
+        assert_eq!(4, buf.previous_word_start());
+        assert_eq!(12, buf.next_word_end());
+
+        buf.set_position(buf.position_of(3));
+        buf.erase_word_after(&mut out).unwrap();
+        assert_eq!("foo  baz", buf.buffer());
+        assert_eq!(3, buf.cursor());
+        buf.yank(&mut out).unwrap();
+        assert_eq!("foo-bär  baz", buf.buffer());
+    }
+
+    #[test]
+    fn multiline_value() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "x");
+        buf.set_position((3, 22));
+        buf.set_value(&mut out, "if true\n  echo 語\nfi").unwrap();
+        assert_eq!((2, 23), buf.position());
+        assert_eq!((2, 21), buf.position_of(0));
+        assert_eq!((9, 21), buf.position_of(7));
+        assert_eq!((0, 22), buf.position_of(8));
+        assert_eq!(19, buf.cursor());
+        assert_eq!(17, buf.index_at_column(0));
+        let drawn = String::from_utf8_lossy(&out);
+        assert!(drawn.contains(
+            "\x1B[22;3Hif true\x1B[23;1H\x1B[K  echo 語\x1B[24;1H\x1B[Kfi"
+        ));
+
+        // Only the changed cells are drawn and the rows left
+        // over are cleared
+        out.clear();
+        buf.set_value(&mut out, "ls").unwrap();
+        assert_eq!((4, 21), buf.position());
+        assert_eq!(
+            "\x1B[22;3Hls\x1B[K\x1B[23;1H\x1B[2K\x1B[24;1H\x1B[2K\x1B[22;5H",
+            String::from_utf8_lossy(&out)
+        );
+
+        out.clear();
+        buf.set_value(&mut out, "lsof").unwrap();
+        assert_eq!("\x1B[22;5Hof\x1B[22;7H", String::from_utf8_lossy(&out));
+    }
+
+    #[test]
+    fn wrapped_value() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "");
+        buf.set_size((10, 24));
+        buf.set_value(&mut out, "abcdefgh語ijk").unwrap();
+        assert_eq!(2, buf.rows());
+        assert_eq!((9, 0), buf.position_of(7));
+        assert_eq!((0, 1), buf.position_of(8));
+        assert_eq!((5, 1), buf.position());
+
+        buf.move_to(&mut out, 3).unwrap();
+        assert_eq!((5, 0), buf.position());
+        buf.move_to(&mut out, 11).unwrap();
+        buf.set_position((1, 1));
+        assert_eq!(9, buf.cursor());
+        buf.set_position((8, 1));
+        assert_eq!(12, buf.cursor());
+
+        // The terminal scrolled by a row
+        buf.move_to_row(5);
+        buf.set_position((8, 5));
+        assert_eq!((2, 4), buf.position_of(0));
+
+        buf.set_value(&mut out, "abcdefgh").unwrap();
+        assert_eq!((0, 5), buf.position());
+        buf.set_value(&mut out, "ab").unwrap();
+        assert_eq!(1, buf.rows());
+    }
+
+    #[cfg(feature = "styling")]
+    #[test]
+    fn highlighted_value() {
+        use crossterm::style::Stylize;
+
+        let highlighter = |line: &str, _| {
+            let end = line.find(' ').unwrap_or(line.len());
+            vec![(0..end, ContentStyle::new().green())]
+        };
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "");
+        buf.set_highlighter(Some(&highlighter));
+        buf.set_value(&mut out, "ls -l").unwrap();
+        let drawn = String::from_utf8_lossy(&out);
+        assert!(drawn.ends_with("> \x1B[38;5;10mls\x1B[0m -l\x1B[1;8H"));
+
+        out.clear();
+        let mut buf = TerminalBuffer::new("> ", Some('*'));
+        buf.set_highlighter(Some(&highlighter));
+        buf.set_value(&mut out, "secret").unwrap();
+        assert!(!String::from_utf8_lossy(&out).contains("\x1B[0m"));
+    }
+
+    #[test]
+    fn placeholder_hint() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "");
+        buf.set_size((10, 24));
+        buf.set_placeholder(Some("user@host"));
+        buf.write_prefix(&mut out).unwrap();
+        assert_eq!(
+            "> \x1B[2muser@ho\x1B[0m\x1B[7D",
+            String::from_utf8_lossy(&out)
+        );
+
+        out.clear();
+        buf.move_to_end(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).starts_with("\x1B[K"));
+
+        out.clear();
+        buf.write_char(&mut out, 'a').unwrap();
+        assert!(!String::from_utf8_lossy(&out).contains("user"));
+        assert_eq!("a", buf.buffer());
+    }
+
+    #[test]
+    fn status_line() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "ab");
+        buf.set_position((4, 23));
+        buf.set_status(&mut out, Some("2 chars".to_string()))
+            .unwrap();
+        assert_eq!(1, buf.rows_below());
+        assert_eq!((4, 22), buf.position());
+        assert_eq!(
+            "\x1B[1S\x1B[24;1H\x1B[K\x1B[2m2 chars\x1B[0m\x1B[23;5H",
+            String::from_utf8_lossy(&out)
+        );
+
+        out.clear();
+        buf.write_char(&mut out, 'c').unwrap();
+        buf.set_status(&mut out, Some("3 chars".to_string()))
+            .unwrap();
+        assert!(String::from_utf8_lossy(&out)
+            .ends_with("\x1B[24;1H\x1B[2m3 chars\x1B[0m\x1B[23;6H"));
+
+        out.clear();
+        buf.move_to_end(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains("\x1B[2K"));
+        assert_eq!(0, buf.rows_below());
+    }
+
+    #[test]
+    fn scroll_at_bottom() {
+        let mut out = Vec::new();
+        let mut buf = TerminalBuffer::new("> ", Some('*'));
+        buf.set_size((10, 24));
+        buf.set_position((2, 23));
+        buf.write_str(&mut out, "日日日日").unwrap();
+        assert_eq!((6, 23), buf.position());
+
+        // Revealing the value adds a row below the last row
+        out.clear();
+        buf.set_revealed(true);
+        buf.redraw(&mut out, buf.position()).unwrap();
+        assert!(String::from_utf8_lossy(&out).starts_with("\x1B[1S"));
+        assert_eq!((0, 23), buf.position());
+        assert_eq!((2, 22), buf.position_of(0));
+    }
+
+    #[test]
+    fn print_above_prompt() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "ab");
+        buf.set_position((4, 22));
+        buf.print_above(&mut out, "first\r\nsecond\n").unwrap();
+        let drawn = String::from_utf8_lossy(&out);
+        assert!(drawn.starts_with("\x1B[23;1H\x1B[Jfirst\r\nsecond\r\n"));
+        assert!(drawn.ends_with("> ab\x1B[24;5H"));
+        assert_eq!((4, 23), buf.position());
+    }
+
+    #[test]
+    fn collapse_prompt() {
+        let mut out = Vec::new();
+        let mut buf = typed("user@host:~/src $ ", "ls");
+        buf.set_position((20, 5));
+        let position = buf.collapse(&mut out, "❯ ").unwrap();
+        assert_eq!((4, 5), position);
+        let drawn = String::from_utf8_lossy(&out);
+        assert!(drawn.starts_with("\x1B[6;1H\x1B[J"));
+        assert!(drawn.contains("❯ ls"));
+        assert_eq!("ls", buf.buffer());
+    }
+
+    #[test]
+    fn resize_reflow() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "abcdefghijkl");
+        buf.set_position((14, 3));
+        buf.resize(&mut out, (10, 24), (4, 4)).unwrap();
+        assert_eq!(2, buf.rows());
+        assert_eq!((4, 4), buf.position());
+        assert_eq!((2, 3), buf.position_of(0));
+        let drawn = String::from_utf8_lossy(&out);
+        assert!(drawn.starts_with("\x1B[4;1H\x1B[J"));
+
+        out.clear();
+        buf.resize(&mut out, (80, 24), (14, 3)).unwrap();
+        assert_eq!(1, buf.rows());
+        assert_eq!((14, 3), buf.position());
+        // The value may still start on the row above when the
+        // terminal did not reflow it
+        let drawn = String::from_utf8_lossy(&out);
+        assert!(drawn.starts_with("\x1B[3;1H\x1B[J"));
+    }
+
+    #[test]
+    fn wide_graphemes_wrap() {
+        let mut buf = TerminalBuffer::new("ab日", None);
+        buf.set_size((3, 24));
+        buf.update("c".to_string());
+        // The wide grapheme of the prefix moves to the next row
+        assert_eq!((2, 1), buf.position_of(0));
+        assert_eq!(3, buf.rows());
+
+        let mut buf = TerminalBuffer::new("> ", None);
+        buf.set_size((4, 24));
+        buf.update("a日本".to_string());
+        assert_eq!((2, 0), buf.position_of(0));
+        assert_eq!((0, 1), buf.position_of(1));
+        assert_eq!((2, 1), buf.position_of(2));
+        assert_eq!((0, 2), buf.position_of(3));
+        // A column inside a wide grapheme is never a cursor position
+        buf.move_to(&mut Vec::new(), 1).unwrap();
+        buf.set_position((1, 1));
+        assert_eq!(2, buf.cursor());
+    }
+
+    #[test]
+    fn emoji_graphemes() {
+        let family = "👨\u{200D}👩\u{200D}👧";
+        let mut out = Vec::new();
+        let mut buf = typed("> ", &format!("{}👍🏽🇺🇸❤\u{FE0F}", family));
+        assert_eq!(4, buf.len());
+        assert_eq!(8, buf.buffer_columns());
+        assert_eq!((10, 0), buf.position());
+
+        buf.move_to(&mut out, 1).unwrap();
+        assert_eq!((4, 0), buf.position());
+        buf.erase_before(&mut out, 1).unwrap();
+        buf.erase_after(&mut out, 1).unwrap();
+        assert_eq!("🇺🇸❤\u{FE0F}", buf.buffer());
+
+        let mut buf = TerminalBuffer::new("> ", Some('*'));
+        buf.update(family.to_string());
+        assert_eq!(1, buf.buffer_columns());
+    }
+
+    #[test]
+    fn cursor_index_and_columns() {
+        let mut buf = TerminalBuffer::new("> ", None);
+        buf.update("a日本e\u{301}z".to_string());
+        assert_eq!(5, buf.len());
+        assert_eq!(7, buf.buffer_columns());
+
+        assert_eq!(0, buf.index_at_column(0));
+        assert_eq!(0, buf.index_at_column(2));
+        assert_eq!(1, buf.index_at_column(3));
+        assert_eq!(2, buf.index_at_column(4));
+        assert_eq!(2, buf.index_at_column(5));
+        assert_eq!(4, buf.index_at_column(8));
+        assert_eq!(5, buf.index_at_column(20));
+
+        assert_eq!(2, buf.column_of(0));
+        assert_eq!(5, buf.column_of(2));
+        assert_eq!(8, buf.column_of(4));
+        assert_eq!(9, buf.column_of(10));
+
+        buf.set_position((7, 3));
+        assert_eq!(3, buf.cursor());
+        assert_eq!((7, 3), buf.position_of(buf.cursor()));
+        let (before, after) = buf.split_at_cursor();
+        assert_eq!("a日本", before);
+        assert_eq!("e\u{301}z", after);
+    }
+
+    #[test]
+    fn diff_redraw() {
+        let mut buf = typed("> ", "abc");
+        let mut out = Vec::new();
+        let mut drawn = |buf: &mut TerminalBuffer<'_>| {
+            let position = buf.position_of(buf.cursor);
+            buf.redraw(&mut out, position).unwrap();
+            String::from_utf8(std::mem::take(&mut out)).unwrap()
+        };
+
+        // Only the cells after the first change are written
+        buf.update("abcd".to_string());
+        buf.cursor = 4;
+        assert_eq!("\x1B[1;6Hd\x1B[1;7H", drawn(&mut buf));
+        buf.update("axbcd".to_string());
+        buf.cursor = 2;
+        assert_eq!("\x1B[1;4Hxbcd\x1B[1;5H", drawn(&mut buf));
+
+        // Nothing changed so only the cursor moves
+        assert_eq!("\x1B[1;5H", drawn(&mut buf));
+
+        // Cells left over from a longer value are cleared
+        buf.update("abcd".to_string());
+        buf.cursor = 1;
+        assert_eq!("\x1B[1;4Hbcd\x1B[K\x1B[1;4H", drawn(&mut buf));
+
+        // Moving to another row draws the whole prompt again
+        buf.move_to_row(0);
+        assert_eq!("\x1B[1;1H\x1B[2K> abcd\x1B[1;4H", drawn(&mut buf));
+    }
+
+    #[test]
+    fn edit_between_graphemes() {
+        let mut out = Vec::new();
+        let mut buf = typed("> ", "a日e\u{301}z");
+        buf.move_to(&mut out, 2).unwrap();
+        assert_eq!((5, 0), buf.position());
+        buf.write_char(&mut out, 'é').unwrap();
+        assert_eq!("a日ée\u{301}z", buf.buffer());
+        assert_eq!((6, 0), buf.position());
+
+        buf.erase_before(&mut out, 2).unwrap();
+        assert_eq!("ae\u{301}z", buf.buffer());
+        assert_eq!((3, 0), buf.position());
+        buf.erase_after(&mut out, 1).unwrap();
+        assert_eq!("az", buf.buffer());
+        assert_eq!(4, buf.columns());
     }
 }
@@ -0,0 +1,64 @@
+//! Terminal window title.
+//!
+//! The title is saved on the terminal title stack before the
+//! prompt changes it and restored from the stack afterwards,
+//! terminals that do not support the stack ignore the sequences.
+use anyhow::Result;
+use crossterm::{terminal::SetTitle, QueueableCommand};
+use std::io::Write;
+
+/// Push the current title on to the title stack.
+pub(crate) fn save<W: Write>(writer: &mut W) -> Result<()> {
+    writer.write_all(b"\x1B[22;0t")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Pop the saved title from the title stack.
+pub(crate) fn restore<W: Write>(writer: &mut W) -> Result<()> {
+    writer.write_all(b"\x1B[23;0t")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Title of the terminal window while a prompt is active.
+#[derive(Default)]
+pub(crate) struct Title {
+    current: Option<String>,
+}
+
+impl Title {
+    /// Set the title when it differs from the current title.
+    pub fn update<W: Write>(
+        &mut self,
+        writer: &mut W,
+        title: String,
+    ) -> Result<()> {
+        if self.current.as_ref() != Some(&title) {
+            writer.queue(SetTitle(&title))?;
+            self.current = Some(title);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_update() -> Result<()> {
+        let mut writer = Vec::new();
+        let mut title = Title::default();
+        save(&mut writer)?;
+        title.update(&mut writer, "~/src".to_string())?;
+        title.update(&mut writer, "~/src".to_string())?;
+        title.update(&mut writer, "make".to_string())?;
+        restore(&mut writer)?;
+        assert_eq!(
+            "\x1B[22;0t\x1B]0;~/src\x07\x1B]0;make\x07\x1B[23;0t",
+            String::from_utf8(writer)?
+        );
+        Ok(())
+    }
+}
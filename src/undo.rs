@@ -0,0 +1,97 @@
+//! Undoing and redoing changes to the line.
+
+/// Largest number of changes that can be undone.
+const MAX_UNDO: usize = 1000;
+
+/// Line and cursor before or after a change.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct Snapshot {
+    pub line: String,
+    pub cursor: usize,
+}
+
+/// Changes to the line that can be undone and redone.
+#[derive(Debug, Default)]
+pub(crate) struct UndoStack {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    current: Snapshot,
+    typing: bool,
+}
+
+impl UndoStack {
+    /// Record the line after a key was handled.
+    ///
+    /// Characters typed one after another are undone together.
+    pub fn record(&mut self, line: &str, cursor: usize, typing: bool) {
+        if line == self.current.line {
+            if cursor != self.current.cursor {
+                // Moving the cursor ends a run of typed characters
+                self.current.cursor = cursor;
+                self.typing = false;
+            }
+            return;
+        }
+        if !(typing && self.typing) {
+            if self.undo.len() == MAX_UNDO {
+                self.undo.remove(0);
+            }
+            self.undo.push(self.current.clone());
+        }
+        self.redo.clear();
+        self.current = Snapshot {
+            line: line.to_string(),
+            cursor,
+        };
+        self.typing = typing;
+    }
+
+    /// Line to show to undo the last change.
+    pub fn undo(&mut self) -> Option<Snapshot> {
+        let previous = self.undo.pop()?;
+        self.redo
+            .push(std::mem::replace(&mut self.current, previous.clone()));
+        self.typing = false;
+        Some(previous)
+    }
+
+    /// Line to show to redo the last undone change.
+    pub fn redo(&mut self) -> Option<Snapshot> {
+        let next = self.redo.pop()?;
+        self.undo
+            .push(std::mem::replace(&mut self.current, next.clone()));
+        self.typing = false;
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(line: &str, cursor: usize) -> Option<Snapshot> {
+        Some(Snapshot {
+            line: line.to_string(),
+            cursor,
+        })
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut stack = UndoStack::default();
+        stack.record("a", 1, true);
+        stack.record("ab", 2, true);
+        stack.record("ab", 0, false);
+        stack.record("", 0, false);
+        assert_eq!(snapshot("ab", 0), stack.undo());
+        assert_eq!(snapshot("", 0), stack.undo());
+        assert_eq!(None, stack.undo());
+
+        assert_eq!(snapshot("ab", 0), stack.redo());
+        stack.record("ab", 0, false);
+        stack.record("xab", 1, true);
+        assert_eq!(None, stack.redo());
+        assert_eq!(snapshot("ab", 0), stack.undo());
+        assert_eq!(snapshot("", 0), stack.undo());
+    }
+}